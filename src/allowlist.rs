@@ -0,0 +1,87 @@
+use std::{collections::BTreeSet, fs, path::Path};
+
+use crate::{RenderedResource, Resource};
+
+/// The form `Builder::path_allowlist` writes the paths in, for whichever proxy is in front
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Allowlist {
+	/// One path per line, with passed-through prefixes ending in `*`
+	Paths,
+	/// A `map` (for the `http` block) setting `$static_builder_allowed` to 1 for allowed URIs
+	Nginx,
+	/// A `@static_builder_allowed` named matcher
+	Caddy,
+	/// `static_builder_allowed` ACL lines
+	Haproxy,
+}
+
+// Quoted (with `\` escapes) where `path` has anything a config tokenizer would split it at
+fn quoted(path: &str) -> String {
+	match path.contains(|c: char| c.is_whitespace() || matches!(c, '"' | '\\' | '#' | ';' | '{' | '}')) {
+		true => format!("\"{}\"", path.replace('\\', "\\\\").replace('"', "\\\"")),
+		false => path.to_string(),
+	}
+}
+
+fn regex_escaped(prefix: &str) -> String {
+	prefix.chars().fold(String::new(), |mut escaped, c| {
+		if "\\.+*?()|[]{}^$".contains(c) {
+			escaped.push('\\');
+		}
+		escaped.push(c);
+		escaped
+	})
+}
+
+fn render(format: Allowlist, paths: &BTreeSet<&str>, prefixes: &[String]) -> String {
+	let header = "# Generated by static-builder: every path served, and every prefix passed through to the application\n";
+
+	match format {
+		Allowlist::Paths => paths.iter().map(|path| format!("{path}\n")).chain(prefixes.iter().map(|prefix| format!("{prefix}*\n"))).collect(),
+		Allowlist::Nginx => {
+			let mut out = format!("{header}# e.g. `if ($static_builder_allowed = 0) {{ return 404; }}` in the server block\nmap $uri $static_builder_allowed {{\n\tdefault 0;\n");
+			for path in paths {
+				out.push_str(&format!("\t{} 1;\n", quoted(path)));
+			}
+			for prefix in prefixes {
+				out.push_str(&format!("\t\"~^{}\" 1;\n", regex_escaped(prefix).replace('"', "\\\"")));
+			}
+			out + "}\n"
+		},
+		Allowlist::Caddy => {
+			let mut out = format!("{header}# e.g. `handle @static_builder_allowed {{ reverse_proxy ... }}` then `respond 404`\n@static_builder_allowed {{\n");
+			for path in paths {
+				out.push_str(&format!("\tpath {}\n", quoted(path)));
+			}
+			for prefix in prefixes {
+				out.push_str(&format!("\tpath {}\n", quoted(&format!("{prefix}*"))));
+			}
+			out + "}\n"
+		},
+		Allowlist::Haproxy => {
+			let mut out = format!("{header}# e.g. `http-request deny deny_status 404 unless static_builder_allowed`\n");
+			let escaped = |path: &str| path.chars().fold(String::new(), |mut escaped, c| {
+				if c.is_whitespace() || matches!(c, '\\' | '"' | '\'' | '#') {
+					escaped.push('\\');
+				}
+				escaped.push(c);
+				escaped
+			});
+			for path in paths {
+				out.push_str(&format!("acl static_builder_allowed path {}\n", escaped(path)));
+			}
+			for prefix in prefixes {
+				out.push_str(&format!("acl static_builder_allowed path_beg {}\n", escaped(prefix)));
+			}
+			out
+		},
+	}
+}
+
+/// Writes every path any set serves (or redirects from), and the passed-through `prefixes`, to
+/// `file` in `format`
+pub(crate) fn write<'a, I>(file: &Path, format: Allowlist, sets: I, prefixes: &[String]) -> Result<(), String> where I: IntoIterator<Item = &'a [(Resource, RenderedResource)]> {
+	let paths: BTreeSet<&str> = sets.into_iter().flatten().flat_map(|(_, page)| page.paths.iter().chain(&page.redirects)).map(String::as_str).collect();
+
+	fs::write(file, render(format, &paths, prefixes)).map_err(|e| format!("failed to write {}: {e}", file.display()))
+}
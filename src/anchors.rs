@@ -0,0 +1,115 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::RenderedResource;
+
+// Every (double- or single-quoted) value of `attr` on any element in `html`
+pub(crate) fn attr_values<'a>(html: &'a str, attr: &str) -> Vec<&'a str> {
+	let mut values = vec![];
+
+	for quote in ['"', '\''] {
+		let prefix = format!("{attr}={quote}");
+		let mut rest = html;
+
+		while let Some(i) = rest.find(&prefix) {
+			let preceded = rest[..i].chars().next_back().is_some_and(char::is_whitespace);
+			rest = &rest[i + prefix.len()..];
+
+			let end = rest.find(quote).unwrap_or(rest.len());
+			if preceded {
+				values.push(&rest[..end]);
+			}
+			rest = &rest[end..];
+		}
+	}
+
+	values
+}
+
+// Fragments as they'd be matched against ids, less %-encoding and the entities HTML escaping adds
+fn decode(fragment: &str) -> String {
+	let fragment = fragment.replace("&amp;", "&").replace("&quot;", "\"").replace("&#39;", "'");
+	let bytes = fragment.as_bytes();
+	let mut decoded = vec![];
+	let mut i = 0;
+
+	while i < bytes.len() {
+		match (bytes[i], fragment.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(h, 16).ok())) {
+			(b'%', Some(byte)) => {
+				decoded.push(byte);
+				i += 3;
+			},
+			(byte, _) => {
+				decoded.push(byte);
+				i += 1;
+			},
+		}
+	}
+
+	String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// The site path `href` on the page at `page` points at, unless it's off site
+pub(crate) fn resolve(page: &str, href: &str) -> Option<String> {
+	let has_scheme = href.find(':').is_some_and(|colon| !href[..colon].contains(['/', '?', '#']));
+	if has_scheme || href.starts_with("//") {
+		return None;
+	}
+
+	let path = href.split(['?', '#']).next().unwrap_or_default();
+	let joined = if path.is_empty() {
+		page.to_string()
+	} else if path.starts_with('/') {
+		path.to_string()
+	} else {
+		format!("{}{path}", &page[..page.rfind('/').map(|i| i + 1).unwrap_or(0)])
+	};
+
+	let mut segments: Vec<&str> = vec![];
+	for segment in joined.split('/').skip(1) {
+		match segment {
+			".." => { segments.pop(); },
+			"." => (),
+			s => segments.push(s),
+		}
+	}
+
+	Some(format!("/{}", segments.join("/")))
+}
+
+/// Describes every link from an HTML page to a fragment of a page here (itself included) that has
+/// no element with that id
+pub(crate) fn broken<'a, I>(pages: I) -> Vec<String> where I: IntoIterator<Item = &'a RenderedResource> {
+	let pages: Vec<_> = pages.into_iter().filter(|p| p.content_type.starts_with("text/html")).collect();
+	let mut ids: HashMap<&str, HashSet<String>> = HashMap::new();
+
+	for page in &pages {
+		let html = String::from_utf8_lossy(&page.body);
+		let page_ids: HashSet<String> = attr_values(&html, "id").into_iter().chain(attr_values(&html, "name")).map(decode).collect();
+
+		for path in page.paths.iter().chain(&page.redirects) {
+			ids.insert(path, page_ids.clone());
+		}
+	}
+
+	let mut broken = vec![];
+
+	for page in &pages {
+		let html = String::from_utf8_lossy(&page.body);
+
+		for href in attr_values(&html, "href") {
+			let Some((_, fragment)) = href.split_once('#') else { continue };
+			if fragment.is_empty() {
+				continue;
+			}
+
+			let Some(target) = resolve(&page.paths[0], href) else { continue };
+
+			// Links to pages that aren't here (or aren't HTML) are someone else's problem
+			if ids.get(target.as_str()).is_some_and(|ids| !ids.contains(&decode(fragment))) {
+				broken.push(format!("{}: link to {href} has no matching id", page.source.display()));
+			}
+		}
+	}
+
+	broken
+}
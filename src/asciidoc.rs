@@ -0,0 +1,965 @@
+use std::collections::HashMap;
+
+const ADMONITIONS: &[&str] = &["NOTE", "TIP", "IMPORTANT", "CAUTION", "WARNING"];
+
+// What a `[...]` line says of the block after it
+#[derive(Default)]
+struct BlockAttributes {
+	style: Option<String>,
+	id: Option<String>,
+	roles: Vec<String>,
+	language: Option<String>,
+	header: bool,
+	columns: Option<usize>,
+}
+
+impl BlockAttributes {
+	fn parse(list: &str) -> Self {
+		let mut attributes = BlockAttributes::default();
+		let mut positional = 0;
+
+		for (n, entry) in split_attributes(list).into_iter().enumerate() {
+			if let Some((name, value)) = entry.split_once('=') {
+				let value = value.trim().trim_matches('"');
+				match name.trim() {
+					"id" => attributes.id = Some(value.to_string()),
+					"role" => attributes.roles.extend(value.split_whitespace().map(str::to_string)),
+					"options" | "opts" => attributes.header |= value.split(',').any(|o| o.trim() == "header"),
+					"cols" => attributes.columns = Some(columns(value)),
+					_ => {},
+				}
+				continue;
+			}
+			if n == 0 {
+				// The style, with any `#id`, `.role` and `%option` shorthands after it
+				let end = entry.find(['#', '.', '%']).unwrap_or(entry.len());
+				attributes.style = Some(entry[..end].trim().to_string()).filter(|s| !s.is_empty());
+				let mut rest = &entry[end..];
+				while let Some(kind) = rest.chars().next() {
+					let len = rest[1..].find(['#', '.', '%']).map(|n| n + 1).unwrap_or(rest.len());
+					let value = rest[1..len].to_string();
+					match kind {
+						'#' => attributes.id = Some(value),
+						'.' => attributes.roles.push(value),
+						_ => attributes.header |= value == "header",
+					}
+					rest = &rest[len..];
+				}
+			} else if positional == 1 && attributes.style.as_deref() == Some("source") {
+				attributes.language = Some(entry.trim().to_string());
+			}
+			positional += 1;
+		}
+
+		attributes
+	}
+
+	// As djot block attributes, if there are any to give
+	fn djot(&self, class: Option<&str>) -> Option<String> {
+		let id = self.id.iter().map(|id| format!("#{id}"));
+		let classes = class.into_iter().map(str::to_string).chain(self.roles.iter().cloned()).map(|role| format!(".{role}"));
+		let all: Vec<String> = id.chain(classes).collect();
+
+		(!all.is_empty()).then(|| format!("{{{}}}", all.join(" ")))
+	}
+}
+
+// An attribute list's entries, split at the commas outside quotes
+fn split_attributes(list: &str) -> Vec<String> {
+	let mut entries = vec![String::new()];
+	let mut quoted = false;
+
+	for c in list.chars() {
+		match c {
+			'"' => {
+				quoted = !quoted;
+				entries.last_mut().unwrap_or(&mut String::new()).push(c);
+			},
+			',' if !quoted => entries.push(String::new()),
+			_ => entries.last_mut().unwrap_or(&mut String::new()).push(c),
+		}
+	}
+
+	entries.into_iter().map(|e| e.trim().to_string()).collect()
+}
+
+// How many columns a `cols` attribute (`3*`, or `1,2,1`, or `2*,1`) makes
+fn columns(cols: &str) -> usize {
+	cols.split(',').map(|spec| spec.split_once('*').and_then(|(n, _)| n.trim().parse().ok()).unwrap_or(1)).sum()
+}
+
+// A `:name: value` attribute entry's name and value (`None` for `:name!:`, which unsets it)
+fn attribute_entry(line: &str) -> Option<(&str, Option<&str>)> {
+	let (name, value) = line.strip_prefix(':')?.split_once(':')?;
+	if name.is_empty() || !value.is_empty() && !value.starts_with(' ') {
+		return None;
+	}
+	match name.strip_suffix('!') {
+		Some(name) => name.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_')).then_some((name, None)),
+		None => name.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_')).then_some((name, Some(value.trim()))),
+	}
+}
+
+// A delimited block's delimiter line, if `line` is one
+fn delimiter(line: &str) -> Option<&str> {
+	let line = line.trim_end();
+	let c = line.chars().next()?;
+
+	match line == "--" || (line.len() >= 4 && matches!(c, '-' | '.' | '=' | '*' | '_' | '+' | '/') && line.chars().all(|d| d == c)) {
+		true => Some(line),
+		false => None,
+	}
+}
+
+// Where `line` starts a list item: its marker (`*`, `**`, `-`, `.`, `1.`), and the text after it
+fn list_item(line: &str) -> Option<(&str, &str)> {
+	let line = line.trim_start();
+	let marker_len = match line.chars().next()? {
+		c @ ('*' | '.') => line.chars().take_while(|d| *d == c).count(),
+		'-' => 1,
+		'0'..='9' => {
+			let digits = line.bytes().take_while(u8::is_ascii_digit).count();
+			(line.as_bytes().get(digits) == Some(&b'.')).then_some(digits + 1)?
+		},
+		_ => return None,
+	};
+	let (marker, text) = line.split_at(marker_len);
+
+	match text.strip_prefix(' ').map(str::trim) {
+		// A numbered item (`1.`) is at the same level as `.`
+		Some(text) if !text.is_empty() && marker.len() <= 5 => Some((if marker.starts_with(|c: char| c.is_ascii_digit()) { "." } else { marker }, text)),
+		_ => None,
+	}
+}
+
+// A description list item's term and the definition on its line, if `line` is one
+fn description_item(line: &str) -> Option<(&str, &str)> {
+	["::", ";;", ":::", "::::"].iter().rev().find_map(|marker| {
+		let (term, definition) = line.split_once(marker)?;
+		let ok = !term.trim().is_empty() && !term.ends_with(':') && (definition.is_empty() || definition.starts_with(' ')) && !term.contains("://");
+		ok.then(|| (term.trim(), definition.trim()))
+	})
+}
+
+fn blank_line(out: &mut Vec<String>) {
+	if out.last().is_some_and(|line| !line.trim().is_empty()) {
+		out.push(String::new());
+	}
+}
+
+// Backticks enough to fence `content`
+fn backticks(content: &str, min: usize) -> String {
+	let longest = content.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+	"`".repeat(min.max(longest + 1))
+}
+
+// Djot's escapes for text to be taken literally
+fn escaped(text: &str) -> String {
+	text.chars().fold(String::new(), |mut out, c| {
+		if c.is_ascii_punctuation() {
+			out.push('\\');
+		}
+		out.push(c);
+		out
+	})
+}
+
+// The end of a macro's `[...]` starting at `start`
+fn closing_bracket(chars: &[char], start: usize) -> Option<usize> {
+	(chars.get(start) == Some(&'[')).then_some(())?;
+	let mut depth = 0;
+
+	for (i, c) in chars.iter().enumerate().skip(start) {
+		match c {
+			'[' => depth += 1,
+			']' => {
+				depth -= 1;
+				if depth == 0 {
+					return Some(i);
+				}
+			},
+			_ => {},
+		}
+	}
+
+	None
+}
+
+// Where the cross reference to `target` goes: an anchor on this page, or one on another
+fn xref(target: &str) -> String {
+	match target.split_once('#') {
+		Some((page, anchor)) => format!("{}#{anchor}", page.strip_suffix(".adoc").map(|p| format!("{p}.html")).unwrap_or(page.to_string())),
+		None if target.ends_with(".adoc") => target.replace(".adoc", ".html"),
+		None => format!("#{target}"),
+	}
+}
+
+struct Converter {
+	attributes: HashMap<String, String>,
+	footnotes: Vec<String>,
+}
+
+impl Converter {
+	fn blocks(&mut self, lines: &[&str], out: &mut Vec<String>) {
+		let mut attributes = BlockAttributes::default();
+		// The markers of the lists the last item is in, outermost first
+		let mut lists: Vec<&str> = vec![];
+		let mut i = 0;
+
+		while i < lines.len() {
+			let line = lines[i].trim_end_matches('\r');
+			let trimmed = line.trim();
+			i += 1;
+
+			if trimmed.is_empty() {
+				blank_line(out);
+				continue;
+			}
+			if line.starts_with("//") && delimiter(line).is_none() {
+				continue;
+			}
+			if let Some((name, value)) = attribute_entry(line) {
+				match value {
+					Some(value) => self.attributes.insert(name.to_string(), value.to_string()),
+					None => self.attributes.remove(name),
+				};
+				continue;
+			}
+			if let Some(id) = trimmed.strip_prefix("[[").and_then(|l| l.strip_suffix("]]")) {
+				attributes.id = id.split(',').next().map(str::to_string);
+				continue;
+			}
+			if let Some(list) = trimmed.strip_prefix('[').and_then(|l| l.strip_suffix(']')).filter(|l| !l.starts_with('[')) {
+				let id = attributes.id.take();
+				attributes = BlockAttributes::parse(list);
+				attributes.id = attributes.id.take().or(id);
+				continue;
+			}
+			if let Some(title) = trimmed.strip_prefix('.').filter(|t| !t.starts_with(['.', ' ']) && list_item(line).is_none()) {
+				blank_line(out);
+				out.push("{.title}".to_string());
+				out.push(self.inline(title));
+				out.push(String::new());
+				continue;
+			}
+
+			// A list continuation: the next block is part of the item above it
+			if trimmed == "+" && !lists.is_empty() {
+				let end = self.block_end(lines, i);
+				let mut inner = vec![];
+				self.blocks(&lines[i..end], &mut inner);
+				let indent = " ".repeat(lists.iter().map(|m| marker_width(m)).sum());
+				blank_line(out);
+				out.extend(inner.into_iter().map(|l| if l.is_empty() { l } else { format!("{indent}{l}") }));
+				i = end;
+				continue;
+			}
+
+			if let Some((marker, text)) = list_item(line) {
+				match lists.iter().position(|m| *m == marker) {
+					Some(level) => lists.truncate(level + 1),
+					None => {
+						// Djot wants a blank line before a list, or one in another
+						blank_line(out);
+						if lists.is_empty() {
+							out.extend(attributes.djot(None));
+						}
+						lists.push(marker);
+					},
+				}
+				let indent = " ".repeat(lists[..lists.len() - 1].iter().map(|m| marker_width(m)).sum());
+				let mut text = vec![text];
+				while i < lines.len() && !lines[i].trim().is_empty() && list_item(lines[i]).is_none() && lines[i].trim() != "+" && delimiter(lines[i]).is_none() {
+					text.push(lines[i].trim());
+					i += 1;
+				}
+				let text = text.join("\n");
+				let djot_marker = if marker.starts_with('.') { "1." } else { "-" };
+				// A checklist item's box is djot's too
+				let (checkbox, text) = match text.split_at_checked(4) {
+					Some((b @ ("[x] " | "[ ] " | "[*] "), rest)) => (b.replace('*', "x"), rest),
+					_ => (String::new(), text.as_str()),
+				};
+				let item_indent = " ".repeat(indent.len() + marker_width(marker));
+				let converted = self.inline(text).replace('\n', &format!("\n{item_indent}"));
+				out.push(format!("{indent}{djot_marker} {checkbox}{converted}"));
+				attributes = BlockAttributes::default();
+				continue;
+			}
+			if !line.starts_with(' ') || lists.is_empty() {
+				lists.clear();
+			}
+
+			if let Some(delimiter) = delimiter(line) {
+				let end = (i..lines.len()).find(|&j| lines[j].trim_end() == delimiter).unwrap_or(lines.len());
+				self.delimited(delimiter, &lines[i..end], &attributes, out);
+				attributes = BlockAttributes::default();
+				i = end + 1;
+				continue;
+			}
+
+			if trimmed == "|===" {
+				let end = (i..lines.len()).find(|&j| lines[j].trim_end() == "|===").unwrap_or(lines.len());
+				self.table(&lines[i..end], &attributes, out);
+				attributes = BlockAttributes::default();
+				i = end + 1;
+				continue;
+			}
+
+			let equals = trimmed.chars().take_while(|c| *c == '=').count();
+			if (1..=6).contains(&equals) && trimmed[equals..].starts_with(' ') {
+				blank_line(out);
+				out.extend(attributes.djot(None));
+				out.push(format!("{} {}", "#".repeat(equals), self.inline(trimmed[equals..].trim())));
+				out.push(String::new());
+				attributes = BlockAttributes::default();
+				continue;
+			}
+
+			if trimmed == "'''" || trimmed == "---" || trimmed == "***" {
+				blank_line(out);
+				out.push("* * *".to_string());
+				out.push(String::new());
+				continue;
+			}
+			if trimmed == "<<<" || trimmed.starts_with("toc::[") {
+				continue;
+			}
+
+			if let Some(target) = trimmed.strip_prefix("image::") {
+				let (target, alt) = target.split_once('[').unwrap_or((target, ""));
+				let alt = alt.trim_end_matches(']').split(',').next().unwrap_or_default().trim_matches('"');
+				blank_line(out);
+				out.extend(attributes.djot(None));
+				out.push(format!("![{}]({})", self.inline(alt), target.replace(' ', "%20")));
+				out.push(String::new());
+				attributes = BlockAttributes::default();
+				continue;
+			}
+
+			if let Some((term, definition)) = description_item(line) {
+				let mut text = vec![definition].into_iter().filter(|d| !d.is_empty()).collect::<Vec<_>>();
+				while i < lines.len() && !lines[i].trim().is_empty() && description_item(lines[i]).is_none() && list_item(lines[i]).is_none() {
+					text.push(lines[i].trim());
+					i += 1;
+				}
+				blank_line(out);
+				out.push(format!(": {}", self.inline(term)));
+				if !text.is_empty() {
+					out.push(String::new());
+					out.push(format!("  {}", self.inline(&text.join("\n")).replace('\n', "\n  ")));
+				}
+				out.push(String::new());
+				continue;
+			}
+
+			// A literal paragraph, indented
+			if line.starts_with([' ', '\t']) {
+				let end = (i..lines.len()).find(|&j| lines[j].trim().is_empty()).unwrap_or(lines.len());
+				let content: Vec<&str> = lines[i - 1..end].iter().map(|l| l.trim_end()).collect();
+				let dedent = content.iter().map(|l| l.len() - l.trim_start().len()).min().unwrap_or(0);
+				let content: Vec<&str> = content.iter().map(|l| &l[dedent..]).collect();
+				self.code(None, &content, &attributes, out);
+				attributes = BlockAttributes::default();
+				i = end;
+				continue;
+			}
+
+			// A paragraph, or an admonition (`NOTE: ...`) made of one
+			let end = (i..lines.len()).find(|&j| lines[j].trim().is_empty() || delimiter(lines[j]).is_some() || lines[j].trim() == "|===").unwrap_or(lines.len());
+			let mut text: Vec<&str> = lines[i - 1..end].iter().map(|l| l.trim()).collect();
+			i = end;
+			let admonition = ADMONITIONS.iter().find_map(|label| text[0].strip_prefix(label).and_then(|t| t.strip_prefix(": ")).map(|t| (label, t)));
+			let style = attributes.style.clone().filter(|s| ADMONITIONS.contains(&s.as_str()));
+			let class = match (admonition, style) {
+				(Some((label, rest)), _) => {
+					text[0] = rest;
+					Some(label.to_lowercase())
+				},
+				(None, style) => style.map(|s| s.to_lowercase()),
+			};
+			let paragraph = self.paragraph(&text);
+			blank_line(out);
+			match class {
+				Some(class) => {
+					out.extend(attributes.djot(None));
+					out.push(format!("::: {class}"));
+					out.push(paragraph);
+					out.push(":::".to_string());
+				},
+				None => {
+					out.extend(attributes.djot(None));
+					out.push(paragraph);
+				},
+			}
+			out.push(String::new());
+			attributes = BlockAttributes::default();
+		}
+	}
+
+	// Where the block starting at `start` (a delimited one, or else one ending at a blank line) ends
+	fn block_end(&self, lines: &[&str], start: usize) -> usize {
+		let mut i = start;
+		while i < lines.len() && lines[i].trim().starts_with('[') && lines[i].trim().ends_with(']') {
+			i += 1;
+		}
+		match lines.get(i).and_then(|l| delimiter(l).or(Some(l.trim()).filter(|l| *l == "|==="))) {
+			Some(delimiter) => (i + 1..lines.len()).find(|&j| lines[j].trim_end() == delimiter).map(|j| j + 1).unwrap_or(lines.len()),
+			None => (i..lines.len()).find(|&j| lines[j].trim().is_empty() || lines[j].trim() == "+" || list_item(lines[j]).is_some()).unwrap_or(lines.len()),
+		}
+	}
+
+	fn delimited(&mut self, delimiter: &str, content: &[&str], attributes: &BlockAttributes, out: &mut Vec<String>) {
+		let style = attributes.style.as_deref();
+
+		match delimiter.chars().next() {
+			Some('/') => {},
+			Some('-') if delimiter != "--" => {
+				let language = attributes.language.clone().or_else(|| style.filter(|s| *s == "source").and(self.attributes.get("source-language").cloned()));
+				self.code(language.as_deref(), content, attributes, out);
+			},
+			Some('.') => self.code(None, content, attributes, out),
+			Some('+') => {
+				let ticks = backticks(&content.join("\n"), 3);
+				blank_line(out);
+				out.push(format!("{ticks} =html"));
+				out.extend(content.iter().map(|l| l.to_string()));
+				out.push(ticks);
+				out.push(String::new());
+			},
+			Some('_') => {
+				let mut inner = vec![];
+				self.blocks(content, &mut inner);
+				while inner.last().is_some_and(|l| l.is_empty()) {
+					inner.pop();
+				}
+				blank_line(out);
+				out.extend(attributes.djot(None));
+				out.extend(inner.iter().map(|l| if l.is_empty() { ">".to_string() } else { format!("> {l}") }));
+				out.push(String::new());
+			},
+			_ => {
+				let class = match (delimiter.chars().next(), style) {
+					(_, Some(s)) if ADMONITIONS.contains(&s) => Some(s.to_lowercase()),
+					(Some('='), _) => Some("example".to_string()),
+					(Some('*'), _) => Some("sidebar".to_string()),
+					_ => None,
+				};
+				let mut inner = vec![];
+				self.blocks(content, &mut inner);
+				blank_line(out);
+				match class {
+					Some(class) => {
+						// Longer than any fence in the div, which would otherwise close it
+						let colons = ":".repeat(3.max(inner.iter().map(|l| l.chars().take_while(|c| *c == ':').count() + 1).max().unwrap_or(0)));
+						out.extend(attributes.djot(None));
+						out.push(format!("{colons} {class}"));
+						out.extend(inner);
+						blank_line(out);
+						out.push(colons);
+					},
+					None => out.extend(inner),
+				}
+				out.push(String::new());
+			},
+		}
+	}
+
+	fn code(&self, language: Option<&str>, content: &[&str], attributes: &BlockAttributes, out: &mut Vec<String>) {
+		let ticks = backticks(&content.join("\n"), 3);
+
+		blank_line(out);
+		out.extend(attributes.djot(None));
+		out.push(format!("{ticks}{}", language.map(|l| format!(" {l}")).unwrap_or_default()));
+		out.extend(content.iter().map(|l| strip_callouts(l)));
+		out.push(ticks);
+		out.push(String::new());
+	}
+
+	fn table(&mut self, content: &[&str], attributes: &BlockAttributes, out: &mut Vec<String>) {
+		let first = content.iter().position(|l| !l.trim().is_empty());
+		let header = attributes.header || first.is_some_and(|n| content.get(n + 1).is_some_and(|l| l.trim().is_empty()) && content[n].trim_start().starts_with('|'));
+		let mut cells: Vec<String> = vec![];
+		let mut first_row = 0;
+
+		for (n, line) in content.iter().enumerate() {
+			let mut pieces = split_cells(line);
+			// Text before the line's first `|` carries on the cell before
+			let carried = pieces.remove(0);
+			if let Some(last) = cells.last_mut().filter(|_| !carried.trim().is_empty()) {
+				last.push('\n');
+				last.push_str(carried.trim());
+			}
+			cells.extend(pieces.into_iter().map(|p| p.trim().to_string()));
+			if Some(n) == first {
+				first_row = cells.len();
+			}
+		}
+		let columns = attributes.columns.unwrap_or(first_row).max(1);
+
+		blank_line(out);
+		out.extend(attributes.djot(None));
+		for (n, row) in cells.chunks(columns).enumerate() {
+			let row: Vec<String> = (0..columns).map(|c| row.get(c).map(|cell| self.paragraph(&cell.lines().collect::<Vec<_>>()).replace('\n', " ").replace('|', "\\|")).unwrap_or_default()).collect();
+			out.push(format!("| {} |", row.join(" | ")));
+			if n == 0 && header {
+				out.push(format!("|{}|", vec!["---"; columns].join("|")));
+			}
+		}
+		out.push(String::new());
+	}
+
+	fn paragraph(&mut self, lines: &[&str]) -> String {
+		// A trailing ` +` is a hard line break
+		let text = lines.iter().map(|l| match l.strip_suffix(" +") {
+			Some(l) => format!("{l}\u{0}"),
+			None => l.to_string(),
+		}).collect::<Vec<_>>().join("\n");
+		let converted = self.inline(&text).replace('\u{0}', "\\");
+
+		// What would start another kind of djot block is only text here
+		match converted.chars().next() {
+			Some('-' | '+' | '*' | ':' | '|' | '>' | '#') if converted[1..].starts_with(' ') => format!("\\{converted}"),
+			_ => converted,
+		}
+	}
+
+	// The footnote reference to put where `footnote:[text]` was
+	fn footnote(&mut self, text: &str) -> String {
+		let text = self.inline(text);
+		self.footnotes.push(text);
+		format!("[^{}]", self.footnotes.len())
+	}
+
+	fn inline(&mut self, text: &str) -> String {
+		let chars: Vec<char> = text.chars().collect();
+		let mut out = String::new();
+		let (mut strong, mut emphasis, mut mark) = (false, false, false);
+		let mut i = 0;
+		let rest = |i: usize| chars[i..].iter().collect::<String>();
+
+		while i < chars.len() {
+			let c = chars[i];
+			let before = i.checked_sub(1).map(|j| chars[j]);
+			let boundary = !before.is_some_and(char::is_alphanumeric);
+
+			// Macros, and the URLs that are links without one
+			if boundary && c.is_ascii_alphabetic() {
+				let tail = rest(i);
+				if let Some(converted) = self.inline_macro(&tail, &chars, i) {
+					out.push_str(&converted.0);
+					i = converted.1;
+					continue;
+				}
+			}
+
+			match c {
+				'\\' if chars.get(i + 1).is_some_and(|c| c.is_ascii_punctuation()) => {
+					out.push('\\');
+					out.push(chars[i + 1]);
+					i += 2;
+				},
+				'`' => {
+					let close = (i + 1..chars.len()).find(|&j| chars[j] == '`');
+					match close {
+						Some(j) => {
+							let code: String = chars[i + 1..j].iter().collect();
+							let code = code.strip_prefix('+').and_then(|c| c.strip_suffix('+')).unwrap_or(&code).to_string();
+							let ticks = backticks(&code, 1);
+							let pad = if code.starts_with('`') || code.ends_with('`') { " " } else { "" };
+							out.push_str(&format!("{ticks}{pad}{code}{pad}{ticks}"));
+							i = j + 1;
+						},
+						None => {
+							out.push_str("\\`");
+							i += 1;
+						},
+					}
+				},
+				'+' => {
+					let n = chars[i..].iter().take_while(|d| **d == '+').count().min(3);
+					let marker: String = "+".repeat(n);
+					let close = rest(i + n).find(&marker).map(|j| i + n + rest(i + n)[..j].chars().count());
+					match close.filter(|j| *j > i + n) {
+						// `+++raw+++` is passed through; `+literal+` is text as is
+						Some(j) if n == 3 => {
+							let raw: String = chars[i + 3..j].iter().collect();
+							let ticks = backticks(&raw, 1);
+							out.push_str(&format!("{ticks}{raw}{ticks}{{=html}}"));
+							i = j + 3;
+						},
+						Some(j) => {
+							out.push_str(&escaped(&chars[i + n..j].iter().collect::<String>()));
+							i = j + n;
+						},
+						None => {
+							out.push_str(&"\\+".repeat(n));
+							i += n;
+						},
+					}
+				},
+				'*' | '_' | '#' => {
+					let n = chars[i..].iter().take_while(|d| **d == c).count();
+					let after = chars.get(i + n).copied();
+					let open = match c { '*' => &mut strong, '_' => &mut emphasis, _ => &mut mark };
+					let djot = match c { '*' => '*', '_' => '_', _ => '=' };
+					// Whether there's a run like this one further on to close it
+					let closer = |min: usize| (i + n + 1..chars.len()).any(|j| chars[j] == c && chars[j - 1] != c && !chars[j - 1].is_whitespace() && chars[j..].iter().take_while(|d| **d == c).count() >= min);
+
+					match n {
+						// Unconstrained, so within words too
+						2 if *open => {
+							*open = false;
+							out.push_str(&format!("{djot}}}"));
+						},
+						2 if after.is_some_and(|a| !a.is_whitespace()) && closer(2) => {
+							*open = true;
+							out.push_str(&format!("{{{djot}"));
+						},
+						1 if *open && before.is_some_and(|b| !b.is_whitespace()) && !after.is_some_and(char::is_alphanumeric) => {
+							*open = false;
+							out.push_str(&format!("{djot}}}"));
+						},
+						1 if boundary && after.is_some_and(|a| !a.is_whitespace()) && closer(1) => {
+							*open = true;
+							out.push_str(&format!("{{{djot}"));
+						},
+						_ => (0..n).for_each(|_| {
+							out.push('\\');
+							out.push(c);
+						}),
+					}
+					i += n;
+				},
+				'^' | '~' => {
+					// Super- and subscripts are spelled alike, but can't hold spaces
+					let close = (i + 1..chars.len()).take_while(|&j| !chars[j].is_whitespace()).find(|&j| chars[j] == c);
+					match close.filter(|j| *j > i + 1) {
+						Some(j) => {
+							out.push(c);
+							out.push_str(&self.inline(&chars[i + 1..j].iter().collect::<String>()));
+							out.push(c);
+							i = j + 1;
+						},
+						None => {
+							out.push('\\');
+							out.push(c);
+							i += 1;
+						},
+					}
+				},
+				'{' => {
+					let end = chars[i..].iter().position(|d| *d == '}').map(|n| i + n);
+					let name = end.map(|end| chars[i + 1..end].iter().collect::<String>());
+					match name.as_deref().and_then(|name| self.attribute(name)) {
+						Some(value) => {
+							out.push_str(&escaped(&value));
+							i = end.unwrap_or(i) + 1;
+						},
+						None => {
+							out.push_str("\\{");
+							i += 1;
+						},
+					}
+				},
+				'<' if chars.get(i + 1) == Some(&'<') => {
+					let end = rest(i).find(">>").map(|n| i + rest(i)[..n].chars().count());
+					match end {
+						Some(end) => {
+							let inner: String = chars[i + 2..end].iter().collect();
+							let (target, text) = inner.split_once(',').unwrap_or((&inner, &inner));
+							out.push_str(&format!("[{}]({})", self.inline(text.trim()), xref(target.trim())));
+							i = end + 2;
+						},
+						None => {
+							out.push_str("\\<\\<");
+							i += 2;
+						},
+					}
+				},
+				'-' => {
+					let n = chars[i..].iter().take_while(|d| **d == '-').count();
+					match n {
+						// An em dash, as `---` is to djot
+						2 => out.push_str("---"),
+						1 => out.push('-'),
+						_ => (0..n).for_each(|_| out.push_str("\\-")),
+					}
+					i += n;
+				},
+				'(' if ["(C)", "(R)", "(TM)"].iter().any(|s| rest(i).starts_with(s)) => {
+					let (symbol, len) = match chars[i + 1] { 'C' => ('\u{a9}', 3), 'R' => ('\u{ae}', 3), _ => ('\u{2122}', 4) };
+					out.push(symbol);
+					i += len;
+				},
+				'}' | '<' | '[' | '$' | '!' | '|' => {
+					out.push('\\');
+					out.push(c);
+					i += 1;
+				},
+				_ => {
+					out.push(c);
+					i += 1;
+				},
+			}
+		}
+		// Anything left open is closed at the end, as AsciiDoc would leave it as text
+		for (open, djot) in [(strong, '*'), (emphasis, '_'), (mark, '=')] {
+			if open {
+				out.push_str(&format!("{djot}}}"));
+			}
+		}
+
+		out
+	}
+
+	// An inline macro (or bare URL) at `tail`, as djot, and where in `chars` it ends
+	fn inline_macro(&mut self, tail: &str, chars: &[char], start: usize) -> Option<(String, usize)> {
+		let url = ["https://", "http://", "ftp://", "mailto:", "irc://"].iter().any(|scheme| tail.starts_with(scheme));
+		let (name, target_start) = match tail.split_once(':') {
+			Some((name @ ("link" | "image" | "xref" | "footnote" | "pass" | "kbd"), _)) => (name, start + name.len() + 1),
+			_ if url => ("url", start),
+			_ => return None,
+		};
+		let target_len = chars[target_start..].iter().take_while(|c| !c.is_whitespace() && **c != '[').count();
+		let target: String = chars[target_start..target_start + target_len].iter().collect();
+		let bracket = target_start + target_len;
+		let close = closing_bracket(chars, bracket);
+		let text = close.map(|close| chars[bracket + 1..close].iter().collect::<String>());
+		let next = close.map(|c| c + 1).unwrap_or(bracket);
+
+		let converted = match (name, text) {
+			("url", None) => {
+				// Less any sentence punctuation after it
+				let trimmed = target.trim_end_matches(['.', ',', ';', ':', '!', '?', ')']);
+				return Some((format!("<{trimmed}>"), start + trimmed.chars().count()));
+			},
+			("url" | "link", Some(text)) => {
+				let target = if name == "url" { target } else { target.replace(' ', "%20") };
+				let text = text.split(',').next().unwrap_or_default().trim_matches('"');
+				match text.is_empty() {
+					true => format!("<{target}>"),
+					false => format!("[{}]({target})", self.inline(text)),
+				}
+			},
+			("image", Some(text)) if !target.is_empty() => format!("![{}]({target})", self.inline(text.split(',').next().unwrap_or_default().trim_matches('"'))),
+			("xref", Some(text)) if !target.is_empty() => {
+				let text = if text.is_empty() { target.clone() } else { text };
+				format!("[{}]({})", self.inline(&text), xref(&target))
+			},
+			("footnote", Some(text)) => self.footnote(&text),
+			("pass", Some(text)) if target.is_empty() => {
+				let ticks = backticks(&text, 1);
+				format!("{ticks}{text}{ticks}{{=html}}")
+			},
+			("kbd", Some(text)) if target.is_empty() => {
+				let ticks = backticks(&text, 1);
+				format!("{ticks}{text}{ticks}")
+			},
+			_ => return None,
+		};
+
+		Some((converted, next))
+	}
+
+	// An attribute reference's value: the document's own, or a built-in character
+	fn attribute(&self, name: &str) -> Option<String> {
+		let builtin = match name {
+			"empty" => "",
+			"sp" | "space" => " ",
+			"nbsp" => "\u{a0}",
+			"zwsp" => "\u{200b}",
+			"amp" => "&",
+			"lt" => "<",
+			"gt" => ">",
+			"plus" => "+",
+			"startsb" => "[",
+			"endsb" => "]",
+			"vbar" => "|",
+			"caret" => "^",
+			"asterisk" => "*",
+			"tilde" => "~",
+			"backtick" => "`",
+			"two-colons" => "::",
+			"apos" => "'",
+			"quot" => "\"",
+			_ => return self.attributes.get(name).cloned(),
+		};
+
+		Some(builtin.to_string())
+	}
+}
+
+fn marker_width(marker: &str) -> usize {
+	match marker.starts_with('.') {
+		true => 3,
+		false => 2,
+	}
+}
+
+// A line of code less any callouts (`<1>`, or `// <1>`) at its end, which are for the reader's
+// eye rather than the code
+fn strip_callouts(line: &str) -> String {
+	let mut end = line.trim_end();
+	while let Some(open) = end.rfind('<').filter(|&open| end.ends_with('>') && end[open + 1..end.len() - 1].chars().all(|c| c.is_ascii_digit() || c == '!' || c == '.') && end.len() - open > 2) {
+		end = end[..open].trim_end();
+	}
+	match end.len() == line.trim_end().len() {
+		true => line.to_string(),
+		false => end.strip_suffix("//").or_else(|| end.strip_suffix('#')).unwrap_or(end).trim_end().to_string(),
+	}
+}
+
+// A table line's cells, split at each unescaped `|`, less any cell specifier (`2+`, `a`) before
+// it; the first is what came before the line's first `|`
+fn split_cells(line: &str) -> Vec<String> {
+	let mut cells = vec![String::new()];
+	let mut chars = line.chars().peekable();
+
+	while let Some(c) = chars.next() {
+		match c {
+			'\\' if chars.peek() == Some(&'|') => {
+				cells.last_mut().unwrap_or(&mut String::new()).push('|');
+				chars.next();
+			},
+			'|' => {
+				if let Some(last) = cells.last_mut() {
+					let start = last.rfind(char::is_whitespace).map(|n| n + 1).unwrap_or(0);
+					let spec = &last[start..];
+					let is_spec = !spec.is_empty() && spec.chars().all(|c| c.is_ascii_digit() || "+*.<^>adehlmsv".contains(c)) && (spec.contains(['+', '*', '<', '^', '>']) || spec.len() == 1 && spec != "." && !spec.chars().all(|c| c.is_ascii_digit()));
+					if is_spec {
+						last.truncate(start);
+					}
+				}
+				cells.push(String::new());
+			},
+			_ => cells.last_mut().unwrap_or(&mut String::new()).push(c),
+		}
+	}
+
+	cells
+}
+
+/// An AsciiDoc document respelled as djot, which the djot renderer then renders like any djot page:
+/// sections, lists, delimited blocks, tables, admonitions (as divs of their own class), the inline
+/// formatting and macros, and the attributes the document sets (but not the rest, which
+/// `Stage::AsciiDoc` lists)
+pub(crate) fn to_djot(asciidoc: &str) -> String {
+	let lines: Vec<&str> = asciidoc.lines().collect();
+	let mut converter = Converter { attributes: HashMap::new(), footnotes: vec![] };
+	let mut out = vec![];
+
+	// A document title, with the author and revision lines after it
+	let start = lines.iter().position(|l| !l.trim().is_empty() && (!l.starts_with("//") || delimiter(l).is_some())).unwrap_or(lines.len());
+	let mut body = 0;
+	if let Some(title) = lines.get(start).and_then(|l| l.strip_prefix("= ")) {
+		out.push(format!("# {}", converter.inline(title.trim())));
+		out.push(String::new());
+		body = start + 1;
+		while body < lines.len() && !lines[body].trim().is_empty() && body - start <= 2 && attribute_entry(lines[body]).is_none() {
+			body += 1;
+		}
+	}
+	converter.blocks(&lines[body..], &mut out);
+
+	for (n, footnote) in converter.footnotes.iter().enumerate() {
+		blank_line(&mut out);
+		out.push(format!("[^{}]: {footnote}", n + 1));
+	}
+
+	out.join("\n") + "\n"
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::to_djot;
+
+	fn html(asciidoc: &str) -> String {
+		jotdown::html::render_to_string(jotdown::Parser::new(&to_djot(asciidoc)))
+	}
+
+	#[test]
+	fn document_title_and_header() {
+		assert_eq!(html("= Title\nJane Doe\nv1.0\n\nIntro.\n"), "<section id=\"Title\">\n<h1>Title</h1>\n<p>Intro.</p>\n</section>\n");
+	}
+
+	#[test]
+	fn sections() {
+		assert_eq!(html("== Section\n\n=== Subsection\n\nText.\n"), "<section id=\"Section\">\n<h2>Section</h2>\n<section id=\"Subsection\">\n<h3>Subsection</h3>\n<p>Text.</p>\n</section>\n</section>\n");
+	}
+
+	#[test]
+	fn inline_formatting() {
+		assert_eq!(html("*bold* _italic_ `mono` #marked# **un**constrained x^2^ H~2~O\n"), "<p><strong>bold</strong> <em>italic</em> <code>mono</code> <mark>marked</mark> <strong>un</strong>constrained x<sup>2</sup> H<sub>2</sub>O</p>\n");
+		assert_eq!(html("A line +\nbroken -- (C) (R) (TM)\n"), "<p>A line<br>\nbroken \u{2014} \u{a9} \u{ae} \u{2122}</p>\n");
+		assert_eq!(html("+literal *text*+ \\*escaped* and <b>\n"), "<p>literal *text* *escaped* and &lt;b&gt;</p>\n");
+	}
+
+	#[test]
+	fn lists() {
+		assert_eq!(html("* one\n** nested\n* two\n"), "<ul>\n<li>\none\n<ul>\n<li>\nnested\n</li>\n</ul>\n</li>\n<li>\ntwo\n</li>\n</ul>\n");
+		assert_eq!(html(". first\n. second\n"), "<ol>\n<li>\nfirst\n</li>\n<li>\nsecond\n</li>\n</ol>\n");
+		assert_eq!(html("* [x] done\n* [ ] todo\n"), "<ul class=\"task-list\">\n<li class=\"checked\">\ndone\n</li>\n<li class=\"unchecked\">\ntodo\n</li>\n</ul>\n");
+		assert_eq!(html("CPU:: The brain\nRAM::\nMemory.\n"), "<dl>\n<dt>CPU</dt>\n<dd>\n<p>The brain</p>\n</dd>\n<dt>RAM</dt>\n<dd>\n<p>Memory.</p>\n</dd>\n</dl>\n");
+	}
+
+	#[test]
+	fn list_continuation() {
+		assert_eq!(html(". Step\n+\n----\ncode\n----\n. Next\n"), "<ol>\n<li>\n<p>Step</p>\n<pre><code>code\n</code></pre>\n</li>\n<li>\n<p>Next</p>\n</li>\n</ol>\n");
+	}
+
+	#[test]
+	fn code_and_literal_blocks() {
+		assert_eq!(html("[source,rust]\n----\nfn main() {} // <1>\n----\n"), "<pre><code class=\"language-rust\">fn main() {}\n</code></pre>\n");
+		assert_eq!(html(":source-language: sh\n\n[source]\n----\nls\n----\n"), "<pre><code class=\"language-sh\">ls\n</code></pre>\n");
+		assert_eq!(html("....\nliteral *block*\n....\n\n  indented literal\n"), "<pre><code>literal *block*\n</code></pre>\n<pre><code>indented literal\n</code></pre>\n");
+	}
+
+	#[test]
+	fn admonitions_and_delimited_blocks() {
+		assert_eq!(html("NOTE: Mind the gap.\n\n[WARNING]\n====\nCareful.\n====\n"), "<div class=\"note\">\n<p>Mind the gap.</p>\n</div>\n<div class=\"warning\">\n<p>Careful.</p>\n</div>\n");
+		assert_eq!(html(".Title\n====\nInside.\n====\n\n****\nAside.\n****\n\n____\nQuoted.\n____\n"), "<p class=\"title\">Title</p>\n<div class=\"example\">\n<p>Inside.</p>\n</div>\n<div class=\"sidebar\">\n<p>Aside.</p>\n</div>\n<blockquote>\n<p>Quoted.</p>\n</blockquote>\n");
+		assert_eq!(html("++++\n<div>raw</div>\n++++\n"), "<div>raw</div>\n");
+	}
+
+	#[test]
+	fn tables() {
+		assert_eq!(html("|===\n|A |B\n\n|1 |2\n|===\n"), "<table>\n<tr>\n<th>A</th>\n<th>B</th>\n</tr>\n<tr>\n<td>1</td>\n<td>2</td>\n</tr>\n</table>\n");
+		assert_eq!(html("[cols=\"2*\"]\n|===\n|1 |2\n|3 |4\n|===\n"), "<table>\n<tr>\n<td>1</td>\n<td>2</td>\n</tr>\n<tr>\n<td>3</td>\n<td>4</td>\n</tr>\n</table>\n");
+	}
+
+	#[test]
+	fn links_images_and_macros() {
+		assert_eq!(html("https://example.com[Example] and https://example.org.\n"), "<p><a href=\"https://example.com\">Example</a> and <a href=\"https://example.org\">https://example.org</a>.</p>\n");
+		assert_eq!(html("link:page.html[a page] <<intro,Intro>> xref:other.adoc#sec[Other]\n"), "<p><a href=\"page.html\">a page</a> <a href=\"#intro\">Intro</a> <a href=\"other.html#sec\">Other</a></p>\n");
+		assert_eq!(html("image:icon.png[Icon] kbd:[Ctrl+C] pass:[<b>raw</b>] +++<i>raw</i>+++\n"), "<p><img alt=\"Icon\" src=\"icon.png\"> <code>Ctrl+C</code> <b>raw</b> <i>raw</i></p>\n");
+		assert_eq!(html("image::diagram.png[A diagram]\n"), "<p><img alt=\"A diagram\" src=\"diagram.png\"></p>\n");
+	}
+
+	#[test]
+	fn footnotes() {
+		assert!(html("Text.footnote:[A note.]\n").contains("<li id=\"fn1\">\n<p>A note.<a href=\"#fnref1\" role=\"doc-backlink\">"));
+	}
+
+	#[test]
+	fn attributes() {
+		assert_eq!(html(":product: Widget\n:gone: x\n:gone!:\n\n{product} {amp} {gone}\n"), "<p>Widget &amp; {gone}</p>\n");
+		assert_eq!(html("[#id.role]\nPara.\n"), "<p id=\"id\" class=\"role\">Para.</p>\n");
+		assert_eq!(html("[[anchor]]\nPara.\n"), "<p id=\"anchor\">Para.</p>\n");
+	}
+
+	#[test]
+	fn comments_and_breaks() {
+		assert_eq!(html("// comment\n////\nblock comment\n////\n'''\n<<<\ntoc::[]\n"), "<hr>\n");
+	}
+
+	// What isn't supported is left as text, or dropped
+	#[test]
+	fn unsupported() {
+		assert_eq!(html("include::other.adoc[]\n"), "<p>include::other.adoc[]</p>\n");
+		assert_eq!(html("ifdef::x[]\nshown\nendif::[]\n"), "<p>ifdef::x[]\nshown\nendif::[]</p>\n");
+		assert_eq!(html("[quote, Author]\n____\nWords.\n____\n"), "<blockquote>\n<p>Words.</p>\n</blockquote>\n");
+		assert_eq!(html("[verse]\n____\nRoses\nare red\n____\n"), "<blockquote>\n<p>Roses\nare red</p>\n</blockquote>\n");
+	}
+}
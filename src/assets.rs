@@ -0,0 +1,35 @@
+use std::{collections::{BTreeMap, HashMap}, sync::Arc};
+
+/// Where content-addressed copies of assets are served, as `/__assets/<hash>/<file name>`
+pub(crate) const NAMESPACE: &str = "/__assets/";
+
+// FNV-1a, which unlike `DefaultHasher` gives the same hashes from one toolchain to the next
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+	bytes.iter().fold(0xcbf29ce484222325, |hash, b| (hash ^ u64::from(*b)).wrapping_mul(0x100000001b3))
+}
+
+/// Each asset's route, and the content-addressed route that also serves it
+pub(crate) type AssetUrls = BTreeMap<String, String>;
+
+// `asset_url(path="/style.css")` in templates.  Without a set of asset URLs (in dev builds), paths
+// come back as they are.
+struct AssetUrl(Option<Arc<AssetUrls>>);
+
+impl tera::Function for AssetUrl {
+	fn call(&self, args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+		let path = args.get("path").and_then(|p| p.as_str()).ok_or("asset_url needs a `path` string")?;
+
+		match &self.0 {
+			None => Ok(path.into()),
+			Some(urls) => urls.get(path).map(|url| url.as_str().into()).ok_or_else(|| format!("asset_url: no asset at {path}").into()),
+		}
+	}
+
+	fn is_safe(&self) -> bool {
+		true
+	}
+}
+
+pub(crate) fn register(templater: &mut tera::Tera, urls: Option<Arc<AssetUrls>>) {
+	templater.register_function("asset_url", AssetUrl(urls));
+}
@@ -0,0 +1,290 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::{codegen::{filtered_paths, path_checks, route_table, set_tables, Options, SetTables}, RenderedResource, Resource};
+
+// Runs before responding to `path` from `routes`: in dev builds, renders it on tokio's blocking pool
+fn prerender(routes: TokenStream, options: &Options<'_>) -> TokenStream {
+	if options.dev {
+		quote! {
+			let routes = #routes;
+			let rendered = path.clone();
+			let _ = ::tokio::task::spawn_blocking(move || ::static_builder::serve::prerender(routes, &rendered)).await;
+		}
+	} else {
+		quote! {}
+	}
+}
+
+fn content_sets(default_paths: &[String], sets: &[(&str, Vec<(Resource, RenderedResource)>)], options: &Options<'_>) -> Result<TokenStream, String> {
+	let SetTables { tables, routes_fn, names, paths } = set_tables(default_paths, sets, options)?;
+	let all_paths = paths.into_iter();
+	let prerender = prerender(quote! { StaticContentSets::routes(set.as_deref()) }, options);
+
+	Ok(quote! {
+		#[derive(Clone)]
+		pub(crate) struct StaticContentSets {
+			select: ::std::sync::Arc<dyn Fn(&::axum::extract::Request) -> Option<String> + Send + Sync>,
+		}
+
+		#[allow(dead_code)]
+		impl StaticContentSets {
+			pub(crate) const NAMES: &'static [&'static str] = &[#(#names),*];
+			pub(crate) const PATHS: &'static [&'static str] = &[#(#all_paths),*];
+
+			/// `select` names the content set to serve each request from; `None`, or a name that
+			/// isn't in `NAMES`, gets the default set (the one `StaticContent` serves)
+			pub(crate) fn new<F>(select: F) -> Self where F: Fn(&::axum::extract::Request) -> Option<String> + Send + Sync + 'static {
+				StaticContentSets { select: ::std::sync::Arc::new(select) }
+			}
+
+			/// Always serves the named set, e.g. one chosen from configuration at startup
+			pub(crate) fn fixed<S>(name: S) -> Self where S: Into<String> {
+				let name = name.into();
+				Self::new(move |_| Some(name.clone()))
+			}
+
+			/// Serves the set named by the request's `Host`, less any port
+			pub(crate) fn by_host() -> Self {
+				Self::new(|req| {
+					let host = req.headers().get(::axum::http::header::HOST)?.to_str().ok()?.to_ascii_lowercase();
+
+					Some(match host.rfind(':') {
+						Some(i) if !host[i..].contains(']') => host[..i].to_string(),
+						_ => host,
+					})
+				})
+			}
+
+			/// A router serving every path in any set, to `merge` into the application's
+			pub(crate) fn router<S>(self) -> ::axum::Router<S> where S: Clone + Send + Sync + 'static {
+				let mut router = ::axum::Router::new();
+
+				for path in Self::PATHS {
+					let sets = self.clone();
+					router = router.route(path, ::axum::routing::any(move |req: ::axum::extract::Request| async move { sets.handle(req).await }));
+				}
+
+				router
+			}
+
+			/// Serves `req` from whichever set `select` picks for it, e.g. as a `Router::fallback`
+			pub(crate) async fn handle(&self, req: ::axum::extract::Request) -> ::axum::response::Response {
+				let set = (self.select)(&req);
+				let path = req.uri().path().to_owned();
+				#prerender
+
+				static_content_http_response(static_content_respond(Self::routes(set.as_deref()), req.method().as_str(), &path, |name| req.headers().get(name).and_then(|v| v.to_str().ok())))
+			}
+
+			#routes_fn
+		}
+
+		#tables
+	})
+}
+
+// Checks of the HTTP semantics every route should have, run with the application's own tests
+fn http_tests(rendered: &[(Resource, RenderedResource)], options: &Options<'_>) -> TokenStream {
+	let filtered = filtered_paths(rendered);
+	let not_allowed = match options.allows("POST") {
+		true => quote! {},
+		false => quote! {
+			#[::tokio::test]
+			async fn other_methods_are_not_allowed() {
+				for path in paths() {
+					let res = call(StaticContent::router(), Method::POST, path).await;
+					assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED, "POST {path}");
+					assert!(res.headers().contains_key(header::ALLOW), "POST {path}");
+				}
+			}
+		},
+	};
+	let options_test = match options.allows("OPTIONS") {
+		true => quote! {
+			#[::tokio::test]
+			async fn options_lists_the_allowed_methods() {
+				for path in paths() {
+					let res = call(StaticContent::router(), Method::OPTIONS, path).await;
+					assert_eq!(res.status(), StatusCode::NO_CONTENT, "OPTIONS {path}");
+					assert!(res.headers().contains_key(header::ALLOW), "OPTIONS {path}");
+				}
+			}
+		},
+		false => quote! {},
+	};
+	let redirects: Vec<_> = rendered.iter().flat_map(|(_, page)| page.redirects.iter().map(|r| (r, &page.paths[0]))).collect();
+	let (redirect_from, redirect_to): (Vec<_>, Vec<_>) = redirects.into_iter().unzip();
+
+	quote! {
+		#[cfg(test)]
+		#[allow(clippy::unwrap_used, clippy::panic)]
+		mod static_content_http_tests {
+			use super::StaticContent;
+			use ::axum::{body::Body, http::{header, Method, Request, StatusCode}, Router};
+			use ::tower::ServiceExt as _;
+
+			const FILTERED: &[&str] = &[#(#filtered),*];
+
+			fn paths() -> impl Iterator<Item = &'static &'static str> {
+				StaticContent::PATHS.iter().filter(|path| !FILTERED.contains(path))
+			}
+
+			const REDIRECTS: &[(&str, &str)] = &[#((#redirect_from, #redirect_to)),*];
+
+			async fn call(app: Router, method: Method, path: &str) -> ::axum::response::Response {
+				app.oneshot(Request::builder().method(method).uri(path).body(Body::empty()).unwrap()).await.unwrap()
+			}
+
+			#[::tokio::test]
+			async fn every_route_answers_get_and_head() {
+				for path in paths() {
+					for method in [Method::GET, Method::HEAD] {
+						let res = call(StaticContent::router(), method.clone(), path).await;
+						assert!(res.status() == StatusCode::OK || res.status() == StatusCode::PERMANENT_REDIRECT, "{method} {path}: {}", res.status());
+					}
+				}
+			}
+
+			#not_allowed
+
+			#options_test
+
+			#[::tokio::test]
+			async fn redirects_point_at_their_canonical_route() {
+				for (from, to) in REDIRECTS {
+					let res = call(StaticContent::router(), Method::GET, from).await;
+					assert_eq!(res.status(), StatusCode::PERMANENT_REDIRECT, "GET {from}");
+					assert_eq!(res.headers().get(header::LOCATION).unwrap(), to, "GET {from}");
+				}
+			}
+
+			#[::tokio::test]
+			async fn matching_etags_are_not_modified() {
+				for path in paths() {
+					let res = call(StaticContent::router(), Method::GET, path).await;
+					let Some(etag) = res.headers().get(header::ETAG).cloned() else { continue };
+					let app: Router = StaticContent::router();
+					let res = app.oneshot(Request::get(*path).header(header::IF_NONE_MATCH, etag.clone()).body(Body::empty()).unwrap()).await.unwrap();
+					assert_eq!(res.status(), StatusCode::NOT_MODIFIED, "GET {path}");
+					assert_eq!(res.headers().get(header::ETAG), Some(&etag), "GET {path}");
+				}
+			}
+
+			#[::tokio::test]
+			async fn unchanged_routes_are_not_modified_since() {
+				for path in paths() {
+					let res = call(StaticContent::router(), Method::GET, path).await;
+					let Some(modified) = res.headers().get(header::LAST_MODIFIED).cloned() else { continue };
+					let app: Router = StaticContent::router();
+					let res = app.oneshot(Request::get(*path).header(header::IF_MODIFIED_SINCE, modified).body(Body::empty()).unwrap()).await.unwrap();
+					assert_eq!(res.status(), StatusCode::NOT_MODIFIED, "GET {path}");
+				}
+			}
+
+			#[::tokio::test]
+			async fn ranges_are_partial_content() {
+				for path in paths() {
+					let app: Router = StaticContent::router();
+					let res = app.oneshot(Request::get(*path).header(header::RANGE, "bytes=0-0").body(Body::empty()).unwrap()).await.unwrap();
+					if res.status() == StatusCode::PERMANENT_REDIRECT { continue }
+					assert!(res.status() == StatusCode::PARTIAL_CONTENT || res.status() == StatusCode::RANGE_NOT_SATISFIABLE, "GET {path}: {}", res.status());
+					assert!(res.headers().contains_key(header::CONTENT_RANGE), "GET {path}");
+				}
+			}
+
+			#[::tokio::test]
+			async fn unknown_paths_are_not_found() {
+				let app = Router::new().fallback(StaticContent::handle);
+				let res = call(app, Method::GET, "/static-builder-no-such-route").await;
+
+				assert_eq!(res.status(), StatusCode::NOT_FOUND);
+			}
+		}
+	}
+}
+
+pub(crate) fn axum_module(rendered: &[(Resource, RenderedResource)], sets: &[(&str, Vec<(Resource, RenderedResource)>)], options: &Options<'_>) -> Result<TokenStream, String> {
+	let (routes, resource_paths) = route_table("STATIC_CONTENT_ROUTES", rendered, options)?;
+	let passthrough = options.passthrough;
+	let panic_lints = options.panic_lints();
+	let respond = options.respond_fn();
+	let prerender = prerender(quote! { STATIC_CONTENT_ROUTES }, options);
+	let sets = if sets.is_empty() { quote! {} } else { content_sets(&resource_paths, sets, options)? };
+	let bodies = options.body_statics()?;
+	let tests = if options.tests { http_tests(rendered, options) } else { quote! {} };
+	let path_checks = path_checks(options);
+
+	Ok(quote! {
+		#[derive(Clone)]
+		pub(crate) struct StaticContent;
+
+		impl StaticContent {
+			pub(crate) const PATHS: &'static [&'static str] = &[#(#resource_paths),*];
+			/// Path prefixes never handled here, for another service to respond to
+			pub(crate) const PASSTHROUGH: &'static [&'static str] = &[#(#passthrough),*];
+
+			pub(crate) fn passes_through(path: &str) -> bool {
+				Self::PASSTHROUGH.iter().any(|prefix| path.starts_with(prefix))
+			}
+
+			/// A router serving every embedded path, to `merge` into the application's (which
+			/// panics on any route both of them have)
+			pub(crate) fn router<S>() -> ::axum::Router<S> where S: Clone + Send + Sync + 'static {
+				let mut router = ::axum::Router::new();
+
+				for path in Self::PATHS {
+					router = router.route(path, ::axum::routing::any(Self::handle));
+				}
+
+				router
+			}
+
+			/// Serves `req`, or 404s it; as a `Router::fallback`, the application's own routes take
+			/// precedence and everything they don't handle is served from here
+			pub(crate) async fn handle(req: ::axum::extract::Request) -> ::axum::response::Response {
+				let path = req.uri().path().to_owned();
+
+				if Self::passes_through(&path) {
+					return ::axum::response::IntoResponse::into_response(::axum::http::StatusCode::NOT_FOUND);
+				}
+				#prerender
+
+				static_content_http_response(static_content_respond(STATIC_CONTENT_ROUTES, req.method().as_str(), &path, |name| req.headers().get(name).and_then(|v| v.to_str().ok())))
+			}
+		}
+
+		// What's served is all decided by `static_builder::serve`; this only hands it to axum
+		#panic_lints
+		fn static_content_http_response(res: ::static_builder::serve::Response) -> ::axum::response::Response {
+			let mut builder = ::axum::http::Response::builder().status(res.status);
+
+			for (name, value) in res.headers {
+				builder = builder.header(name, value.into_owned());
+			}
+
+			let body = match res.body {
+				::std::borrow::Cow::Borrowed(body) => ::axum::body::Body::from(body),
+				::std::borrow::Cow::Owned(body) => ::axum::body::Body::from(body),
+			};
+
+			builder.body(body).unwrap_or_else(|_| {
+				let mut res = ::axum::response::Response::default();
+				*res.status_mut() = ::axum::http::StatusCode::INTERNAL_SERVER_ERROR;
+				res
+			})
+		}
+
+		#respond
+
+		#routes
+
+		#sets
+
+		#bodies
+
+		#path_checks
+
+		#tests
+	})
+}
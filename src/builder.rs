@@ -0,0 +1,1214 @@
+use std::{env, fmt, fs, fs::File, io, io::Write as _, mem, path::{Path, PathBuf}, sync::Arc, time::{Duration, Instant}};
+
+use crate::{allowlist, anchors, assets::{self, fnv1a, AssetUrls}, axum, build_profile, build_target, codegen, command::{self, ExternalCommand}, compress::{self, Encoding}, freshness, graph, icon, images::{self, ImageEncoder}, json_ld, manifest, map, minify, old_urls, orphans, page_index, pdf::{self, PdfRenderer}, poem, portable, rocket, scan_resources, search, serve, site::SiteConfig, sitemap, spellcheck::{self, Dictionary}, tidy, warp, ContentRenderer, DjotOptions, Allowlist, Feed, ImageFormat, RenderedResource, Resource, Rule, Scan, Window};
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+const DEFAULT_INLINE_LIMIT: usize = 1024;
+
+pub struct Progress<'a> {
+	pub done: usize,
+	pub total: usize,
+	pub current: &'a Path,
+	pub elapsed: Duration,
+}
+
+impl fmt::Display for Progress<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "rendering {}/{} ({:.1}s elapsed): {}", self.done + 1, self.total, self.elapsed.as_secs_f64(), self.current.display())
+	}
+}
+
+/// Which routes serve the root `index.html`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RootIndex {
+	/// Both the `index.html` route and the directory route(s)
+	#[default]
+	Both,
+	/// Only `/index.html` (or `<prefix>/index.html`)
+	IndexOnly,
+	/// Only `/` (or `<prefix>/` and `<prefix>`)
+	DirectoryOnly,
+}
+
+/// The web framework the generated module serves content with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+	/// An actix-web `HttpServiceFactory`, for `App::service` (or `App::default_service`).  Unlike
+	/// every other backend's, its responses' headers aren't in a fixed order: actix-web keeps them in
+	/// a hash map, so sends the same headers in an order that can change from one run to the next
+	#[default]
+	Actix,
+	/// An `axum::Router`, for `Router::merge` (or a handler for `Router::fallback`)
+	Axum,
+	/// Rocket routes, for `Rocket::mount`
+	Rocket,
+	/// A `warp::Filter`, to `or` with the application's own
+	Warp,
+	/// A Poem `Endpoint`, to nest in a `Route`
+	Poem,
+	/// No web framework at all: a `RESOURCES` table of path, body and media type (and
+	/// `REDIRECTS`), with `resource` and `redirect` lookups, for serving some other way.  Content
+	/// is always embedded, and neither content sets nor scheduled variants are supported.
+	Map,
+}
+
+fn cross_compiling() -> bool {
+	env::var("HOST").is_ok_and(|host| host != build_target())
+}
+
+fn drop_passthrough(passthrough: &[String], rendered: &mut Vec<(Resource, RenderedResource)>) {
+	let passes = |path: &String| passthrough.iter().any(|prefix| path.starts_with(prefix.as_str()));
+
+	for (_, page) in rendered.iter_mut() {
+		page.paths.retain(|p| !passes(p));
+		page.redirects.retain(|p| !passes(p));
+	}
+
+	rendered.retain(|(_, page)| !page.paths.is_empty());
+}
+
+type ProgressCallback = Box<dyn FnMut(&Progress<'_>)>;
+type ResourcesCallback = Box<dyn FnMut(&mut Vec<(Resource, RenderedResource)>) -> Result<(), String>>;
+
+pub struct Builder {
+	base_path: PathBuf,
+	rules: Vec<(String, Rule)>,
+	fields_index: Option<String>,
+	sitemap: Option<(String, String)>,
+	feeds: Vec<Feed>,
+	page_indexes: Vec<(String, String)>,
+	search: Option<(String, String, String)>,
+	icons: Vec<(&'static str, PathBuf)>,
+	favicon_ico: Vec<PathBuf>,
+	passthrough: Vec<String>,
+	methods: Vec<String>,
+	allow_empty: bool,
+	deny_panic: bool,
+	inline_limit: usize,
+	asset_namespace: bool,
+	http_tests: bool,
+	embed: Option<bool>,
+	tidy_html: Option<bool>,
+	minify_html: bool,
+	minify_extensions: Vec<String>,
+	access_log: u64,
+	external_bodies: Option<(usize, PathBuf)>,
+	mount_prefix: String,
+	root_index: RootIndex,
+	backend: Backend,
+	sets: Vec<(String, Builder)>,
+	hosts_dir: Option<PathBuf>,
+	dictionaries: Vec<PathBuf>,
+	manifest: Option<PathBuf>,
+	reference_graph: Option<PathBuf>,
+	allowlist: Option<(PathBuf, Allowlist)>,
+	stale_after: Vec<(String, u32)>,
+	check_anchors: Option<bool>,
+	check_orphans: Option<bool>,
+	old_urls: Option<(PathBuf, bool)>,
+	portable_paths: Option<bool>,
+	shard_routes: bool,
+	precompress: Vec<Encoding>,
+	compress_min_size: usize,
+	compress_extensions: Option<Vec<String>>,
+	cache_control: Vec<(String, String)>,
+	not_found: Option<PathBuf>,
+	schedules: Vec<(PathBuf, PathBuf, String, String)>,
+	// Scheduled variants, rendered but kept out of the site until code is generated for them
+	variants: Vec<(Resource, RenderedResource)>,
+	json_ld: Option<(String, String)>,
+	site_config: Option<PathBuf>,
+	pdfs: Vec<(String, PdfRenderer)>,
+	image_variants: Vec<(String, ImageFormat, ImageEncoder)>,
+	renderers: Vec<(String, Arc<dyn ContentRenderer>)>,
+	commands: Vec<(String, ExternalCommand)>,
+	on_progress: Option<ProgressCallback>,
+	before_codegen: Option<ResourcesCallback>,
+	// What `prepare` found the generated code depends on, for `watch_for_rebuild`: the directories
+	// whose files it routes or renders with, and the files it embeds or was configured by
+	watch_dirs: Vec<PathBuf>,
+	rebuild_on: Vec<PathBuf>,
+}
+
+impl Builder {
+	pub fn new<P>(base_path: P) -> Self where P: AsRef<Path> {
+		Builder { base_path: base_path.as_ref().to_path_buf(), rules: vec![], fields_index: None, sitemap: None, feeds: vec![], page_indexes: vec![], search: None, icons: vec![], favicon_ico: vec![], passthrough: vec![], methods: serve::DEFAULT_METHODS.iter().map(|m| m.to_string()).collect(), allow_empty: false, deny_panic: false, inline_limit: DEFAULT_INLINE_LIMIT, asset_namespace: false, http_tests: false, embed: None, tidy_html: None, minify_html: false, minify_extensions: vec![], access_log: 0, external_bodies: None, mount_prefix: String::new(), root_index: RootIndex::default(), backend: Backend::default(), sets: vec![], hosts_dir: None, dictionaries: vec![], manifest: None, reference_graph: None, allowlist: None, stale_after: vec![], check_anchors: None, check_orphans: None, old_urls: None, portable_paths: None, shard_routes: false, precompress: vec![], compress_min_size: compress::DEFAULT_MIN_SIZE, compress_extensions: None, cache_control: vec![], not_found: None, schedules: vec![], variants: vec![], json_ld: None, site_config: None, pdfs: vec![], image_variants: vec![], renderers: vec![], commands: vec![], on_progress: None, before_codegen: None, watch_dirs: vec![], rebuild_on: vec![] }
+	}
+
+	/// Applies `rule` to every file whose path (relative to the base path) matches `glob`.  Rules
+	/// are applied in the order given, and `.static-builder.yaml` directory configs override them.
+	pub fn rule<S>(mut self, glob: S, rule: Rule) -> Self where S: Into<String> {
+		self.rules.push((glob.into(), rule));
+		self
+	}
+
+	pub fn djot_options(self, djot: DjotOptions) -> Self {
+		self.rule("**", Rule::new().djot(djot))
+	}
+
+	/// Builds the variant of every djot page for these audiences (see `DjotOptions::audiences`),
+	/// e.g. `["internal"]` for the internal docs, keeping `{.audience-internal}` regions and
+	/// dropping those for anyone else
+	pub fn audiences<I, S>(self, audiences: I) -> Self where I: IntoIterator<Item = S>, S: Into<String> {
+		self.djot_options(DjotOptions::new().audiences(audiences))
+	}
+
+	/// Adds a JSON route listing the URL, title and `{.metadata}` fields of every page that has
+	/// such fields, for API-reference-style docs sites to search or cross-reference
+	pub fn fields_index<S>(mut self, route: S) -> Self where S: Into<String> {
+		self.fields_index = Some(route.into());
+		self
+	}
+
+	/// Adds an XML sitemap route (e.g. `/sitemap.xml`) listing every HTML page under `base_url`.
+	/// Pages can opt out with `sitemap: false` in their frontmatter, or set `priority:` and
+	/// `changefreq:`.
+	pub fn sitemap<S, U>(mut self, route: S, base_url: U) -> Self where S: Into<String>, U: Into<String> {
+		self.sitemap = Some((route.into(), base_url.into()));
+		self
+	}
+
+	/// Adds a JSON route (e.g. `/index.json`) listing the title, URL, date, tags and summary of
+	/// every HTML page served under `section` (`/` for all of them), for client-side search and
+	/// related-content widgets.  Call it once per section that wants its own listing.
+	pub fn page_index<R, S>(mut self, route: R, section: S) -> Self where R: Into<String>, S: Into<String> {
+		self.page_indexes.push((route.into(), section.into()));
+		self
+	}
+
+	/// Adds a client-side search page at `route` (e.g. `/search.html`), working from the first
+	/// `page_index` (or an `/index.json` of the whole site, if there isn't one), and an
+	/// `/opensearch.xml` descriptor for it so browsers can offer the site as a search engine.
+	/// Layouts advertise it with `<link rel="search" type="application/opensearchdescription+xml"
+	/// href="/opensearch.xml">`.
+	pub fn search<R, N, U>(mut self, route: R, short_name: N, base_url: U) -> Self where R: Into<String>, N: Into<String>, U: Into<String> {
+		self.search = Some((route.into(), short_name.into(), base_url.into()));
+		self
+	}
+
+	/// Adds an Atom feed route; call it once per feed, e.g. one for the whole site and one per section
+	pub fn feed(mut self, feed: Feed) -> Self {
+		self.feeds.push(feed);
+		self
+	}
+
+	/// Also serves `source` (relative to the base path, wherever it lives in the tree) at
+	/// `/favicon.ico`, where browsers look for it whether or not pages link to it
+	pub fn favicon<P>(mut self, source: P) -> Self where P: AsRef<Path> {
+		self.icons.push(("/favicon.ico", source.as_ref().to_path_buf()));
+		self
+	}
+
+	/// Generates `/favicon.ico` from `sources` (PNGs, relative to the base path), one image per size
+	/// (e.g. 16, 32 and 48 pixels square), which browsers may keep a month without asking again
+	pub fn favicon_ico<I, P>(mut self, sources: I) -> Self where I: IntoIterator<Item = P>, P: AsRef<Path> {
+		self.favicon_ico.extend(sources.into_iter().map(|source| source.as_ref().to_path_buf()));
+		// First, so that any policy for it given to `cache_control` wins
+		if !self.cache_control.iter().any(|(glob, _)| glob == "/favicon.ico") {
+			self.cache_control.insert(0, ("/favicon.ico".to_string(), icon::CACHE_CONTROL.to_string()));
+		}
+		self
+	}
+
+	/// Also serves `source` (a PNG, relative to the base path) at `/apple-touch-icon.png` and
+	/// `/apple-touch-icon-precomposed.png`, which iOS and others probe for
+	pub fn touch_icon<P>(mut self, source: P) -> Self where P: AsRef<Path> {
+		self.icons.push(("/apple-touch-icon.png", source.as_ref().to_path_buf()));
+		self.icons.push(("/apple-touch-icon-precomposed.png", source.as_ref().to_path_buf()));
+		self
+	}
+
+	/// Leaves every URL starting with `prefix` to the application's other services, even where
+	/// there's content for it or `StaticContent` is the default service.  Prefixes are URLs as
+	/// requested, regardless of any mount prefix.
+	pub fn passthrough<S>(mut self, prefix: S) -> Self where S: Into<String> {
+		self.passthrough.push(prefix.into());
+		self
+	}
+
+	/// The methods answered, rather than with a 405: by default GET, HEAD and OPTIONS.  Any other
+	/// than HEAD and OPTIONS is answered as GET is, e.g. POST for a form's thank-you page.  OPTIONS
+	/// (and every 405) lists them in `Allow`.
+	pub fn allow_methods<I, S>(mut self, methods: I) -> Self where I: IntoIterator<Item = S>, S: AsRef<str> {
+		self.methods = methods.into_iter().map(|m| m.as_ref().to_ascii_uppercase()).collect();
+		self
+	}
+
+	/// Passes through `/.well-known/acme-challenge/`, so a certbot (or similar) HTTP-01 responder
+	/// can answer challenges while `StaticContent` owns the root
+	pub fn acme_passthrough(self) -> Self {
+		self.passthrough("/.well-known/acme-challenge/")
+	}
+
+	/// Builds a service with no content (every request gets a 404, with a build warning) when the
+	/// content directory is empty, rather than failing the build
+	pub fn allow_empty(mut self, allow_empty: bool) -> Self {
+		self.allow_empty = allow_empty;
+		self
+	}
+
+	/// Marks the generated code handing responses to the framework
+	/// `#[deny(clippy::panic, clippy::unwrap_used, clippy::expect_used)]`, so clippy checks nothing in
+	/// the request path can panic; responses themselves are built by `serve`, which never does.
+	pub fn deny_panic(mut self, deny_panic: bool) -> Self {
+		self.deny_panic = deny_panic;
+		self
+	}
+
+	/// Embedded bodies up to `limit` bytes (default 1 KiB) are inlined into the route table; bigger
+	/// ones become statics of their own, shared by every route serving them and `include_bytes!`ed
+	/// from `OUT_DIR`
+	pub fn inline_limit(mut self, limit: usize) -> Self {
+		self.inline_limit = limit;
+		self
+	}
+
+	/// Rather than embedding bodies over `threshold` bytes, writes them to `dir` and reads them from
+	/// there on each request, to keep huge assets out of the binary.  `dir` is read from the same
+	/// path at runtime as it's written to at build time, so deploy it alongside (or make it absolute).
+	pub fn external_bodies<P>(mut self, threshold: usize, dir: P) -> Self where P: Into<PathBuf> {
+		self.external_bodies = Some((threshold, dir.into()));
+		self
+	}
+
+	/// Also serves every asset (anything embedded verbatim that isn't HTML) at
+	/// `/__assets/<hash>/<file name>`, with `Cache-Control: immutable`; templates get those URLs
+	/// from `asset_url(path="/style.css")`.  In dev builds `asset_url` just returns the path.
+	pub fn asset_namespace(mut self, asset_namespace: bool) -> Self {
+		self.asset_namespace = asset_namespace;
+		self
+	}
+
+	fn asset_urls(&self, resources: &[Resource]) -> Result<AssetUrls, String> {
+		let mut urls = AssetUrls::new();
+
+		for r in resources.iter().filter(|r| (r.pipeline().is_empty() || r.is_generated()) && !r.content_type().starts_with("text/html")) {
+			let bytes = match &r.bytes {
+				// A stylesheet's (or a command's output's) URL changes with what it's served as
+				_ if r.is_generated() => r.render()?.body,
+				Some(bytes) => bytes.clone(),
+				None => fs::read(&r.source).map_err(|e| format!("failed to read {}: {e}", r.source.display()))?,
+			};
+			let path = r.paths().remove(0);
+			let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+
+			urls.insert(path.display().to_string(), format!("{}{}{:016x}/{name}", self.mount_prefix, assets::NAMESPACE, fnv1a(&bytes)));
+		}
+
+		Ok(urls)
+	}
+
+	/// Also generates `#[cfg(test)]` tests that request every route and check the status codes and
+	/// headers `StaticContent` answers with, so they run with the application's own `cargo test`,
+	/// and that nothing but a route is found for `serve::awkward_paths` (traversal attempts, odd
+	/// encodings and near misses).  Fuzz targets can call `StaticContent::fuzz`, generated whether
+	/// or not this is set, with their input for the same check.  The tests need actix-web's
+	/// `macros` feature; for axum, tokio's and a `tower` dev-dependency; for warp, tokio's; and for
+	/// Poem, its `test` feature and tokio's.
+	pub fn http_tests(mut self, http_tests: bool) -> Self {
+		self.http_tests = http_tests;
+		self
+	}
+
+	/// Whether to strip HTML pages' comments (other than IE conditional ones) and trailing
+	/// whitespace, leaving `<pre>`, `<textarea>`, `<script>` and `<style>` alone; by default, builds
+	/// that embed content do
+	pub fn tidy_html(mut self, tidy_html: bool) -> Self {
+		self.tidy_html = Some(tidy_html);
+		self
+	}
+
+	/// Whether to minify HTML pages in release builds that embed content, beyond tidying them:
+	/// comments go and every run of whitespace between tags collapses to a single space (still
+	/// leaving `<pre>`, `<textarea>`, `<script>` and `<style>` alone).  Off by default, since it
+	/// changes text laid out with CSS's `white-space: pre`.
+	pub fn minify_html(mut self, minify_html: bool) -> Self {
+		self.minify_html = minify_html;
+		self
+	}
+
+	/// Minifies embedded stylesheets and scripts served at paths with any of `extensions` (`css`
+	/// for CSS, `js` or `mjs` for JavaScript): comments go (but for `/*! ... */` licence comments),
+	/// as does the whitespace they don't need, though scripts keep their line breaks.  None are by
+	/// default.
+	pub fn minify_extensions<I, S>(mut self, extensions: I) -> Self where I: IntoIterator<Item = S>, S: Into<String> {
+		self.minify_extensions = extensions.into_iter().map(|e| e.into().trim_start_matches('.').to_string()).collect();
+		self
+	}
+
+	/// Logs one in every `every` responses served from here (1 for all of them, and 0, the default,
+	/// for none), and nothing the application serves itself, as combined log format lines on
+	/// standard output, or to a hook registered with `serve::on_access` (e.g. as `tracing` events)
+	pub fn access_log(mut self, every: u64) -> Self {
+		self.access_log = every;
+		self
+	}
+
+	/// Which web framework the generated module is for
+	pub fn backend(mut self, backend: Backend) -> Self {
+		self.backend = backend;
+		self
+	}
+
+	/// Warns, at build time, about pages served under `prefix` (e.g. `/advisories/`) whose
+	/// frontmatter `updated:` (or `date:`) is more than `days` old, as a reminder to review them.
+	/// Where several prefixes cover a page, the longest decides.
+	pub fn stale_after<S>(mut self, prefix: S, days: u32) -> Self where S: Into<String> {
+		self.stale_after.push((prefix.into(), days));
+		self
+	}
+
+	/// Checks that every link between pages here to a fragment (`/docs/install.html#linux`, or just
+	/// `#linux`) names an id the target page has, reporting each that doesn't as a build warning,
+	/// or with `deny`, failing the build
+	pub fn check_anchors(mut self, deny: bool) -> Self {
+		self.check_anchors = Some(deny);
+		self
+	}
+
+	/// Reports every page that no chain of links from the root page reaches, and every other file
+	/// (image, stylesheet, download) that none of those pages uses, as a build warning, or with
+	/// `deny`, failing the build
+	pub fn check_orphans(mut self, deny: bool) -> Self {
+		self.check_orphans = Some(deny);
+		self
+	}
+
+	/// Reports every URL of `file` (an old site's `sitemap.xml`, or a list of URLs or paths, one a
+	/// line) that nothing here serves (or redirects from, or passes through) as a build warning, or
+	/// with `deny`, failing the build, for moving a site here without breaking links into it.
+	/// Where a page's path ends in the same name as a URL's, the page is suggested for a
+	/// `redirect_from:` list in its frontmatter, whose paths (under the mount prefix) redirect to it.
+	pub fn check_old_urls<P>(mut self, file: P, deny: bool) -> Self where P: Into<PathBuf> {
+		self.old_urls = Some((file.into(), deny));
+		self
+	}
+
+	/// Adds schema.org JSON-LD to every HTML page that has none of its own: `WebSite` (named
+	/// `site_name`) on the root page, `BreadcrumbList` on the rest, and `Article` on those whose
+	/// frontmatter has a `date:`.  `base_url` is where the site is published.  Dev builds, which
+	/// render each page as it's requested, go without.
+	pub fn json_ld<N, U>(mut self, site_name: N, base_url: U) -> Self where N: Into<String>, U: Into<String> {
+		self.json_ld = Some((site_name.into(), base_url.into()));
+		self
+	}
+
+	/// Reads site-wide settings from `file` (e.g. `site.yaml`), overlaid, when the
+	/// `STATIC_BUILDER_ENV` environment variable names an environment (e.g. `production`), by the
+	/// same keys in its file (`site.production.yaml`).  All of them are `site` in templates (for
+	/// `{% if site.analytics %}` and the like), and some mean more: `base_url` replaces the base URL
+	/// given for the sitemap, feeds, search and JSON-LD; `robots` serves `/robots.txt`, letting every
+	/// crawler in (`allow`), keeping them all out (`deny`) or as written; and `headers` maps globs
+	/// (as for `rule`) to headers for matching files.
+	pub fn site_config<P>(mut self, file: P) -> Self where P: Into<PathBuf> {
+		self.site_config = Some(file.into());
+		self
+	}
+
+	// Loads the site config, if there is one, and puts its base URL wherever one's wanted
+	fn site(&mut self) -> Result<Option<SiteConfig>, String> {
+		let Some(file) = &self.site_config else { return Ok(None) };
+		let site = SiteConfig::load(file)?;
+
+		if let Some(base_url) = &site.base_url {
+			for url in [self.sitemap.as_mut().map(|s| &mut s.1), self.search.as_mut().map(|s| &mut s.2), self.json_ld.as_mut().map(|j| &mut j.1)].into_iter().flatten() {
+				url.clone_from(base_url);
+			}
+			for feed in &mut self.feeds {
+				feed.base_url.clone_from(base_url);
+			}
+		}
+
+		Ok(Some(site))
+	}
+
+	fn scan(&mut self) -> Result<(Scan, Option<SiteConfig>), String> {
+		let site = self.site()?;
+		let rules = match &site {
+			Some(site) => [site.rules(), self.rules.clone()].concat(),
+			None => self.rules.clone(),
+		};
+
+		let mut scan = scan_resources(&self.base_path, &rules)?;
+		scan.resources = scan.resources.into_iter().map(|r| r.with_renderers(&self.renderers)).collect();
+		if !self.commands.is_empty() {
+			scan.resources = command::assign(scan.resources, &self.base_path, &self.commands)?;
+		}
+
+		Ok((scan, site))
+	}
+
+	/// Renders sources with `extension` (without its dot, e.g. `org`) with `renderer`, whose HTML
+	/// is then laid out like a djot page's, and served as `.html`; this takes precedence over the
+	/// built-in formats' extensions.  What it renders is embedded even in dev builds, as it only
+	/// runs here.
+	pub fn renderer<S, R>(mut self, extension: S, renderer: R) -> Self where S: Into<String>, R: ContentRenderer + 'static {
+		self.renderers.push((extension.into(), Arc::new(renderer)));
+		self
+	}
+
+	/// Serves what `program` writes to its standard output, run with `args`, in place of every file
+	/// whose path (relative to the base path) matches `glob`, at the file's own path, e.g.
+	/// `command("styles/site.css", "tailwindcss", ["-i", "{source}", "-o", "-"])`.  `{source}` in
+	/// an argument stands for the file's path, which is also written to the program's standard input.
+	/// Of several commands whose globs match, the last applies.  Commands run at build time, dev
+	/// builds included, from the package's directory; any other files they read need their own
+	/// `cargo::rerun-if-changed`.
+	pub fn command<S, P, I, A>(mut self, glob: S, program: P, args: I) -> Self where S: Into<String>, P: Into<String>, I: IntoIterator<Item = A>, A: Into<String> {
+		self.commands.push((glob.into(), (program.into(), args.into_iter().map(Into::into).collect())));
+		self
+	}
+
+	/// Also serves a PDF of every HTML page whose source (relative to the base path) matches `glob`,
+	/// beside it (`/manual/install.pdf` for `/manual/install.html`, `index.pdf` for a directory),
+	/// rendered by `render` from the page as served, e.g. with `pdf_command`.  Can be called more
+	/// than once.  PDFs are rendered at build time, dev builds included.
+	pub fn pdf<S, F>(mut self, glob: S, render: F) -> Self where S: Into<String>, F: FnMut(&RenderedResource) -> Result<Vec<u8>, String> + 'static {
+		self.pdfs.push((glob.into(), Box::new(render)));
+		self
+	}
+
+	/// Also serves each raster image whose path (relative to the base path) matches `glob` as
+	/// `format`, encoded at build time by `encode` (given the image's source and content, e.g. an
+	/// `image_command`), to clients whose `Accept` header names it (with no less weight than the
+	/// image's own type, if it names that too), where it's smaller.  Others get the image as it is.  Can be called for each format, and clients naming both get the smaller.
+	pub fn image_variants<S, F>(mut self, glob: S, format: ImageFormat, encode: F) -> Self where S: Into<String>, F: FnMut(&Path, &[u8]) -> Result<Vec<u8>, String> + 'static {
+		self.image_variants.push((glob.into(), format, Box::new(encode)));
+		self
+	}
+
+	/// Embeds a gzipped copy of every text body (HTML, CSS, JavaScript, JSON, SVG and the like) that
+	/// compresses usefully, which is served with `content-encoding: gzip` to requests whose
+	/// `Accept-Encoding` allows it.  Dev builds, which render on request, serve bodies as they are.
+	/// The same as `precompress([Encoding::Gzip])`, or no precompression at all.
+	pub fn gzip(self, gzip: bool) -> Self {
+		self.precompress(gzip.then_some(Encoding::Gzip))
+	}
+
+	/// Like `gzip`, but with a copy in each of `encodings` that compresses usefully.  Requests get
+	/// whichever their `Accept-Encoding` wants most, and the smallest of those it wants as much.
+	pub fn precompress<I>(mut self, encodings: I) -> Self where I: IntoIterator<Item = Encoding> {
+		self.precompress.clear();
+		for encoding in encodings {
+			if !self.precompress.contains(&encoding) {
+				self.precompress.push(encoding);
+			}
+		}
+		self
+	}
+
+	/// Leaves bodies smaller than `bytes` uncompressed; the default is 256
+	pub fn compress_min_size(mut self, bytes: usize) -> Self {
+		self.compress_min_size = bytes;
+		self
+	}
+
+	/// Precompresses bodies by the extension of the path they're served at (with a path ending in
+	/// `/` counting as `.html`) rather than by whether their media type is text
+	pub fn compress_extensions<I, S>(mut self, extensions: I) -> Self where I: IntoIterator<Item = S>, S: Into<String> {
+		self.compress_extensions = Some(extensions.into_iter().map(|e| e.into().trim_start_matches('.').to_string()).collect());
+		self
+	}
+
+	/// Serves everything at a path matching `glob` with this `Cache-Control` policy, e.g.
+	/// `cache_control("*.css", "max-age=31536000, immutable")` or `cache_control("*.html",
+	/// "no-cache")`.  A glob with no `/` matches file names in any directory, and one with a `/`
+	/// the whole path (less its leading `/`), with a directory route counting as its `index.html`.
+	/// Later policies win over earlier ones, and a rule's own `cache-control` header over both.
+	pub fn cache_control<G, P>(mut self, glob: G, policy: P) -> Self where G: Into<String>, P: Into<String> {
+		self.cache_control.push((glob.into(), policy.into()));
+		self
+	}
+
+	/// Answers requests for paths nothing serves with the page rendered from `source` (relative to
+	/// the base path, e.g. `404.dj`) and a 404, rather than an empty 404.  The page is still served
+	/// at its own path, which `check_orphans` counts as reachable; `noindex: true` in its frontmatter
+	/// keeps it out of the sitemap.  Content sets answer with their own page at that path, if any.
+	pub fn not_found<P>(mut self, source: P) -> Self where P: AsRef<Path> {
+		self.not_found = Some(source.as_ref().to_path_buf());
+		self
+	}
+
+	/// Serves the page rendered from `variant` in place of the one from `source` (both relative to
+	/// the base path) from `from` until `until`, which are RFC 3339 times or `YYYY-MM-DD` dates (at
+	/// midnight UTC), e.g. a holiday banner's week.  Which is served is decided by the server's clock
+	/// on each request, so there's no rebuild at either end; caching is capped at the next switch.  A
+	/// page can have several windows, which mustn't overlap.  The variant isn't served at a path of
+	/// its own, nor listed anywhere, but content sets rendering both pages switch them too.
+	pub fn schedule<P, Q, F, U>(mut self, source: P, variant: Q, from: F, until: U) -> Self where P: AsRef<Path>, Q: AsRef<Path>, F: Into<String>, U: Into<String> {
+		self.schedules.push((source.as_ref().to_path_buf(), variant.as_ref().to_path_buf(), from.into(), until.into()));
+		self
+	}
+
+	// Each scheduled variant's source, and the page (by source) it's served in place of, when
+	fn windows(&self) -> Result<Vec<(PathBuf, Window)>, String> {
+		let mut windows: Vec<(PathBuf, Window)> = vec![];
+
+		for (source, variant, from, until) in &self.schedules {
+			let time = |t: &str| freshness::parse_time(t).ok_or_else(|| format!("invalid time {t:?} scheduling {}", variant.display()));
+			let (page, variant, from, until) = (self.base_path.join(source), self.base_path.join(variant), time(from)?, time(until)?);
+
+			if from >= until {
+				return Err(format!("{} is scheduled to end before it starts", variant.display()));
+			}
+			if page == variant {
+				return Err(format!("{} is scheduled in place of itself", page.display()));
+			}
+			if let Some((other, _)) = windows.iter().find(|(_, (p, f, u))| *p == page && *f < until && from < *u) {
+				return Err(format!("{} and {} are scheduled in place of {} at once", other.display(), variant.display(), page.display()));
+			}
+			windows.push((variant, (page, from, until)));
+		}
+
+		Ok(windows)
+	}
+
+	/// Splits the generated route tables into a module per top-level directory (under any mount
+	/// prefix), with the parent table dispatching to them, so that big sites don't generate one
+	/// enormous table.  `Backend::Map` has no route tables to split.
+	pub fn shard_routes(mut self, shard_routes: bool) -> Self {
+		self.shard_routes = shard_routes;
+		self
+	}
+
+	/// Fails the build on any path served that couldn't be a file name everywhere an export or CDN
+	/// sync might put it: Windows-reserved names (`CON`, `aux.js`), characters Windows doesn't
+	/// allow, a trailing dot or space, or too long a segment or path.  With `rename`, such paths
+	/// are instead renamed (`aux_.js`) with a warning, where that's enough.
+	pub fn portable_paths(mut self, rename: bool) -> Self {
+		self.portable_paths = Some(rename);
+		self
+	}
+
+	/// Compares the routes generated (with their sizes and hashes) against the manifest of them
+	/// committed at `file`, and reports every one added, removed or changed as a build warning, for
+	/// reviewing content changes in CI logs.  The manifest is written when `file` doesn't exist, or
+	/// the `STATIC_BUILDER_UPDATE_MANIFEST` environment variable is set.
+	pub fn route_manifest<P>(mut self, file: P) -> Self where P: Into<PathBuf> {
+		self.manifest = Some(file.into());
+		self
+	}
+
+	/// Writes which pages link to which, and which files (images, stylesheets, downloads) each uses,
+	/// to `file` on each build, as Graphviz DOT if it's a `.dot` file and JSON (`nodes` by path, and
+	/// `edges` from one path to another) otherwise, for visualizing or for working out what a change
+	/// affects.  It's the graph `check_orphans` walks, taken after `before_codegen`.
+	pub fn reference_graph<P>(mut self, file: P) -> Self where P: Into<PathBuf> {
+		self.reference_graph = Some(file.into());
+		self
+	}
+
+	/// Writes every path served (by any set, or as a redirect) and every `passthrough` prefix to
+	/// `file` on each build, as `format`, so that a proxy in front can turn away requests for
+	/// anything else before they reach the application
+	pub fn path_allowlist<P>(mut self, file: P, format: Allowlist) -> Self where P: Into<PathBuf> {
+		self.allowlist = Some((file.into(), format));
+		self
+	}
+
+	/// Whether to embed content in the binary, rather than rendering it from disk on each request.
+	/// By default, release builds (including custom profiles that inherit from `release`) and
+	/// cross-compiled builds, whose content directory won't be there on the target, embed, and
+	/// others don't.  `Backend::Map` always embeds.
+	pub fn embed(mut self, embed: bool) -> Self {
+		self.embed = Some(embed);
+		self
+	}
+
+	fn embeds(&self) -> bool {
+		if self.backend == Backend::Map {
+			return true;
+		}
+
+		if let Some(embed) = self.embed {
+			return embed;
+		}
+
+		cross_compiling() || env::var("PROFILE").is_ok_and(|p| p == "release")
+	}
+
+	/// Applies `f` only when building with the named cargo profile (see `build_profile`), e.g.
+	/// `.for_profile("release-lto", |b| b.deny_panic(true))`
+	pub fn for_profile<S, F>(self, profile: S, f: F) -> Self where S: AsRef<str>, F: FnOnce(Self) -> Self {
+		if build_profile() == profile.as_ref() { f(self) } else { self }
+	}
+
+	/// Serves everything under `prefix` (e.g. `/static`) rather than at the root
+	pub fn mount_prefix<S>(mut self, prefix: S) -> Self where S: AsRef<str> {
+		let prefix = prefix.as_ref().trim_matches('/');
+		self.mount_prefix = if prefix.is_empty() { String::new() } else { format!("/{prefix}") };
+		self
+	}
+
+	pub fn root_index(mut self, root_index: RootIndex) -> Self {
+		self.root_index = root_index;
+		self
+	}
+
+	fn mount(&self, page: &mut RenderedResource) {
+		let is_root = page.paths.iter().chain(&page.redirects).any(|p| p == "/");
+
+		for paths in [&mut page.paths, &mut page.redirects] {
+			let mut mounted = vec![];
+
+			for path in paths.drain(..) {
+				if path == "/" {
+					if matches!(self.root_index, RootIndex::IndexOnly) {
+						continue;
+					}
+
+					mounted.push(format!("{}/", self.mount_prefix));
+
+					if !self.mount_prefix.is_empty() {
+						mounted.push(self.mount_prefix.clone());
+					}
+				} else if !(is_root && path == "/index.html" && matches!(self.root_index, RootIndex::DirectoryOnly)) {
+					mounted.push(format!("{}{path}", self.mount_prefix));
+				}
+			}
+
+			*paths = mounted;
+		}
+
+		// Dropping the canonical route promotes the first redirect in its place
+		if page.paths.is_empty() && !page.redirects.is_empty() {
+			page.paths.push(page.redirects.remove(0));
+		}
+	}
+
+	pub fn render_tree(&mut self) -> Result<Vec<RenderedResource>, String> {
+		let (scan, site) = self.scan()?;
+
+		Ok(self.render_all(scan.resources, site.as_ref())?.into_iter().map(|(_, rendered)| rendered).collect())
+	}
+
+	// Each source is read and rendered exactly once; everything downstream works from the rendered copy
+	fn render_all(&mut self, mut resources: Vec<Resource>, site: Option<&SiteConfig>) -> Result<Vec<(Resource, RenderedResource)>, String> {
+		let started = Instant::now();
+		let mut last_heartbeat = started;
+		let total = resources.len();
+		let mut rendered: Vec<(Resource, RenderedResource)> = vec![];
+		let assets = if self.asset_namespace { Some(Arc::new(self.asset_urls(&resources)?)) } else { None };
+		let windows = self.windows()?;
+
+		// Templates see every other page, so they're rendered last
+		resources.sort_by_key(Resource::is_template);
+		let mut pages = None;
+
+		for (n, r) in resources.into_iter().enumerate() {
+			let r = match &assets {
+				Some(assets) => r.with_assets(assets.clone()),
+				None => r,
+			};
+			let r = match r.is_template() {
+				true => {
+					let pages = pages.get_or_insert_with(|| {
+						let pages = rendered.iter().filter(|(r, _)| !windows.iter().any(|(variant, _)| *variant == r.source)).map(|(_, page)| page);
+						Arc::new(page_index::template_pages(pages))
+					});
+					r.with_pages(pages.clone())
+				},
+				false => r,
+			};
+			let progress = Progress { done: n, total, current: &r.source, elapsed: started.elapsed() };
+
+			if let Some(on_progress) = &mut self.on_progress {
+				on_progress(&progress);
+			} else if last_heartbeat.elapsed() >= HEARTBEAT_INTERVAL {
+				println!("cargo::warning=static-builder: {progress}");
+				last_heartbeat = Instant::now();
+			}
+
+			let mut page = r.render()?;
+			let asset_url = assets.as_ref().and_then(|assets| assets.get(&page.paths[0])).cloned();
+			self.mount(&mut page);
+			page.paths.extend(asset_url);
+
+			// Most likely a page that hasn't been written yet, which is still served, just blank
+			if !r.is_virtual() && !r.pipeline().is_empty() && fs::read_to_string(&r.source).is_ok_and(|s| s.trim().is_empty()) {
+				println!("cargo::warning=static-builder: {} is empty, and is served as an empty page", r.source.display());
+			}
+
+			rendered.push((r, page));
+		}
+
+		// Scheduled variants are only ever served in place of their pages, so nothing else sees them
+		if !windows.is_empty() {
+			let (variants, pages): (Vec<_>, Vec<_>) = rendered.into_iter().partition(|(r, _)| windows.iter().any(|(variant, _)| *variant == r.source));
+			rendered = pages;
+
+			for (variant, (page, _, _)) in &windows {
+				if !variants.iter().any(|(r, _)| r.source == *variant) {
+					return Err(format!("scheduled variant {} isn't in the content tree", variant.display()));
+				}
+				if !rendered.iter().any(|(r, _)| r.source == *page) {
+					return Err(format!("{}, scheduled in place of {}, isn't in the content tree", variant.display(), page.display()));
+				}
+			}
+			self.variants = variants.into_iter().map(|(r, page)| {
+				let own = windows.iter().filter(|(variant, _)| *variant == r.source).map(|(_, window)| window.clone()).collect();
+				(r.with_windows(own), page)
+			}).collect();
+		}
+
+		if let Some(route) = &self.fields_index {
+			let entries: Vec<_> = rendered.iter().filter(|(_, page)| !page.fields.is_empty()).map(|(_, page)| serde_json::json!({
+				"url": page.paths[0],
+				"title": page.title(),
+				"fields": page.fields,
+			})).collect();
+
+			let r = Resource::from_bytes(PathBuf::from(route), serde_json::to_vec_pretty(&entries).unwrap());
+			let mut page = r.render()?;
+			self.mount(&mut page);
+			rendered.push((r, page));
+		}
+
+		// Browsers probe the root for icons, so these aliases ignore the mount prefix
+		for (route, source) in &self.icons {
+			let source = self.base_path.join(source);
+			let (_, page) = rendered.iter_mut().find(|(r, _)| r.source == source).ok_or_else(|| format!("icon {} isn't in the content tree", source.display()))?;
+
+			if !page.paths.iter().any(|p| p == route) {
+				page.paths.push(route.to_string());
+			}
+		}
+
+		if !self.favicon_ico.is_empty() {
+			let pngs = self.favicon_ico.iter().map(|source| {
+				let source = self.base_path.join(source);
+				fs::read(&source).map(|png| (source.clone(), png)).map_err(|e| format!("failed to read icon {}: {e}", source.display()))
+			}).collect::<Result<Vec<_>, String>>()?;
+
+			if rendered.iter().any(|(_, page)| page.paths.iter().any(|p| p == "/favicon.ico")) {
+				return Err("the generated /favicon.ico would be served over another".to_string());
+			}
+
+			let r = Resource::from_bytes(PathBuf::from("/favicon.ico"), icon::ico(&pngs)?);
+			let page = r.render()?;
+			rendered.push((r, page));
+		}
+
+		let mut listings = vec![];
+
+		for feed in &self.feeds {
+			let r = Resource::from_bytes(PathBuf::from(&feed.route), feed.render(rendered.iter().map(|(_, page)| page), &self.mount_prefix)?)
+				.with_content_type("application/atom+xml");
+			let mut page = r.render()?;
+			self.mount(&mut page);
+			listings.push((r, page));
+		}
+
+		for (route, section) in &self.page_indexes {
+			let r = Resource::from_bytes(PathBuf::from(route), page_index::page_index(&format!("{}{section}", self.mount_prefix), rendered.iter().map(|(_, page)| page)));
+			let mut page = r.render()?;
+			self.mount(&mut page);
+			listings.push((r, page));
+		}
+
+		if let Some((route, short_name, base_url)) = &self.search {
+			let index_route = match self.page_indexes.first() {
+				Some((index_route, _)) => index_route.clone(),
+				None => {
+					let r = Resource::from_bytes(PathBuf::from("/index.json"), page_index::page_index("", rendered.iter().map(|(_, page)| page)));
+					let mut page = r.render()?;
+					self.mount(&mut page);
+					listings.push((r, page));
+					"/index.json".to_string()
+				},
+			};
+
+			let r = Resource::from_bytes(PathBuf::from(route), search::page(short_name, &format!("{}{index_route}", self.mount_prefix)))
+				.with_rule(Rule::new().render([]));
+			let mut page = r.render()?;
+			self.mount(&mut page);
+			let search_url = format!("{}{}", base_url.trim_end_matches('/'), page.paths[0]);
+			listings.push((r, page));
+
+			let r = Resource::from_bytes(PathBuf::from("/opensearch.xml"), search::descriptor(short_name, &search_url))
+				.with_content_type("application/opensearchdescription+xml");
+			let mut page = r.render()?;
+			self.mount(&mut page);
+			listings.push((r, page));
+		}
+
+		rendered.append(&mut listings);
+
+		let mut sitemap_url = None;
+
+		if let Some((route, base_url)) = &self.sitemap {
+			let r = Resource::from_bytes(PathBuf::from(route), sitemap::sitemap(base_url, rendered.iter().map(|(_, page)| page))?);
+			let mut page = r.render()?;
+			self.mount(&mut page);
+			sitemap_url = Some(format!("{}{}", base_url.trim_end_matches('/'), page.paths[0]));
+			rendered.push((r, page));
+		}
+
+		// Crawlers only look for it at the root, so it ignores the mount prefix
+		if let Some(robots) = site.and_then(|site| site.robots(sitemap_url.as_deref())) {
+			if rendered.iter().any(|(_, page)| page.paths.iter().any(|p| p == "/robots.txt")) {
+				return Err("the site config's robots would be served over the content's /robots.txt".to_string());
+			}
+
+			let r = Resource::from_bytes(PathBuf::from("/robots.txt"), robots.into_bytes());
+			let page = r.render()?;
+			rendered.push((r, page));
+		}
+
+		Ok(rendered)
+	}
+
+	/// Called before each resource is rendered.  Without a callback, long builds emit a
+	/// `cargo::warning` heartbeat every few seconds instead, so they don't look hung.
+	pub fn on_progress<F>(mut self, f: F) -> Self where F: FnMut(&Progress<'_>) + 'static {
+		self.on_progress = Some(Box::new(f));
+		self
+	}
+
+	/// Called with every rendered resource (after all the checks and post-processing) just before
+	/// code is generated for them, to add, drop or change any; paths are as served, under any mount
+	/// prefix.  An error fails the build.  Dev builds render pages as they're requested, so changes
+	/// to their bodies only apply to virtual resources there.
+	pub fn before_codegen<F>(mut self, f: F) -> Self where F: FnMut(&mut Vec<(Resource, RenderedResource)>) -> Result<(), String> + 'static {
+		self.before_codegen = Some(Box::new(f));
+		self
+	}
+
+	/// Adds another named content set, rendered from its own builder (which can use the same
+	/// base path with different rules, e.g. another brand's `layouts`), for the generated
+	/// `StaticContentSets` service to choose between at runtime.
+	pub fn content_set<S>(mut self, name: S, set: Builder) -> Self where S: Into<String> {
+		self.sets.push((name.into(), set));
+		self
+	}
+
+	/// Makes each subdirectory of `dir` (relative to the base path, e.g. `sites`) a content set of
+	/// its own, named for the hostname it's served to (`sites/example.com/**` for `Host:
+	/// example.com`), for `StaticContentSets::by_host()`.  Nothing under `dir` is in the default set.
+	pub fn hosts_dir<P>(mut self, dir: P) -> Self where P: AsRef<Path> {
+		self.hosts_dir = Some(dir.as_ref().to_path_buf());
+		self
+	}
+
+	/// Spell checks the prose of every HTML page against the given word lists (e.g.
+	/// `/usr/share/dict/words` plus the project's own jargon), emitting a build warning with the
+	/// source file and line of each unknown word.  Can be called more than once.
+	pub fn spellcheck<P>(mut self, dictionary: P) -> Self where P: Into<PathBuf> {
+		self.dictionaries.push(dictionary.into());
+		self
+	}
+
+	fn host_sets(&self) -> Result<Vec<(String, Builder)>, String> {
+		let Some(dir) = &self.hosts_dir else { return Ok(vec![]) };
+		let dir = self.base_path.join(dir);
+		let mut sets = vec![];
+
+		for entry in fs::read_dir(&dir).map_err(|e| format!("failed to read hosts directory {}: {e}", dir.display()))? {
+			let entry = entry.map_err(|e| format!("failed to read hosts directory {}: {e}", dir.display()))?;
+
+			if entry.file_type().map_err(|e| format!("failed to stat {}: {e}", entry.path().display()))?.is_dir() {
+				let host = entry.file_name().to_string_lossy().to_lowercase();
+				let mut set = Builder::new(entry.path()).mount_prefix(&self.mount_prefix).root_index(self.root_index);
+				set.rules.clone_from(&self.rules);
+				set.dictionaries.clone_from(&self.dictionaries);
+				sets.push((host, set));
+			}
+		}
+
+		sets.sort_by(|a, b| a.0.cmp(&b.0));
+
+		Ok(sets)
+	}
+
+	// Gives content sets what they don't say for themselves
+	fn inherit(&self, sets: &mut [(String, Builder)]) {
+		for (_, set) in sets {
+			set.embed = set.embed.or(self.embed);
+			set.portable_paths = set.portable_paths.or(self.portable_paths);
+			if set.site_config.is_none() {
+				set.site_config.clone_from(&self.site_config);
+			}
+		}
+	}
+
+	// The body of every route `write` embeds, by content set (`None` for this builder's own), and
+	// the directories and files they were rendered from
+	#[cfg(feature = "watch")]
+	pub(crate) fn routes(&mut self) -> Result<(crate::watch::Routes, Vec<PathBuf>), String> {
+		let mut routes = crate::watch::Routes::new();
+		let mut read = vec![];
+		let mut add = |set: Option<&str>, builder: &mut Builder, passthrough: &[String]| -> Result<(), String> {
+			let mut rendered = builder.prepare()?;
+			drop_passthrough(passthrough, &mut rendered);
+			read.extend(builder.watch_dirs.iter().chain(&builder.rebuild_on).cloned());
+			for (_, page) in rendered {
+				routes.extend(page.paths.into_iter().map(|path| ((set.map(str::to_string), path), page.body.clone())));
+			}
+			Ok(())
+		};
+
+		let passthrough = self.passthrough.clone();
+		add(None, self, &passthrough)?;
+		// Host sets are found afresh each time, and only kept until they're rendered
+		let mut sets = mem::take(&mut self.sets);
+		let own = sets.len();
+		let rendered = self.host_sets().and_then(|mut host_sets| {
+			sets.append(&mut host_sets);
+			self.inherit(&mut sets);
+			sets.iter_mut().try_for_each(|(name, set)| add(Some(name), set, &passthrough))
+		});
+		sets.truncate(own);
+		self.sets = sets;
+		rendered?;
+
+		read.sort();
+		read.dedup();
+
+		Ok((routes, read))
+	}
+
+	// Where the page `not_found` names is served
+	fn not_found_path(&self, rendered: &[(Resource, RenderedResource)]) -> Result<Option<String>, String> {
+		let Some(source) = &self.not_found else { return Ok(None) };
+		let source = self.base_path.join(source);
+		let (_, page) = rendered.iter().find(|(r, _)| r.source == source).ok_or_else(|| format!("not-found page {} isn't in the content tree", source.display()))?;
+
+		Ok(Some(page.paths[0].clone()))
+	}
+
+	fn prepare(&mut self) -> Result<Vec<(Resource, RenderedResource)>, String> {
+		let (mut scan, site) = self.scan()?;
+
+		if let Some(dir) = &self.hosts_dir {
+			let dir = self.base_path.join(dir);
+			scan.resources.retain(|r| !r.source.starts_with(&dir));
+		}
+
+		for dep in &scan.dependencies {
+			println!("cargo::rerun-if-changed={}", dep.display());
+		}
+
+		self.watch_dirs = vec![self.base_path.clone()];
+		for r in &scan.resources {
+			for dir in [r.rule().layouts.as_deref().unwrap_or(Path::new("layouts")), r.rule().partials.as_deref().unwrap_or(Path::new("partials"))] {
+				if !self.watch_dirs.iter().any(|d| d == dir) {
+					self.watch_dirs.push(dir.to_path_buf());
+				}
+			}
+		}
+		self.rebuild_on = scan.dependencies.iter().filter(|dep| dep.is_file()).cloned().chain(self.site_config.clone()).collect();
+
+		for r in &scan.resources {
+			if let Some(name) = r.rule().headers.keys().find(|name| codegen::SERVED_HEADERS.contains(&name.as_str())) {
+				return Err(format!("{}: rules can't set {name}, which depends on how each response is served", r.source.display()));
+			}
+		}
+
+		// Templates are rendered from every page, so a change to any is a change to them
+		let reruns = self.embeds() || scan.resources.iter().any(Resource::is_template);
+		for r in scan.resources.iter().filter(|r| !r.is_virtual() && (reruns || r.renders_at_build_time())) {
+			println!("cargo::rerun-if-changed={}", r.source.display());
+			self.rebuild_on.push(r.source.clone());
+			for dependency in r.dependencies()? {
+				println!("cargo::rerun-if-changed={}", dependency.display());
+				self.rebuild_on.push(dependency);
+			}
+		}
+
+		let mut rendered = self.render_all(scan.resources, site.as_ref())?;
+
+		if let Some(rename) = self.portable_paths {
+			portable::check(&mut rendered, rename)?;
+		}
+
+		if let Some((site_name, base_url)) = &self.json_ld {
+			json_ld::inject(&mut rendered, site_name, base_url, &self.mount_prefix);
+		}
+
+		let minify = self.minify_html && self.embeds() && env::var("PROFILE").is_ok_and(|p| p == "release");
+		if minify || self.tidy_html.unwrap_or_else(|| self.embeds()) {
+			let clean = if minify { tidy::minify } else { tidy::tidy };
+			for (_, page) in rendered.iter_mut().chain(&mut self.variants).filter(|(_, page)| page.content_type.starts_with("text/html")) {
+				if let Ok(html) = std::str::from_utf8(&page.body) {
+					page.body = clean(html).into_bytes();
+				}
+			}
+		}
+
+		if !self.minify_extensions.is_empty() {
+			let embeds = self.embeds();
+			for (r, page) in rendered.iter_mut().filter(|(r, _)| embeds || r.renders_at_build_time()) {
+				let ext = Path::new(&page.paths[0]).extension().and_then(|e| e.to_str()).unwrap_or_default();
+				let minify = match ext {
+					_ if !self.minify_extensions.iter().any(|e| e == ext) => continue,
+					"css" => minify::css,
+					"js" | "mjs" => minify::js,
+					_ => return Err(format!("{}: can't minify .{ext} files", r.source.display())),
+				};
+				if let Ok(text) = std::str::from_utf8(&page.body) {
+					page.body = minify(text).into_bytes();
+				}
+			}
+		}
+
+		for (glob, render) in &mut self.pdfs {
+			pdf::add(&mut rendered, &self.base_path, glob, render)?;
+		}
+
+		for (glob, format, encode) in &mut self.image_variants {
+			images::add(&mut rendered, &self.base_path, glob, *format, encode)?;
+		}
+
+		if !self.stale_after.is_empty() {
+			freshness::check(&self.stale_after, rendered.iter().map(|(_, page)| page), &self.mount_prefix);
+		}
+
+		if let Some(deny) = self.check_anchors {
+			let broken = anchors::broken(rendered.iter().map(|(_, page)| page));
+
+			if deny && !broken.is_empty() {
+				return Err(format!("broken fragment links:\n{}", broken.join("\n")));
+			}
+
+			for link in broken {
+				println!("cargo::warning={link}");
+			}
+		}
+
+		if let Some(deny) = self.check_orphans {
+			let orphans = orphans::orphans(&rendered, &self.mount_prefix, self.not_found_path(&rendered)?.as_deref());
+
+			if deny && !orphans.is_empty() {
+				return Err(format!("unreachable content:\n{}", orphans.join("\n")));
+			}
+
+			for orphan in orphans {
+				println!("cargo::warning={orphan}");
+			}
+		}
+
+		if let Some((file, deny)) = &self.old_urls {
+			println!("cargo::rerun-if-changed={}", file.display());
+			let uncovered = old_urls::uncovered(file, &rendered, &self.mount_prefix, &self.passthrough)?;
+
+			if *deny && !uncovered.is_empty() {
+				return Err(format!("old URLs not served:\n{}", uncovered.join("\n")));
+			}
+
+			for url in uncovered {
+				println!("cargo::warning=static-builder: {url}");
+			}
+		}
+
+		if !self.dictionaries.is_empty() {
+			for dictionary in &self.dictionaries {
+				println!("cargo::rerun-if-changed={}", dictionary.display());
+			}
+
+			let dictionary = Dictionary::load(&self.dictionaries)?;
+
+			for (_, page) in &rendered {
+				spellcheck::check(page, &dictionary);
+			}
+		}
+
+		if let Some(before_codegen) = &mut self.before_codegen {
+			before_codegen(&mut rendered)?;
+		}
+
+		if let Some(file) = &self.reference_graph {
+			graph::write(file, &rendered)?;
+		}
+
+		Ok(rendered)
+	}
+
+	pub fn write(mut self, fd: &mut File) -> Result<(), io::Error> {
+		let dev = !self.embeds();
+
+		if self.embed.is_none() && cross_compiling() && env::var("PROFILE").is_ok_and(|p| p != "release") {
+			println!("cargo::warning=static-builder: embedding content, as this build is for {} rather than the build host; use Builder::embed(false) to serve it from disk anyway", build_target());
+		}
+		let mut rendered = self.prepare().map_err(io::Error::other)?;
+		let variants = mem::take(&mut self.variants);
+		let not_found = self.not_found_path(&rendered).map_err(io::Error::other)?;
+		drop_passthrough(&self.passthrough, &mut rendered);
+		let mut sets = mem::take(&mut self.sets);
+		sets.append(&mut self.host_sets().map_err(io::Error::other)?);
+		self.inherit(&mut sets);
+		self.sets = sets;
+		let mut watch_dirs: Vec<String> = self.watch_dirs.iter().map(|d| d.display().to_string()).collect();
+		let mut rebuild_on: Vec<String> = self.rebuild_on.iter().map(|f| f.display().to_string()).collect();
+		let (mut sets, set_variants): (Vec<_>, Vec<_>) = self.sets.iter_mut().map(|(name, set)| {
+			let prepared = set.prepare().map_err(io::Error::other)?;
+			watch_dirs.extend(set.watch_dirs.iter().map(|d| d.display().to_string()));
+			rebuild_on.extend(set.rebuild_on.iter().map(|f| f.display().to_string()));
+			Ok(((name.as_str(), prepared), mem::take(&mut set.variants)))
+		}).collect::<Result<Vec<_>, io::Error>>()?.into_iter().unzip();
+		watch_dirs.sort();
+		watch_dirs.dedup();
+		rebuild_on.sort();
+		rebuild_on.dedup();
+
+		for (_, set) in &mut sets {
+			drop_passthrough(&self.passthrough, set);
+		}
+
+		if !rendered.iter().chain(sets.iter().flat_map(|(_, set)| set)).any(|(r, _)| !r.is_virtual()) {
+			if !self.allow_empty {
+				return Err(io::Error::other(format!("no content found in {}; use Builder::allow_empty(true) if that's intended", self.base_path.display())));
+			}
+
+			println!("cargo::warning=static-builder: no content found in {}, so every request will get a 404", self.base_path.display());
+		}
+
+		if let Some((file, format)) = &self.allowlist {
+			let all = std::iter::once(rendered.as_slice()).chain(sets.iter().map(|(_, set)| set.as_slice()));
+			allowlist::write(file, *format, all, &self.passthrough).map_err(io::Error::other)?;
+		}
+
+		if let Some(file) = &self.manifest {
+			let all = std::iter::once(("", rendered.as_slice())).chain(sets.iter().map(|(name, set)| (*name, set.as_slice())));
+			manifest::compare(file, &manifest::manifest(all)).map_err(io::Error::other)?;
+		}
+
+		// Variants only join in now, as route tables are all that serve them
+		rendered.extend(variants);
+		for ((_, set), variants) in sets.iter_mut().zip(set_variants) {
+			set.extend(variants);
+		}
+
+		let cache_control = codegen::cache_policies(&self.cache_control).map_err(io::Error::other)?;
+		let options = codegen::Options {
+			passthrough: &self.passthrough,
+			deny_panic: self.deny_panic,
+			dev,
+			inline_limit: self.inline_limit,
+			external_bodies: self.external_bodies.as_ref().map(|(t, d)| (*t, d.as_path())),
+			immutable_prefix: self.asset_namespace.then(|| format!("{}{}", self.mount_prefix, assets::NAMESPACE)),
+			cache_control: &cache_control,
+			tests: self.http_tests,
+			methods: &self.methods,
+			not_found: not_found.as_deref(),
+			encodings: &self.precompress,
+			compress_min_size: self.compress_min_size,
+			compress_extensions: self.compress_extensions.as_deref(),
+			shard_under: self.shard_routes.then(|| self.mount_prefix.clone()),
+			access_log: self.access_log,
+			watch: dev.then_some((watch_dirs.as_slice(), rebuild_on.as_slice())),
+			..Default::default()
+		};
+		let quoted_code = match self.backend {
+			Backend::Actix => codegen::actix_module(&rendered, &sets, &options),
+			Backend::Axum => axum::axum_module(&rendered, &sets, &options),
+			Backend::Rocket => rocket::rocket_module(&rendered, &sets, &options),
+			Backend::Warp => warp::warp_module(&rendered, &sets, &options),
+			Backend::Poem => poem::poem_module(&rendered, &sets, &options),
+			Backend::Map => map::map_module(&rendered, &sets, &options),
+		}.map_err(io::Error::other)?;
+		let syntax_tree = syn::parse2(quoted_code).map_err(io::Error::other)?;
+		writeln!(fd, "{}", prettyplease::unparse(&syntax_tree))?;
+
+		Ok(())
+	}
+}
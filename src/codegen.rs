@@ -0,0 +1,938 @@
+use globset::{GlobBuilder, GlobMatcher};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use std::{cell::RefCell, collections::{BTreeSet, HashMap}, env, fs, path::{Path, PathBuf}, time::SystemTime};
+
+use crate::{assets::fnv1a, compress::{self, Encoding}, RenderedResource, Resource, Store};
+
+#[derive(Default)]
+pub(crate) struct Options<'a> {
+	pub(crate) passthrough: &'a [String],
+	pub(crate) deny_panic: bool,
+	// Dev builds render on each request (on the runtime's blocking thread pool) rather than embedding
+	pub(crate) dev: bool,
+	pub(crate) inline_limit: usize,
+	pub(crate) external_bodies: Option<(usize, &'a Path)>,
+	// Routes under here are content-addressed, so never change
+	pub(crate) immutable_prefix: Option<String>,
+	// `Cache-Control` policies by served path, the last match winning
+	pub(crate) cache_control: &'a [(GlobMatcher, String)],
+	pub(crate) tests: bool,
+	// Compressible bodies get a variant in each of these too, if they're at least `compress_min_size`
+	// and (when there's a list) their path has one of `compress_extensions`
+	pub(crate) encodings: &'a [Encoding],
+	pub(crate) compress_min_size: usize,
+	pub(crate) compress_extensions: Option<&'a [String]>,
+	// The methods `serve` answers, rather than 405ing
+	pub(crate) methods: &'a [String],
+	// The path of the page answering requests for paths nothing serves
+	pub(crate) not_found: Option<&'a str>,
+	// Route tables are split into a shard per directory directly under this (the mount prefix)
+	pub(crate) shard_under: Option<String>,
+	// One in how many responses is logged (see `serve::log_access`), if any are
+	pub(crate) access_log: u64,
+	// In dev builds, the directories and files `watch_for_rebuild` watches
+	pub(crate) watch: Option<(&'a [String], &'a [String])>,
+	// Embedded bodies too big to inline, each given a static of its own however many routes serve it
+	pub(crate) static_bodies: RefCell<HashMap<Vec<u8>, usize>>,
+	// The blob of `Store::Packed` bodies, and where in it each one is
+	pub(crate) packed: RefCell<Vec<u8>>,
+	pub(crate) packed_ranges: RefCell<HashMap<Vec<u8>, (usize, usize)>>,
+}
+
+// `Builder::cache_control`'s globs, a glob with no `/` matching file names in any directory
+pub(crate) fn cache_policies(policies: &[(String, String)]) -> Result<Vec<(GlobMatcher, String)>, String> {
+	policies.iter().map(|(glob, policy)| {
+		let pattern = if glob.contains('/') { glob.trim_start_matches('/').to_string() } else { format!("**/{glob}") };
+		let matcher = GlobBuilder::new(&pattern).literal_separator(true).build().map_err(|e| format!("invalid glob {glob:?}: {e}"))?.compile_matcher();
+
+		Ok((matcher, policy.clone()))
+	}).collect()
+}
+
+impl Options<'_> {
+	// Whether `page`, served at `path`, is worth precompressing
+	fn compresses(&self, path: &str, r: &Resource, page: &RenderedResource) -> bool {
+		let rule = r.rule();
+		if self.encodings.is_empty() || rule.content_encoding.is_some() {
+			return false;
+		}
+		if let Some(compress) = rule.compress {
+			return compress;
+		}
+		if page.body.len() < self.compress_min_size {
+			return false;
+		}
+
+		match self.compress_extensions {
+			Some(extensions) => {
+				let name = path.rsplit('/').next().filter(|name| !name.is_empty()).unwrap_or("index.html");
+				name.rsplit_once('.').is_some_and(|(_, ext)| extensions.iter().any(|e| e.eq_ignore_ascii_case(ext)))
+			},
+			None => compress::compressible(&page.content_type),
+		}
+	}
+
+	// The headers serving `page` at `path` gets besides its content type
+	fn headers(&self, path: &str, r: &Resource, page: &RenderedResource) -> TokenStream {
+		let mut headers = vec![];
+
+		// A rule's own `cache-control` header is more specific than any policy
+		if !r.rule().headers.contains_key("cache-control") {
+			let file = match path.strip_prefix('/').unwrap_or(path) {
+				dir if dir.is_empty() || dir.ends_with('/') => format!("{dir}index.html"),
+				file => file.to_string(),
+			};
+			let policy = self.cache_control.iter().rev().find(|(glob, _)| glob.is_match(&file)).map(|(_, policy)| policy.as_str());
+			let immutable = self.immutable_prefix.as_ref().is_some_and(|prefix| path.starts_with(prefix.as_str())).then_some("public, max-age=31536000, immutable");
+
+			if let Some(policy) = policy.or(immutable) {
+				headers.push(("cache-control", policy));
+			}
+		}
+		if page.noindex() {
+			headers.push(("x-robots-tag", "noindex"));
+		}
+		if let Some(lang) = r.rule().lang.as_ref().filter(|_| page.content_type.starts_with("text/html")) {
+			headers.push(("content-language", lang));
+		}
+		if let Some(coding) = &r.rule().content_encoding {
+			headers.push(("content-encoding", coding));
+		}
+		headers.extend(r.rule().headers.iter().map(|(name, value)| (name.as_str(), value.as_str())));
+		// In the order `serve::respond` sends them in, so route tables read the same
+		headers.sort_by_key(|(name, _)| *name);
+		let headers = headers.iter().map(|(name, value)| quote! { (#name, #value) });
+
+		quote! { &[#(#headers),*] }
+	}
+
+	// A `&'static dyn serve::BodyStore` for an embedded body, by how its rule says to store it, or
+	// else by size: small ones inline, bigger ones as a shared static, and (if configured) huge ones
+	// read at runtime from a file written next to the build
+	fn body(&self, store: Option<&Store>, body: &[u8]) -> Result<TokenStream, String> {
+		match store {
+			Some(Store::Static) => {
+				let bytes = self.static_bytes(body);
+				Ok(quote! { &::static_builder::serve::Embedded(#bytes) })
+			},
+			Some(Store::Packed) => {
+				let mut blob = self.packed.borrow_mut();
+				let (start, end) = *self.packed_ranges.borrow_mut().entry(body.to_vec()).or_insert_with(|| {
+					blob.extend_from_slice(body);
+					(blob.len() - body.len(), blob.len())
+				});
+
+				Ok(quote! { &::static_builder::serve::Packed { blob: STATIC_CONTENT_PACK, start: #start, end: #end } })
+			},
+			Some(Store::Disk) => {
+				let dir = match self.external_bodies {
+					Some((_, dir)) => dir.to_path_buf(),
+					None => PathBuf::from(env::var_os("OUT_DIR").ok_or("Store::Disk needs OUT_DIR or Builder::external_bodies")?).join("static-bodies"),
+				};
+
+				self.external(&dir, body)
+			},
+			Some(Store::Custom(ty)) => {
+				let ty: syn::Path = syn::parse_str(ty).map_err(|e| format!("invalid body store type {ty:?}: {e}"))?;
+				let bytes = self.static_bytes(body);
+
+				Ok(quote! { &#ty::new(#bytes) })
+			},
+			None => match self.external_bodies.filter(|(threshold, _)| body.len() > *threshold) {
+				Some((_, dir)) => self.external(dir, body),
+				None => {
+					let bytes = self.static_bytes(body);
+					Ok(quote! { &::static_builder::serve::Embedded(#bytes) })
+				},
+			},
+		}
+	}
+
+	fn external(&self, dir: &Path, body: &[u8]) -> Result<TokenStream, String> {
+		let file = write_body(dir, body)?.display().to_string();
+		let len = body.len() as u64;
+
+		Ok(quote! { &::static_builder::serve::External(#file, #len) })
+	}
+
+	// A `&'static [u8]` expression for `body`: inline if it's small, else a static shared by every
+	// route serving it
+	pub(crate) fn static_bytes(&self, body: &[u8]) -> TokenStream {
+		if body.len() <= self.inline_limit {
+			return quote! { &[#(#body),*] };
+		}
+
+		let mut bodies = self.static_bodies.borrow_mut();
+		let next = bodies.len();
+		let i = *bodies.entry(body.to_vec()).or_insert(next);
+		let name = format_ident!("STATIC_CONTENT_BODY_{i}");
+
+		quote! { #name }
+	}
+
+	// The statics `static_bytes` and `Store::Packed` refer to, included from files in `OUT_DIR` so
+	// that the compiler isn't handed a token per byte (or, without `OUT_DIR`, as byte literals)
+	pub(crate) fn body_statics(&self) -> Result<TokenStream, String> {
+		let mut bodies: Vec<_> = self.static_bodies.borrow().iter().map(|(body, i)| (*i, body.clone())).collect();
+		bodies.sort_by_key(|(i, _)| *i);
+
+		let mut bodies: Vec<_> = bodies.into_iter().map(|(i, body)| (format_ident!("STATIC_CONTENT_BODY_{i}"), body)).collect();
+		let pack = self.packed.borrow();
+		if !pack.is_empty() {
+			bodies.push((format_ident!("STATIC_CONTENT_PACK"), pack.clone()));
+		}
+
+		let dir = env::var_os("OUT_DIR").map(|out_dir| PathBuf::from(out_dir).join("static-bodies"));
+		let statics = bodies.into_iter().map(|(name, body)| match &dir {
+			Some(dir) => {
+				let file = write_body(dir, &body)?.display().to_string();
+				Ok(quote! { static #name: &[u8] = include_bytes!(#file); })
+			},
+			None => Ok(quote! { static #name: &[u8] = &[#(#body),*]; }),
+		}).collect::<Result<Vec<_>, String>>()?;
+
+		Ok(quote! { #(#statics)* })
+	}
+
+	// `static_content_respond`, which is `serve::respond` allowing only the configured methods (and
+	// logging what it responds with, if asked to)
+	pub(crate) fn respond_fn(&self) -> TokenStream {
+		let methods = self.methods;
+		let respond = match self.not_found {
+			Some(not_found) => quote! { ::static_builder::serve::respond_with_not_found(routes, &[#(#methods),*], #not_found, method, path, &header) },
+			None => quote! { ::static_builder::serve::respond_allowing(routes, &[#(#methods),*], method, path, &header) },
+		};
+		let respond = match self.access_log {
+			0 => respond,
+			every => quote! {
+				let response = #respond;
+				::static_builder::serve::log_access(#every, method, path, &response, header);
+				response
+			},
+		};
+
+		quote! {
+			fn static_content_respond<'a, H>(routes: &'static [::static_builder::serve::Route], method: &str, path: &str, header: H) -> ::static_builder::serve::Response where H: Fn(&str) -> Option<&'a str> {
+				#respond
+			}
+		}
+	}
+
+	pub(crate) fn allows(&self, method: &str) -> bool {
+		self.methods.iter().any(|m| m == method)
+	}
+
+	// On the functions serving responses, which are where a deny-panic build lets clippy check for itself
+	pub(crate) fn panic_lints(&self) -> TokenStream {
+		if self.deny_panic {
+			quote! { #[deny(clippy::panic, clippy::unwrap_used, clippy::expect_used)] }
+		} else {
+			quote! { #[allow(clippy::panic, clippy::unwrap_used)] }
+		}
+	}
+}
+
+// Writes `body` into `dir`, named for its hash, so that identical bodies share a file
+fn write_body(dir: &Path, body: &[u8]) -> Result<PathBuf, String> {
+	let file = dir.join(format!("{:016x}", fnv1a(body)));
+
+	fs::create_dir_all(dir).map_err(|e| format!("failed to create {}: {e}", dir.display()))?;
+	fs::write(&file, body).map_err(|e| format!("failed to write {}: {e}", file.display()))?;
+
+	Ok(file)
+}
+
+// Headers `serve` sets for itself, so rules can't
+pub(crate) const SERVED_HEADERS: &[&str] = &["accept-ranges", "content-encoding", "content-length", "content-range", "content-type", "etag", "last-modified", "location", "vary"];
+
+// The filters a resource goes through as it's served, which only pages are
+fn filters<'a>(r: &'a Resource, page: &RenderedResource) -> &'a [String] {
+	if page.content_type.starts_with("text/html") { r.rule().filters() } else { &[] }
+}
+
+// The routes whose responses are only known once the application's filters have run, which the
+// generated HTTP tests, having none of them, leave out
+pub(crate) fn filtered_paths(rendered: &[(Resource, RenderedResource)]) -> Vec<&str> {
+	rendered.iter().filter(|(r, page)| !filters(r, page).is_empty()).flat_map(|(_, page)| &page.paths).map(String::as_str).collect()
+}
+
+// The `serve::Route` table for a rendered resource set, as a static named `name`, and every path in it
+pub(crate) fn route_table(name: &str, rendered: &[(Resource, RenderedResource)], options: &Options<'_>) -> Result<(TokenStream, Vec<String>), String> {
+	let mut routes = vec![];
+	// Generated resources were modified as they were built, unless reproducible builds say otherwise
+	let build_time = env::var("SOURCE_DATE_EPOCH").ok().and_then(|t| t.parse().ok())
+		.unwrap_or_else(|| SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default());
+
+	// Scheduled variants' bodies, by the source of the page each is served in place of
+	let mut windows: HashMap<&Path, Vec<(u64, u64, TokenStream)>> = HashMap::new();
+
+	for (r, rendered) in rendered {
+		let source = rendered.source.display().to_string();
+		let rule = serde_json::to_string(r.rule()).unwrap();
+		let media_type = &rendered.content_type;
+		let embedded = !options.dev || r.is_virtual() || r.renders_at_build_time();
+		let last_modified = embedded.then(|| r.freshness().and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok()).map_or(build_time, |d| d.as_secs()));
+		let last_modified = last_modified.map_or(quote! { None }, |secs| quote! { Some(#secs) });
+		let etag = embedded.then(|| format!("{:016x}", fnv1a(&rendered.body))).map_or(quote! { None }, |hash| quote! { Some(#hash) });
+		// Filtered only as they're served, so no precompressed copy would do
+		let filters = filters(r, rendered);
+		let variants = match embedded && filters.is_empty() && options.compresses(&rendered.paths[0], r, rendered) {
+			true => compress::variants(&rendered.body, options.encodings)?,
+			false => vec![],
+		};
+
+		let content = |path: &str| -> Result<TokenStream, String> {
+			let headers = options.headers(path, r, rendered);
+			let body = if embedded {
+				options.body(r.rule().store.as_ref(), &rendered.body)?
+			} else {
+				quote! { &::static_builder::serve::Rendered { source: #source, rule: #rule } }
+			};
+			let encodings = variants.iter().map(|(encoding, body)| {
+				let (name, body) = (encoding.name(), options.body(r.rule().store.as_ref(), body)?);
+				Ok(quote! { (#name, #body) })
+			}).collect::<Result<Vec<_>, String>>()?;
+			// Made at build time, so always embedded
+			let formats = rendered.formats.iter().map(|(format, body)| {
+				let (hash, body) = (format!("{:016x}", fnv1a(body)), options.body(r.rule().store.as_ref(), body)?);
+				Ok(quote! {
+					(#format, &::static_builder::serve::Serve::Content { media_type: #format, headers: #headers, body: #body, encodings: &[], etag: Some(#hash), last_modified: #last_modified, formats: &[], filters: &[] })
+				})
+			}).collect::<Result<Vec<_>, String>>()?;
+
+			Ok(quote! {
+				::static_builder::serve::Serve::Content { media_type: #media_type, headers: #headers, body: #body, encodings: &[#(#encodings),*], etag: #etag, last_modified: #last_modified, formats: &[#(#formats),*], filters: &[#(#filters),*] }
+			})
+		};
+
+		// A scheduled variant is only served in place of its pages, never at a path of its own
+		if !r.windows().is_empty() {
+			let serve = content(&rendered.paths[0])?;
+			for (page, from, until) in r.windows() {
+				windows.entry(page.as_path()).or_default().push((*from, *until, serve.clone()));
+			}
+			continue;
+		}
+
+		for path in &rendered.paths {
+			routes.push((path.clone(), r.source.as_path(), true, content(path)?));
+		}
+
+		let canonical = &rendered.paths[0];
+
+		for path in &rendered.redirects {
+			routes.push((path.clone(), r.source.as_path(), false, quote! { ::static_builder::serve::Serve::Redirect(#canonical) }));
+		}
+	}
+
+	// Lookups are binary searches, so no path can have two routes
+	routes.sort_by(|a, b| a.0.cmp(&b.0));
+	if let Some(pair) = routes.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+		let by = |(_, source, page, _): &(String, &Path, bool, TokenStream)| match page {
+			true => source.display().to_string(),
+			false => format!("a redirect to {}", source.display()),
+		};
+		return Err(format!("{} would be served by both {} and {}", pair[0].0, by(&pair[0]), by(&pair[1])));
+	}
+
+	let routes: Vec<(String, TokenStream)> = routes.into_iter().map(|(path, source, page, serve)| match windows.get(source).filter(|_| page) {
+		Some(windows) => {
+			let windows = windows.iter().map(|(from, until, serve)| quote! { ::static_builder::serve::Window { from: #from, until: #until, serve: #serve } });
+			(path, quote! { ::static_builder::serve::Serve::Scheduled { windows: &[#(#windows),*], otherwise: &#serve } })
+		},
+		None => (path, serve),
+	}).collect();
+
+	let paths: Vec<_> = routes.iter().map(|(path, _)| path.clone()).collect();
+	let entry = |path: &str, serve: &TokenStream| quote! { ::static_builder::serve::Route { path: #path, serve: #serve } };
+	let ident = format_ident!("{name}");
+
+	let Some(under) = &options.shard_under else {
+		let entries = routes.iter().map(|(path, serve)| entry(path, serve));
+		return Ok((quote! { static #ident: &[::static_builder::serve::Route] = &[#(#entries),*]; }, paths));
+	};
+
+	// Sorting keeps each directory's routes together
+	let mut top = vec![];
+	let mut shards: Vec<(String, Vec<TokenStream>)> = vec![];
+
+	for (path, serve) in &routes {
+		let dir = path.strip_prefix(under.as_str()).and_then(|p| p.strip_prefix('/')).and_then(|p| p.split_once('/')).map(|(dir, _)| format!("{under}/{dir}/"));
+
+		match (dir, shards.last_mut()) {
+			(Some(dir), Some((last, entries))) if *last == dir => entries.push(entry(path, serve)),
+			(Some(dir), _) => shards.push((dir, vec![entry(path, serve)])),
+			(None, _) => top.push((path.clone(), entry(path, serve))),
+		}
+	}
+
+	let modules: Vec<_> = (0..shards.len()).map(|i| format_ident!("{}_{i}", name.to_lowercase())).collect();
+	for ((dir, _), module) in shards.iter().zip(&modules) {
+		top.push((dir.clone(), entry(dir, &quote! { ::static_builder::serve::Serve::Shard(#module::ROUTES) })));
+	}
+	top.sort_by(|a, b| a.0.cmp(&b.0));
+
+	let entries = top.into_iter().map(|(_, entry)| entry);
+	let shard_entries = shards.into_iter().map(|(_, entries)| entries);
+
+	Ok((quote! {
+		static #ident: &[::static_builder::serve::Route] = &[#(#entries),*];
+
+		#(
+			mod #modules {
+				#[allow(unused_imports)]
+				use super::*;
+
+				pub(super) static ROUTES: &[::static_builder::serve::Route] = &[#(#shard_entries),*];
+			}
+		)*
+	}, paths))
+}
+
+// `select` binds whatever `respond` needs from `self` to produce the `HttpResponse` for `req`; in dev
+// builds, `prerender` is a closure rendering `path` on the blocking pool first, so slow renders
+// don't hold up the reactor
+fn service_impls(name: &str, respond: TokenStream, select: TokenStream, prerender: TokenStream, options: &Options<'_>) -> TokenStream {
+	let ty = format_ident!("{name}");
+
+	let (future, call) = if options.dev {
+		(
+			quote! { ::std::pin::Pin<Box<dyn ::std::future::Future<Output = Result<Self::Response, Self::Error>>>> },
+			quote! {
+				#select
+				let path = req.path().to_owned();
+				let prerender = #prerender;
+
+				Box::pin(async move {
+					let _ = ::actix_web::web::block(prerender).await;
+					let res = #respond;
+					Ok(req.into_response(res))
+				})
+			},
+		)
+	} else {
+		(
+			quote! { ::std::future::Ready<Result<Self::Response, Self::Error>> },
+			quote! {
+				#select
+				let res = #respond;
+				::std::future::ready(Ok(req.into_response(res)))
+			},
+		)
+	};
+
+	quote! {
+		impl ::actix_web::dev::HttpServiceFactory for #ty {
+			fn register(self, config: &mut ::actix_web::dev::AppService) {
+				let mut res_def = ::actix_web::dev::ResourceDef::new(Self::PATHS.to_vec());
+				res_def.set_name(#name);
+
+				// Not matching passed-through paths lets actix carry on to the application's other services
+				let guard = ::actix_web::guard::fn_guard(|ctx| !StaticContent::passes_through(ctx.head().uri.path()));
+				config.register_service(res_def, Some(vec![Box::new(guard) as Box<dyn ::actix_web::guard::Guard>]), self, None);
+			}
+		}
+
+		impl ::actix_web::dev::ServiceFactory<::actix_web::dev::ServiceRequest> for #ty {
+			type Response = ::actix_web::dev::ServiceResponse;
+			type Error = ::actix_web::Error;
+			type InitError = ();
+			type Config = ();
+			type Service = #ty;
+			type Future = ::std::future::Ready<Result<Self::Service, ()>>;
+
+			fn new_service(&self, _cfg: Self::Config) -> Self::Future {
+				::std::future::ready(Ok(self.clone()))
+			}
+		}
+
+		impl ::actix_web::dev::Service<::actix_web::dev::ServiceRequest> for #ty {
+			type Response = ::actix_web::dev::ServiceResponse;
+			type Error = ::actix_web::Error;
+			type Future = #future;
+
+			::actix_web::dev::always_ready!();
+
+			fn call(&self, req: ::actix_web::dev::ServiceRequest) -> Self::Future {
+				#call
+			}
+		}
+	}
+}
+
+// A fuzz entry point, `watch_for_rebuild`, and (with `tests`) a test looking up awkward paths, for
+// every backend's `StaticContent`
+pub(crate) fn path_checks(options: &Options<'_>) -> TokenStream {
+	let watch = match options.watch {
+		Some((dirs, rebuild_on)) => quote! {
+			let stamp = stamp.as_ref().to_path_buf();
+			::std::thread::spawn(move || {
+				if let Err(e) = ::static_builder::watch_for_rebuild(&[#(#dirs),*], &[#(#rebuild_on),*], &stamp, ::std::time::Duration::from_millis(250)) {
+					eprintln!("static-builder: {e}");
+				}
+			});
+		},
+		None => quote! { let _ = stamp; },
+	};
+	let tests = match options.tests {
+		true => quote! {
+			#[cfg(test)]
+			#[allow(clippy::unwrap_used, clippy::panic)]
+			mod static_content_path_tests {
+				use super::{StaticContent, STATIC_CONTENT_ROUTES};
+
+				#[test]
+				fn only_routed_paths_are_found() {
+					let paths = ::static_builder::serve::awkward_paths(StaticContent::PATHS);
+
+					for path in paths.iter().map(String::as_str).chain(StaticContent::PATHS.iter().copied()) {
+						::static_builder::serve::check_path(STATIC_CONTENT_ROUTES, StaticContent::PATHS, path);
+					}
+				}
+			}
+		},
+		false => quote! {},
+	};
+
+	quote! {
+		#[allow(unexpected_cfgs)]
+		impl StaticContent {
+			/// For a cargo-fuzz target (which builds with `--cfg fuzzing`) to call with its input:
+			/// panics if looking up the path it spells finds a route it shouldn't, or none it should
+			#[cfg(fuzzing)]
+			#[allow(dead_code)]
+			pub(crate) fn fuzz(data: &[u8]) {
+				::static_builder::serve::check_path(STATIC_CONTENT_ROUTES, Self::PATHS, &String::from_utf8_lossy(data));
+			}
+
+			/// In dev builds, watches the content in the background, writing to `stamp` whenever
+			/// something changes that needs this module regenerated (a file added or removed, or one
+			/// rendered at build time edited), and not for edits to what's served from disk; does
+			/// nothing in builds that embed content.  Call it at startup, and have cargo-watch rebuild
+			/// for `stamp` and the code alone, e.g. `cargo watch -i 'content/**' -i 'layouts/**' -i
+			/// 'partials/**' -x run` with `stamp` outside them (or bacon, with `stamp` in its `watch`).
+			#[allow(dead_code)]
+			pub(crate) fn watch_for_rebuild<P>(stamp: P) where P: AsRef<::std::path::Path> {
+				#watch
+			}
+		}
+
+		#tests
+	}
+}
+
+// What every backend's `StaticContentSets` is made of: a route table for each set, a `routes`
+// function (for its impl) picking one by name, the names, and every path in any set
+pub(crate) struct SetTables<'a> {
+	pub(crate) tables: TokenStream,
+	pub(crate) routes_fn: TokenStream,
+	pub(crate) names: Vec<&'a str>,
+	pub(crate) paths: BTreeSet<String>,
+}
+
+pub(crate) fn set_tables<'a>(default_paths: &[String], sets: &[(&'a str, Vec<(Resource, RenderedResource)>)], options: &Options<'_>) -> Result<SetTables<'a>, String> {
+	let mut names = vec![];
+	let mut tables = vec![];
+	let mut table_names = vec![];
+	let mut paths: BTreeSet<String> = default_paths.iter().cloned().collect();
+
+	for (i, (name, rendered)) in sets.iter().enumerate() {
+		let table_name = format!("STATIC_CONTENT_SET_ROUTES_{i}");
+		let (table, set_paths) = route_table(&table_name, rendered, options)?;
+
+		names.push(*name);
+		tables.push(table);
+		table_names.push(format_ident!("{table_name}"));
+		paths.extend(set_paths);
+	}
+
+	let routes_fn = quote! {
+		/// The route table for the named set
+		pub(crate) fn routes(set: Option<&str>) -> &'static [::static_builder::serve::Route] {
+			match set {
+				#(Some(#names) => #table_names,)*
+				_ => STATIC_CONTENT_ROUTES,
+			}
+		}
+	};
+
+	Ok(SetTables { tables: quote! { #(#tables)* }, routes_fn, names, paths })
+}
+
+fn content_sets(default_paths: &[String], sets: &[(&str, Vec<(Resource, RenderedResource)>)], options: &Options<'_>) -> Result<TokenStream, String> {
+	let SetTables { tables, routes_fn, names, paths } = set_tables(default_paths, sets, options)?;
+	let all_paths = paths.into_iter();
+	let impls = service_impls(
+		"StaticContentSets",
+		quote! { static_content_http_response(static_content_respond(StaticContentSets::routes(set.as_deref()), req.method().as_str(), req.path(), |name| req.headers().get(name).and_then(|v| v.to_str().ok()))) },
+		quote! { let set = (self.select)(&req); },
+		quote! {{
+			let set = set.clone();
+			move || ::static_builder::serve::prerender(StaticContentSets::routes(set.as_deref()), &path)
+		}},
+		options,
+	);
+
+	Ok(quote! {
+		#[derive(Clone)]
+		pub(crate) struct StaticContentSets {
+			select: ::std::sync::Arc<dyn Fn(&::actix_web::dev::ServiceRequest) -> Option<String> + Send + Sync>,
+		}
+
+		#[allow(dead_code)]
+		impl StaticContentSets {
+			pub(crate) const NAMES: &'static [&'static str] = &[#(#names),*];
+			pub(crate) const PATHS: &'static [&'static str] = &[#(#all_paths),*];
+
+			/// `select` names the content set to serve each request from; `None`, or a name that
+			/// isn't in `NAMES`, gets the default set (the one `StaticContent` serves)
+			pub(crate) fn new<F>(select: F) -> Self where F: Fn(&::actix_web::dev::ServiceRequest) -> Option<String> + Send + Sync + 'static {
+				StaticContentSets { select: ::std::sync::Arc::new(select) }
+			}
+
+			/// Always serves the named set, e.g. one chosen from configuration at startup
+			pub(crate) fn fixed<S>(name: S) -> Self where S: Into<String> {
+				let name = name.into();
+				Self::new(move |_| Some(name.clone()))
+			}
+
+			/// Serves the set named by the request's `Host`, less any port
+			pub(crate) fn by_host() -> Self {
+				Self::new(|req| {
+					let info = req.connection_info();
+					let host = info.host().to_ascii_lowercase();
+
+					Some(match host.rfind(':') {
+						Some(i) if !host[i..].contains(']') => host[..i].to_string(),
+						_ => host,
+					})
+				})
+			}
+
+			#routes_fn
+		}
+
+		#tables
+
+		#impls
+	})
+}
+
+// Checks of the HTTP semantics every route should have, run with the application's own tests
+fn http_tests(rendered: &[(Resource, RenderedResource)], options: &Options<'_>) -> TokenStream {
+	let filtered = filtered_paths(rendered);
+	let not_allowed = match options.allows("POST") {
+		true => quote! {},
+		false => quote! {
+			#[::actix_web::test]
+			async fn other_methods_are_not_allowed() {
+				let app = test::init_service(App::new().service(StaticContent)).await;
+
+				for path in paths() {
+					let res = test::call_service(&app, test::TestRequest::post().uri(path).to_request()).await;
+					assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED, "POST {path}");
+					assert!(res.headers().contains_key(header::ALLOW), "POST {path}");
+				}
+			}
+		},
+	};
+	let options_test = match options.allows("OPTIONS") {
+		true => quote! {
+			#[::actix_web::test]
+			async fn options_lists_the_allowed_methods() {
+				let app = test::init_service(App::new().service(StaticContent)).await;
+
+				for path in paths() {
+					let res = test::call_service(&app, test::TestRequest::default().method(Method::OPTIONS).uri(path).to_request()).await;
+					assert_eq!(res.status(), StatusCode::NO_CONTENT, "OPTIONS {path}");
+					assert!(res.headers().contains_key(header::ALLOW), "OPTIONS {path}");
+				}
+			}
+		},
+		false => quote! {},
+	};
+	let redirects: Vec<_> = rendered.iter().flat_map(|(_, page)| page.redirects.iter().map(|r| (r, &page.paths[0]))).collect();
+	let (redirect_from, redirect_to): (Vec<_>, Vec<_>) = redirects.into_iter().unzip();
+
+	quote! {
+		#[cfg(test)]
+		#[allow(clippy::unwrap_used, clippy::panic)]
+		mod static_content_http_tests {
+			use super::StaticContent;
+			use ::actix_web::{http::{header, Method, StatusCode}, test, web, App, HttpResponse};
+
+			const FILTERED: &[&str] = &[#(#filtered),*];
+
+			fn paths() -> impl Iterator<Item = &'static &'static str> {
+				StaticContent::PATHS.iter().filter(|path| !FILTERED.contains(path))
+			}
+
+			const REDIRECTS: &[(&str, &str)] = &[#((#redirect_from, #redirect_to)),*];
+
+			#[::actix_web::test]
+			async fn every_route_answers_get_and_head() {
+				let app = test::init_service(App::new().service(StaticContent)).await;
+
+				for path in paths() {
+					for method in [Method::GET, Method::HEAD] {
+						let res = test::call_service(&app, test::TestRequest::default().method(method.clone()).uri(path).to_request()).await;
+						assert!(res.status() == StatusCode::OK || res.status() == StatusCode::PERMANENT_REDIRECT, "{method} {path}: {}", res.status());
+					}
+				}
+			}
+
+			#not_allowed
+
+			#options_test
+
+			#[::actix_web::test]
+			async fn redirects_point_at_their_canonical_route() {
+				let app = test::init_service(App::new().service(StaticContent)).await;
+
+				for (from, to) in REDIRECTS {
+					let res = test::call_service(&app, test::TestRequest::get().uri(from).to_request()).await;
+					assert_eq!(res.status(), StatusCode::PERMANENT_REDIRECT, "GET {from}");
+					assert_eq!(res.headers().get(header::LOCATION).unwrap(), to, "GET {from}");
+				}
+			}
+
+			#[::actix_web::test]
+			async fn matching_etags_are_not_modified() {
+				let app = test::init_service(App::new().service(StaticContent)).await;
+
+				for path in paths() {
+					let res = test::call_service(&app, test::TestRequest::get().uri(path).to_request()).await;
+					let Some(etag) = res.headers().get(header::ETAG).cloned() else { continue };
+					let res = test::call_service(&app, test::TestRequest::get().uri(path).insert_header((header::IF_NONE_MATCH, etag.clone())).to_request()).await;
+					assert_eq!(res.status(), StatusCode::NOT_MODIFIED, "GET {path}");
+					assert_eq!(res.headers().get(header::ETAG), Some(&etag), "GET {path}");
+				}
+			}
+
+			#[::actix_web::test]
+			async fn unchanged_routes_are_not_modified_since() {
+				let app = test::init_service(App::new().service(StaticContent)).await;
+
+				for path in paths() {
+					let res = test::call_service(&app, test::TestRequest::get().uri(path).to_request()).await;
+					let Some(modified) = res.headers().get(header::LAST_MODIFIED).cloned() else { continue };
+					let res = test::call_service(&app, test::TestRequest::get().uri(path).insert_header((header::IF_MODIFIED_SINCE, modified)).to_request()).await;
+					assert_eq!(res.status(), StatusCode::NOT_MODIFIED, "GET {path}");
+				}
+			}
+
+			#[::actix_web::test]
+			async fn ranges_are_partial_content() {
+				let app = test::init_service(App::new().service(StaticContent)).await;
+
+				for path in paths() {
+					let res = test::call_service(&app, test::TestRequest::get().uri(path).insert_header((header::RANGE, "bytes=0-0")).to_request()).await;
+					if res.status() == StatusCode::PERMANENT_REDIRECT { continue }
+					assert!(res.status() == StatusCode::PARTIAL_CONTENT || res.status() == StatusCode::RANGE_NOT_SATISFIABLE, "GET {path}: {}", res.status());
+					assert!(res.headers().contains_key(header::CONTENT_RANGE), "GET {path}");
+				}
+			}
+
+			#[::actix_web::test]
+			async fn unknown_paths_are_not_found() {
+				let app = test::init_service(App::new().default_service(StaticContent::default_service())).await;
+				let res = test::call_service(&app, test::TestRequest::get().uri("/static-builder-no-such-route").to_request()).await;
+
+				assert_eq!(res.status(), StatusCode::NOT_FOUND);
+			}
+
+			#[::actix_web::test]
+			async fn fallthrough_hands_on_what_isnt_served_here() {
+				let app = test::init_service(App::new().wrap(StaticContent::fallthrough()).default_service(web::to(|| async { HttpResponse::ImATeapot().finish() }))).await;
+				let res = test::call_service(&app, test::TestRequest::get().uri("/static-builder-no-such-route").to_request()).await;
+				assert_eq!(res.status(), StatusCode::IM_A_TEAPOT);
+
+				for path in paths() {
+					let res = test::call_service(&app, test::TestRequest::get().uri(path).to_request()).await;
+					assert_ne!(res.status(), StatusCode::IM_A_TEAPOT, "GET {path}");
+				}
+			}
+		}
+	}
+}
+
+pub(crate) fn actix_module(rendered: &[(Resource, RenderedResource)], sets: &[(&str, Vec<(Resource, RenderedResource)>)], options: &Options<'_>) -> Result<TokenStream, String> {
+	let (routes, resource_paths) = route_table("STATIC_CONTENT_ROUTES", rendered, options)?;
+	let passthrough = options.passthrough;
+	let methods = options.methods;
+	let panic_lints = options.panic_lints();
+	let respond = options.respond_fn();
+	let impls = service_impls(
+		"StaticContent",
+		quote! { StaticContent::response(req.request()) },
+		quote! {},
+		quote! { move || StaticContent::prerender(&path) },
+		options,
+	);
+	let sets = if sets.is_empty() { quote! {} } else { content_sets(&resource_paths, sets, options)? };
+	let bodies = options.body_statics()?;
+	let tests = if options.tests { http_tests(rendered, options) } else { quote! {} };
+	let path_checks = path_checks(options);
+
+	Ok(quote! {
+		#[derive(Clone)]
+		pub(crate) struct StaticContent;
+
+		impl StaticContent {
+			pub(crate) const PATHS: &'static [&'static str] = &[#(#resource_paths),*];
+			/// Path prefixes never handled here, for another service to respond to
+			pub(crate) const PASSTHROUGH: &'static [&'static str] = &[#(#passthrough),*];
+
+			pub(crate) fn passes_through(path: &str) -> bool {
+				Self::PASSTHROUGH.iter().any(|prefix| path.starts_with(prefix))
+			}
+
+			/// Every `(embedded path, application route)` pair where one of the application's own
+			/// route patterns would also match a path served from here
+			#[allow(dead_code)]
+			pub(crate) fn route_collisions<'a>(app_routes: &[&'a str]) -> Vec<(&'static str, &'a str)> {
+				let mut collisions = vec![];
+
+				for route in app_routes {
+					let def = ::actix_web::dev::ResourceDef::new(*route);
+
+					for path in Self::PATHS {
+						if def.is_match(path) {
+							collisions.push((*path, *route));
+						}
+					}
+				}
+
+				collisions
+			}
+
+			/// In debug builds, complains on stderr about any route collisions; call it with the
+			/// application's route patterns at startup
+			#[allow(dead_code)]
+			pub(crate) fn warn_route_collisions(app_routes: &[&str]) {
+				if cfg!(debug_assertions) {
+					for (path, route) in Self::route_collisions(app_routes) {
+						eprintln!("warning: embedded static path {path} collides with application route {route}");
+					}
+				}
+			}
+
+			/// For `App::default_service`, so that the application's own routes take precedence and
+			/// everything they don't handle is served (or 404ed) from here
+			#[allow(dead_code)]
+			pub(crate) fn default_service() -> Self {
+				StaticContent
+			}
+
+			/// Middleware, for `App::wrap`, serving whatever is served from here ahead of the rest of
+			/// the application and handing everything else on to it: its routes, and then its own
+			/// default service, which is where to say what unmatched paths get
+			#[allow(dead_code)]
+			pub(crate) fn fallthrough() -> StaticContentFallthrough {
+				StaticContentFallthrough
+			}
+
+			/// Whether a `method` request for `path` is answered from here
+			pub(crate) fn serves(method: &str, path: &str) -> bool {
+				!Self::passes_through(path) && [#(#methods),*].contains(&method) && ::static_builder::serve::route(STATIC_CONTENT_ROUTES, path).is_some()
+			}
+
+			fn response(req: &::actix_web::HttpRequest) -> ::actix_web::HttpResponse {
+				static_content_http_response(static_content_respond(STATIC_CONTENT_ROUTES, req.method().as_str(), req.path(), |name| req.headers().get(name).and_then(|v| v.to_str().ok())))
+			}
+
+			// Renders `path` into the render cache, so `response` finds it there
+			#[allow(dead_code)]
+			fn prerender(path: &str) {
+				::static_builder::serve::prerender(STATIC_CONTENT_ROUTES, path);
+			}
+		}
+
+		// What's served is all decided by `static_builder::serve`; this only hands it to actix
+		#panic_lints
+		fn static_content_http_response(res: ::static_builder::serve::Response) -> ::actix_web::HttpResponse {
+			let status = ::actix_web::http::StatusCode::from_u16(res.status).unwrap_or(::actix_web::http::StatusCode::INTERNAL_SERVER_ERROR);
+			let mut builder = ::actix_web::HttpResponse::build(status);
+			let head_length = res.headers.iter().find(|(name, _)| *name == "content-length").and_then(|(_, length)| length.parse().ok());
+
+			// actix-web sends the length of the body it's given, so a HEAD response gets one that says
+			// it's that long, which actix-web never sends
+			for (name, value) in res.headers.into_iter().filter(|(name, _)| *name != "content-length") {
+				builder.append_header((name, value.into_owned()));
+			}
+			if let Some(length) = head_length {
+				return builder.body(StaticContentHeadBody(length));
+			}
+
+			match res.body {
+				::std::borrow::Cow::Borrowed(body) => builder.body(body),
+				::std::borrow::Cow::Owned(body) => builder.body(body),
+			}
+		}
+
+		struct StaticContentHeadBody(u64);
+
+		impl ::actix_web::body::MessageBody for StaticContentHeadBody {
+			type Error = ::std::convert::Infallible;
+
+			fn size(&self) -> ::actix_web::body::BodySize {
+				::actix_web::body::BodySize::Sized(self.0)
+			}
+
+			fn poll_next(self: ::std::pin::Pin<&mut Self>, _: &mut ::std::task::Context<'_>) -> ::std::task::Poll<Option<Result<::actix_web::web::Bytes, Self::Error>>> {
+				::std::task::Poll::Ready(None)
+			}
+		}
+
+		pub(crate) struct StaticContentFallthrough;
+
+		impl<S, B> ::actix_web::dev::Transform<S, ::actix_web::dev::ServiceRequest> for StaticContentFallthrough where S: ::actix_web::dev::Service<::actix_web::dev::ServiceRequest, Response = ::actix_web::dev::ServiceResponse<B>, Error = ::actix_web::Error> + 'static, B: ::actix_web::body::MessageBody + 'static {
+			type Response = ::actix_web::dev::ServiceResponse<::actix_web::body::EitherBody<B>>;
+			type Error = ::actix_web::Error;
+			type Transform = StaticContentFallthroughService<S>;
+			type InitError = ();
+			type Future = ::std::future::Ready<Result<Self::Transform, Self::InitError>>;
+
+			fn new_transform(&self, service: S) -> Self::Future {
+				::std::future::ready(Ok(StaticContentFallthroughService(service)))
+			}
+		}
+
+		pub(crate) struct StaticContentFallthroughService<S>(S);
+
+		impl<S, B> ::actix_web::dev::Service<::actix_web::dev::ServiceRequest> for StaticContentFallthroughService<S> where S: ::actix_web::dev::Service<::actix_web::dev::ServiceRequest, Response = ::actix_web::dev::ServiceResponse<B>, Error = ::actix_web::Error> + 'static, B: ::actix_web::body::MessageBody + 'static {
+			type Response = ::actix_web::dev::ServiceResponse<::actix_web::body::EitherBody<B>>;
+			type Error = ::actix_web::Error;
+			type Future = ::std::pin::Pin<Box<dyn ::std::future::Future<Output = Result<Self::Response, Self::Error>>>>;
+
+			fn poll_ready(&self, cx: &mut ::std::task::Context<'_>) -> ::std::task::Poll<Result<(), Self::Error>> {
+				::actix_web::dev::Service::poll_ready(&self.0, cx)
+			}
+
+			fn call(&self, req: ::actix_web::dev::ServiceRequest) -> Self::Future {
+				if StaticContent::serves(req.method().as_str(), req.path()) {
+					let res = ::actix_web::dev::Service::call(&StaticContent, req);
+					Box::pin(async move { res.await.map(|res| res.map_into_right_body()) })
+				} else {
+					let res = ::actix_web::dev::Service::call(&self.0, req);
+					Box::pin(async move { res.await.map(|res| res.map_into_left_body()) })
+				}
+			}
+		}
+
+		#respond
+
+		#routes
+
+		#impls
+
+		#sets
+
+		#bodies
+
+		#path_checks
+
+		#tests
+	})
+}
@@ -0,0 +1,58 @@
+use globset::GlobBuilder;
+use std::{io::Write as _, path::Path, process::{Command, Stdio}, thread};
+
+use crate::Resource;
+
+// A program to run for `Builder::command`, and its arguments
+pub(crate) type ExternalCommand = (String, Vec<String>);
+
+// Gives every resource whose source (relative to `base_path`) matches one of `commands`' globs the
+// last such command, warning of any glob that matches nothing
+pub(crate) fn assign(resources: Vec<Resource>, base_path: &Path, commands: &[(String, ExternalCommand)]) -> Result<Vec<Resource>, String> {
+	let mut matchers = vec![];
+	for (glob, command) in commands {
+		let matcher = GlobBuilder::new(glob).literal_separator(true).build().map_err(|e| format!("invalid glob {glob:?}: {e}"))?.compile_matcher();
+		matchers.push((glob, matcher, command));
+	}
+	let mut matched = vec![false; matchers.len()];
+
+	let resources = resources.into_iter().map(|r| {
+		let source = r.source.strip_prefix(base_path).unwrap_or(&r.source).to_path_buf();
+		match matchers.iter().enumerate().rev().find(|(_, (_, matcher, _))| !r.is_virtual() && matcher.is_match(&source)) {
+			Some((i, (_, _, command))) => {
+				matched[i] = true;
+				r.with_command((*command).clone())
+			},
+			None => r,
+		}
+	}).collect();
+
+	for ((glob, _, _), matched) in matchers.iter().zip(matched) {
+		if !matched {
+			println!("cargo::warning=static-builder: no file matches command glob {glob:?}");
+		}
+	}
+
+	Ok(resources)
+}
+
+// What `command` writes to its standard output, with `{source}` in its arguments standing for the
+// source's path and the source itself on its standard input
+pub(crate) fn run((program, args): &ExternalCommand, source: &Path, input: Vec<u8>) -> Result<Vec<u8>, String> {
+	let args = args.iter().map(|arg| arg.replace("{source}", &source.display().to_string()));
+	let mut child = Command::new(program).args(args).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().map_err(|e| format!("failed to run {program}: {e}"))?;
+	let mut stdin = child.stdin.take().ok_or_else(|| format!("no stdin for {program}"))?;
+	// Written from a thread of its own, so a command writing output as it reads can't deadlock;
+	// one that reads its input from a file instead may well close its standard input unread
+	let writer = thread::spawn(move || {
+		let _ = stdin.write_all(&input);
+	});
+	let output = child.wait_with_output().map_err(|e| format!("failed to run {program}: {e}"))?;
+	writer.join().map_err(|_| format!("failed to write to {program}"))?;
+
+	if !output.status.success() {
+		return Err(format!("{program} exited with {}", output.status));
+	}
+
+	Ok(output.stdout)
+}
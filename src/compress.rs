@@ -0,0 +1,114 @@
+//! Precompressing bodies at build time, with flate2, brotli and zstd at their best ratios
+
+use std::io::Write;
+
+/// Bodies smaller than this gain too little from compression to be worth more copies
+pub(crate) const DEFAULT_MIN_SIZE: usize = 256;
+
+/// A content coding bodies can be precompressed with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+	Gzip,
+	Brotli,
+	Zstd,
+}
+
+impl Encoding {
+	/// Its name in `Accept-Encoding` and `Content-Encoding`
+	pub fn name(&self) -> &'static str {
+		match self {
+			Encoding::Gzip => "gzip",
+			Encoding::Brotli => "br",
+			Encoding::Zstd => "zstd",
+		}
+	}
+
+	fn compress(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+		match self {
+			Encoding::Gzip => {
+				let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::best());
+				encoder.write_all(data).and_then(|_| encoder.finish())
+			},
+			Encoding::Brotli => {
+				let params = brotli::enc::BrotliEncoderParams { quality: 11, lgwin: 22, ..Default::default() };
+				let mut out = vec![];
+				brotli::BrotliCompress(&mut &data[..], &mut out, &params).map(|_| out)
+			},
+			Encoding::Zstd => zstd::bulk::compress(data, 19),
+		}.map_err(|e| format!("failed to compress with {}: {e}", self.name()))
+	}
+}
+
+// Whether bodies of `media_type` are text (or near enough) that compresses
+pub(crate) fn compressible(media_type: &str) -> bool {
+	let essence = media_type.split(';').next().unwrap_or_default().trim();
+
+	essence.starts_with("text/") || essence.ends_with("+xml") || essence.ends_with("+json")
+		|| ["application/json", "application/xml", "image/vnd.microsoft.icon"].contains(&essence)
+}
+
+// `body` in each of `encodings` that saves at least a tenth of it, smallest first
+pub(crate) fn variants(body: &[u8], encodings: &[Encoding]) -> Result<Vec<(Encoding, Vec<u8>)>, String> {
+	let mut variants = vec![];
+	for encoding in encodings {
+		let compressed = encoding.compress(body)?;
+		if compressed.len() < body.len() - body.len() / 10 {
+			variants.push((*encoding, compressed));
+		}
+	}
+	variants.sort_by_key(|(_, compressed)| compressed.len());
+
+	Ok(variants)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{compressible, variants, Encoding};
+	use crate::Resource;
+	use std::io::Read;
+
+	fn decompress(encoding: Encoding, body: &[u8]) -> Vec<u8> {
+		let mut out = vec![];
+		match encoding {
+			Encoding::Gzip => flate2::read::GzDecoder::new(body).read_to_end(&mut out).map(|_| ()),
+			Encoding::Brotli => brotli::BrotliDecompress(&mut &body[..], &mut out),
+			Encoding::Zstd => zstd::stream::copy_decode(body, &mut out),
+		}.unwrap();
+
+		out
+	}
+
+	#[test]
+	fn variants_round_trip() {
+		let body = "<p>static content, served again and again</p>\n".repeat(64);
+		let variants = variants(body.as_bytes(), &[Encoding::Gzip, Encoding::Brotli, Encoding::Zstd]).unwrap();
+
+		assert_eq!(variants.len(), 3);
+		assert!(variants.windows(2).all(|pair| pair[0].1.len() <= pair[1].1.len()));
+		for (encoding, compressed) in variants {
+			assert_eq!(decompress(encoding, &compressed), body.as_bytes(), "for {}", encoding.name());
+		}
+	}
+
+	#[test]
+	fn incompressible_bodies_have_no_variants() {
+		// xorshift noise
+		let mut state = 0x2545_f491_u32;
+		let body: Vec<u8> = (0..4096).map(|_| {
+			state ^= state << 13;
+			state ^= state >> 17;
+			state ^= state << 5;
+			state as u8
+		}).collect();
+
+		assert!(variants(&body, &[Encoding::Gzip, Encoding::Brotli, Encoding::Zstd]).unwrap().is_empty());
+	}
+
+	#[test]
+	fn scripts_and_icons_are_compressible() {
+		for (source, compresses) in [("app.js", true), ("favicon.ico", true), ("data.json", true), ("logo.svg", true), ("photo.jpg", false)] {
+			let r = Resource::new(source.into(), source.into());
+			assert_eq!(compressible(r.content_type()), compresses, "for {source} ({})", r.content_type());
+		}
+	}
+}
@@ -0,0 +1,341 @@
+use globset::{GlobBuilder, GlobMatcher};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{collections::BTreeMap, env, fs, path::{Path, PathBuf}, str::FromStr};
+
+use crate::djot::DjotOptions;
+
+pub(crate) const DIRECTORY_CONFIG: &str = ".static-builder.yaml";
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+	Djot,
+	/// CommonMark, with GitHub's tables, task lists and strikethrough, rendered with the same options
+	/// and sections as djot (see `Rule::markdown_extensions`)
+	Markdown,
+	/// AsciiDoc, rendered as djot is (with the `asciidoc` feature; see `Rule::asciidoc_extensions`),
+	/// so far as its common subset goes: there are no `include::` or `ifdef::` directives, callout
+	/// lists, quote attributions, verse line breaks, or table cell spans and styles, and sections
+	/// get djot's ids rather than AsciiDoc's `_section` ones
+	#[cfg(feature = "asciidoc")]
+	AsciiDoc,
+	/// Sass, compiled to CSS (with the `sass` feature), for `.scss` and `.sass` sources
+	#[cfg(feature = "sass")]
+	Sass,
+	/// The `ContentRenderer` registered (with `Builder::renderer`) for the source's extension
+	Custom,
+	Tera,
+}
+
+/// How embedded bodies are stored in the binary (see `serve::BodyStore`)
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Store {
+	/// A static of its own, or inline in the route table if it's small
+	Static,
+	/// A range of one static blob shared by every body packed into it
+	Packed,
+	/// A file beside the build (in the `external_bodies` directory, or else `OUT_DIR`), read on
+	/// each request
+	Disk,
+	/// A type of the application's implementing `serve::BodyStore`, by its path (e.g.
+	/// `crate::vault::Sealed`), which has a `const fn new(&'static [u8]) -> Self`
+	Custom(String),
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Rule {
+	#[serde(deserialize_with = "deserialize_render", skip_serializing_if = "Option::is_none")]
+	pub(crate) render: Option<Vec<Stage>>,
+	pub(crate) djot: DjotOptions,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) feature: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) djot_extensions: Option<Vec<String>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) markdown_extensions: Option<Vec<String>>,
+	#[cfg(feature = "asciidoc")]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) asciidoc_extensions: Option<Vec<String>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) layouts: Option<PathBuf>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) partials: Option<PathBuf>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) store: Option<Store>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) lang: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) compress: Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) content_encoding: Option<String>,
+	#[serde(skip_serializing_if = "BTreeMap::is_empty")]
+	pub(crate) headers: BTreeMap<String, String>,
+	#[cfg(feature = "filters")]
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub(crate) filters: Vec<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) site: Option<serde_json::Value>,
+}
+
+impl Rule {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// An empty pipeline embeds matching files verbatim
+	pub fn render<I>(mut self, stages: I) -> Self where I: IntoIterator<Item = Stage> {
+		self.render = Some(stages.into_iter().collect());
+		self
+	}
+
+	pub fn djot(mut self, djot: DjotOptions) -> Self {
+		self.djot = djot;
+		self
+	}
+
+	/// Only include matching files when the crate being built has the named cargo feature enabled
+	pub fn feature<S>(mut self, feature: S) -> Self where S: Into<String> {
+		self.feature = Some(feature.into());
+		self
+	}
+
+	/// The source extensions rendered as djot, instead of `dj` and `djot`; a file with one of them
+	/// as its second-to-last extension (`page.dj.txt`) is djot too, and served as `page.html`
+	pub fn djot_extensions<I, S>(mut self, extensions: I) -> Self where I: IntoIterator<Item = S>, S: Into<String> {
+		self.djot_extensions = Some(extensions.into_iter().map(Into::into).collect());
+		self
+	}
+
+	pub(crate) fn is_djot_extension(&self, ext: &str) -> bool {
+		self.djot_extensions.as_ref().map(|exts| exts.iter().any(|e| e == ext)).unwrap_or(matches!(ext, "dj" | "djot"))
+	}
+
+	/// The source extensions rendered as Markdown, instead of `md` and `markdown`; as for djot, a
+	/// second-to-last extension counts too
+	pub fn markdown_extensions<I, S>(mut self, extensions: I) -> Self where I: IntoIterator<Item = S>, S: Into<String> {
+		self.markdown_extensions = Some(extensions.into_iter().map(Into::into).collect());
+		self
+	}
+
+	pub(crate) fn is_markdown_extension(&self, ext: &str) -> bool {
+		self.markdown_extensions.as_ref().map(|exts| exts.iter().any(|e| e == ext)).unwrap_or(matches!(ext, "md" | "markdown"))
+	}
+
+	/// The source extensions rendered as AsciiDoc, instead of `adoc` and `asciidoc`
+	#[cfg(feature = "asciidoc")]
+	pub fn asciidoc_extensions<I, S>(mut self, extensions: I) -> Self where I: IntoIterator<Item = S>, S: Into<String> {
+		self.asciidoc_extensions = Some(extensions.into_iter().map(Into::into).collect());
+		self
+	}
+
+	#[cfg(feature = "asciidoc")]
+	pub(crate) fn is_asciidoc_extension(&self, ext: &str) -> bool {
+		self.asciidoc_extensions.as_ref().map(|exts| exts.iter().any(|e| e == ext)).unwrap_or(matches!(ext, "adoc" | "asciidoc"))
+	}
+
+	/// Where layouts are loaded from, instead of `layouts/`
+	pub fn layouts<P>(mut self, dir: P) -> Self where P: Into<PathBuf> {
+		self.layouts = Some(dir.into());
+		self
+	}
+
+	/// Where partials are loaded from, instead of `partials/`
+	pub fn partials<P>(mut self, dir: P) -> Self where P: Into<PathBuf> {
+		self.partials = Some(dir.into());
+		self
+	}
+
+	/// How matching files' bodies are embedded, instead of by their size (see `Builder::inline_limit`
+	/// and `Builder::external_bodies`)
+	pub fn store(mut self, store: Store) -> Self {
+		self.store = Some(store);
+		self
+	}
+
+	/// The language of matching pages, for their `<html lang>` and `Content-Language`, and as `lang`
+	/// in their template context (where `{{ page.date | date_format(locale=lang) }}` formats dates for
+	/// it)
+	pub fn lang<S>(mut self, lang: S) -> Self where S: Into<String> {
+		self.lang = Some(lang.into());
+		self
+	}
+
+	/// Whether matching bodies are precompressed (see `Builder::precompress`) whatever their type,
+	/// extension or size, or never are
+	pub fn compress(mut self, compress: bool) -> Self {
+		self.compress = Some(compress);
+		self
+	}
+
+	/// Serves matching files as they are with this `Content-Encoding` (e.g. `gzip` for `.tar.gz`
+	/// files, which are then served as tarballs) to every request, with no precompression or
+	/// negotiation
+	pub fn content_encoding<S>(mut self, coding: S) -> Self where S: Into<String> {
+		self.content_encoding = Some(coding.into());
+		self
+	}
+
+	/// Adds a header to matching files' responses, replacing any of the same name an earlier rule
+	/// added
+	pub fn header<N, V>(mut self, name: N, value: V) -> Self where N: AsRef<str>, V: Into<String> {
+		self.headers.insert(name.as_ref().to_ascii_lowercase(), value.into());
+		self
+	}
+
+	/// Runs the filter the application registers as `name` (with `serve::add_filter`) on matching
+	/// HTML pages as each is served, after any filters earlier rules name.  Filtered pages are
+	/// served whole, without precompression or validators, as their bodies are only known then;
+	/// with the `filters` feature on the dependency as well as the build dependency.
+	#[cfg(feature = "filters")]
+	pub fn filter<S>(mut self, name: S) -> Self where S: Into<String> {
+		self.filters.push(name.into());
+		self
+	}
+
+	// The site config's settings, as `site` in templates
+	pub(crate) fn site(mut self, site: serde_json::Value) -> Self {
+		self.site = Some(site);
+		self
+	}
+
+	pub(crate) fn merge(&mut self, other: &Rule) {
+		if other.render.is_some() {
+			self.render.clone_from(&other.render);
+		}
+		self.djot.merge(&other.djot);
+		if other.feature.is_some() {
+			self.feature.clone_from(&other.feature);
+		}
+		if other.djot_extensions.is_some() {
+			self.djot_extensions.clone_from(&other.djot_extensions);
+		}
+		if other.markdown_extensions.is_some() {
+			self.markdown_extensions.clone_from(&other.markdown_extensions);
+		}
+		#[cfg(feature = "asciidoc")]
+		if other.asciidoc_extensions.is_some() {
+			self.asciidoc_extensions.clone_from(&other.asciidoc_extensions);
+		}
+		if other.layouts.is_some() {
+			self.layouts.clone_from(&other.layouts);
+		}
+		if other.partials.is_some() {
+			self.partials.clone_from(&other.partials);
+		}
+		if other.store.is_some() {
+			self.store.clone_from(&other.store);
+		}
+		if other.lang.is_some() {
+			self.lang.clone_from(&other.lang);
+		}
+		if other.compress.is_some() {
+			self.compress = other.compress;
+		}
+		if other.content_encoding.is_some() {
+			self.content_encoding.clone_from(&other.content_encoding);
+		}
+		self.headers.extend(other.headers.iter().map(|(name, value)| (name.to_ascii_lowercase(), value.clone())));
+		#[cfg(feature = "filters")]
+		for filter in &other.filters {
+			if !self.filters.contains(filter) {
+				self.filters.push(filter.clone());
+			}
+		}
+		if other.site.is_some() {
+			self.site.clone_from(&other.site);
+		}
+	}
+
+	// The filters matching pages' responses go through as they're served
+	pub(crate) fn filters(&self) -> &[String] {
+		#[cfg(feature = "filters")]
+		return &self.filters;
+		#[cfg(not(feature = "filters"))]
+		&[]
+	}
+
+	pub(crate) fn enabled(&self) -> bool {
+		self.feature.as_ref().map(|f| env::var_os(format!("CARGO_FEATURE_{}", f.to_uppercase().replace('-', "_"))).is_some()).unwrap_or(true)
+	}
+}
+
+impl FromStr for Rule {
+	type Err = serde_json::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		serde_json::from_str(s)
+	}
+}
+
+fn deserialize_render<'de, D>(deserializer: D) -> Result<Option<Vec<Stage>>, D::Error> where D: Deserializer<'de> {
+	#[derive(Deserialize)]
+	#[serde(untagged)]
+	enum RenderSpec {
+		Keyword(String),
+		Stages(Vec<Stage>),
+	}
+
+	match Option::<RenderSpec>::deserialize(deserializer)? {
+		None => Ok(None),
+		Some(RenderSpec::Keyword(k)) if k == "none" => Ok(Some(vec![])),
+		Some(RenderSpec::Keyword(k)) => Err(serde::de::Error::custom(format!("unknown render pipeline {k:?}"))),
+		Some(RenderSpec::Stages(stages)) => Ok(Some(stages)),
+	}
+}
+
+#[derive(Deserialize)]
+struct DirectoryConfig {
+	#[serde(flatten)]
+	rule: Rule,
+	#[serde(default)]
+	files: serde_yaml::Mapping,
+}
+
+#[derive(Default)]
+pub(crate) struct Rules(Vec<(GlobMatcher, Rule)>);
+
+impl Rules {
+	pub(crate) fn push(&mut self, glob: &str, rule: Rule) -> Result<(), String> {
+		let matcher = GlobBuilder::new(glob).literal_separator(true).build().map_err(|e| format!("invalid glob {glob:?}: {e}"))?.compile_matcher();
+		self.0.push((matcher, rule));
+
+		Ok(())
+	}
+
+	// `dir` is relative to the content base path, and the empty path for the base itself
+	pub(crate) fn load_directory_config(&mut self, dir: &Path, config_file: &Path) -> Result<(), String> {
+		let config: DirectoryConfig = serde_yaml::from_str(&fs::read_to_string(config_file).map_err(|e| format!("failed to read {}: {e}", config_file.display()))?)
+			.map_err(|e| format!("failed to parse {}: {e}", config_file.display()))?;
+
+		let prefix = if dir.as_os_str().is_empty() {
+			String::new()
+		} else {
+			format!("{}/", globset::escape(dir.to_str().unwrap()))
+		};
+
+		self.push(&format!("{prefix}**"), config.rule)?;
+
+		for (glob, rule) in config.files {
+			let glob = glob.as_str().ok_or_else(|| format!("non-string file glob in {}", config_file.display()))?;
+			let rule = serde_yaml::from_value(rule).map_err(|e| format!("failed to parse rule for {glob:?} in {}: {e}", config_file.display()))?;
+
+			self.push(&format!("{prefix}{glob}"), rule)?;
+		}
+
+		Ok(())
+	}
+
+	pub(crate) fn resolve(&self, path: &Path) -> Rule {
+		let mut resolved = Rule::default();
+
+		for (matcher, rule) in &self.0 {
+			if matcher.is_match(path) {
+				resolved.merge(rule);
+			}
+		}
+
+		resolved
+	}
+}
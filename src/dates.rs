@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+
+use crate::freshness::days_from_civil;
+
+// A language's month and weekday names (Monday first), in the forms dates use
+struct Names {
+	months: [&'static str; 12],
+	abbreviated: [&'static str; 12],
+	weekdays: [&'static str; 7],
+}
+
+const EN: Names = Names {
+	months: ["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"],
+	abbreviated: ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"],
+	weekdays: ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"],
+};
+
+const FR: Names = Names {
+	months: ["janvier", "février", "mars", "avril", "mai", "juin", "juillet", "août", "septembre", "octobre", "novembre", "décembre"],
+	abbreviated: ["janv.", "févr.", "mars", "avr.", "mai", "juin", "juil.", "août", "sept.", "oct.", "nov.", "déc."],
+	weekdays: ["lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche"],
+};
+
+const DE: Names = Names {
+	months: ["Januar", "Februar", "März", "April", "Mai", "Juni", "Juli", "August", "September", "Oktober", "November", "Dezember"],
+	abbreviated: ["Jan.", "Feb.", "März", "Apr.", "Mai", "Juni", "Juli", "Aug.", "Sept.", "Okt.", "Nov.", "Dez."],
+	weekdays: ["Montag", "Dienstag", "Mittwoch", "Donnerstag", "Freitag", "Samstag", "Sonntag"],
+};
+
+const ES: Names = Names {
+	months: ["enero", "febrero", "marzo", "abril", "mayo", "junio", "julio", "agosto", "septiembre", "octubre", "noviembre", "diciembre"],
+	abbreviated: ["ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sept", "oct", "nov", "dic"],
+	weekdays: ["lunes", "martes", "miércoles", "jueves", "viernes", "sábado", "domingo"],
+};
+
+const IT: Names = Names {
+	months: ["gennaio", "febbraio", "marzo", "aprile", "maggio", "giugno", "luglio", "agosto", "settembre", "ottobre", "novembre", "dicembre"],
+	abbreviated: ["gen", "feb", "mar", "apr", "mag", "giu", "lug", "ago", "set", "ott", "nov", "dic"],
+	weekdays: ["lunedì", "martedì", "mercoledì", "giovedì", "venerdì", "sabato", "domenica"],
+};
+
+const PT: Names = Names {
+	months: ["janeiro", "fevereiro", "março", "abril", "maio", "junho", "julho", "agosto", "setembro", "outubro", "novembro", "dezembro"],
+	abbreviated: ["jan.", "fev.", "mar.", "abr.", "mai.", "jun.", "jul.", "ago.", "set.", "out.", "nov.", "dez."],
+	weekdays: ["segunda-feira", "terça-feira", "quarta-feira", "quinta-feira", "sexta-feira", "sábado", "domingo"],
+};
+
+const NL: Names = Names {
+	months: ["januari", "februari", "maart", "april", "mei", "juni", "juli", "augustus", "september", "oktober", "november", "december"],
+	abbreviated: ["jan", "feb", "mrt", "apr", "mei", "jun", "jul", "aug", "sep", "okt", "nov", "dec"],
+	weekdays: ["maandag", "dinsdag", "woensdag", "donderdag", "vrijdag", "zaterdag", "zondag"],
+};
+
+const SV: Names = Names {
+	months: ["januari", "februari", "mars", "april", "maj", "juni", "juli", "augusti", "september", "oktober", "november", "december"],
+	abbreviated: ["jan.", "feb.", "mars", "apr.", "maj", "juni", "juli", "aug.", "sep.", "okt.", "nov.", "dec."],
+	weekdays: ["måndag", "tisdag", "onsdag", "torsdag", "fredag", "lördag", "söndag"],
+};
+
+// Months in the genitive, as they are in a date
+const PL: Names = Names {
+	months: ["stycznia", "lutego", "marca", "kwietnia", "maja", "czerwca", "lipca", "sierpnia", "września", "października", "listopada", "grudnia"],
+	abbreviated: ["sty", "lut", "mar", "kwi", "maj", "cze", "lip", "sie", "wrz", "paź", "lis", "gru"],
+	weekdays: ["poniedziałek", "wtorek", "środa", "czwartek", "piątek", "sobota", "niedziela"],
+};
+
+const RU: Names = Names {
+	months: ["января", "февраля", "марта", "апреля", "мая", "июня", "июля", "августа", "сентября", "октября", "ноября", "декабря"],
+	abbreviated: ["янв.", "февр.", "мар.", "апр.", "мая", "июн.", "июл.", "авг.", "сент.", "окт.", "нояб.", "дек."],
+	weekdays: ["понедельник", "вторник", "среда", "четверг", "пятница", "суббота", "воскресенье"],
+};
+
+const JA: Names = Names {
+	months: ["1月", "2月", "3月", "4月", "5月", "6月", "7月", "8月", "9月", "10月", "11月", "12月"],
+	abbreviated: ["1月", "2月", "3月", "4月", "5月", "6月", "7月", "8月", "9月", "10月", "11月", "12月"],
+	weekdays: ["月曜日", "火曜日", "水曜日", "木曜日", "金曜日", "土曜日", "日曜日"],
+};
+
+const ZH: Names = Names {
+	months: ["一月", "二月", "三月", "四月", "五月", "六月", "七月", "八月", "九月", "十月", "十一月", "十二月"],
+	abbreviated: ["1月", "2月", "3月", "4月", "5月", "6月", "7月", "8月", "9月", "10月", "11月", "12月"],
+	weekdays: ["星期一", "星期二", "星期三", "星期四", "星期五", "星期六", "星期日"],
+};
+
+const KO: Names = Names {
+	months: ["1월", "2월", "3월", "4월", "5월", "6월", "7월", "8월", "9월", "10월", "11월", "12월"],
+	abbreviated: ["1월", "2월", "3월", "4월", "5월", "6월", "7월", "8월", "9월", "10월", "11월", "12월"],
+	weekdays: ["월요일", "화요일", "수요일", "목요일", "금요일", "토요일", "일요일"],
+};
+
+// Each locale's names and its short, medium, long and full date patterns (after CLDR's), by
+// lowercase language tag.  Tags with a region not here fall back to their language's.
+const LOCALES: &[(&str, &Names, [&str; 4])] = &[
+	("en", &EN, ["M/d/yy", "MMM d, y", "MMMM d, y", "EEEE, MMMM d, y"]),
+	("en-gb", &EN, ["dd/MM/y", "d MMM y", "d MMMM y", "EEEE d MMMM y"]),
+	("en-au", &EN, ["d/M/yy", "d MMM y", "d MMMM y", "EEEE d MMMM y"]),
+	("en-ie", &EN, ["dd/MM/y", "d MMM y", "d MMMM y", "EEEE d MMMM y"]),
+	("en-nz", &EN, ["d/MM/yy", "d MMM y", "d MMMM y", "EEEE, d MMMM y"]),
+	("en-ca", &EN, ["y-MM-dd", "MMM d, y", "MMMM d, y", "EEEE, MMMM d, y"]),
+	("fr", &FR, ["dd/MM/y", "d MMM y", "d MMMM y", "EEEE d MMMM y"]),
+	("fr-ca", &FR, ["y-MM-dd", "d MMM y", "d MMMM y", "EEEE d MMMM y"]),
+	("de", &DE, ["dd.MM.yy", "dd.MM.y", "d. MMMM y", "EEEE, d. MMMM y"]),
+	("es", &ES, ["d/M/yy", "d MMM y", "d 'de' MMMM 'de' y", "EEEE, d 'de' MMMM 'de' y"]),
+	("it", &IT, ["dd/MM/yy", "d MMM y", "d MMMM y", "EEEE d MMMM y"]),
+	("pt", &PT, ["dd/MM/y", "d 'de' MMM 'de' y", "d 'de' MMMM 'de' y", "EEEE, d 'de' MMMM 'de' y"]),
+	("pt-pt", &PT, ["dd/MM/yy", "dd/MM/y", "d 'de' MMMM 'de' y", "EEEE, d 'de' MMMM 'de' y"]),
+	("nl", &NL, ["dd-MM-y", "d MMM y", "d MMMM y", "EEEE d MMMM y"]),
+	("sv", &SV, ["y-MM-dd", "d MMM y", "d MMMM y", "EEEE d MMMM y"]),
+	("pl", &PL, ["d.MM.y", "d MMM y", "d MMMM y", "EEEE, d MMMM y"]),
+	("ru", &RU, ["dd.MM.y", "d MMM y 'г'.", "d MMMM y 'г'.", "EEEE, d MMMM y 'г'."]),
+	("ja", &JA, ["y/MM/dd", "y/MM/dd", "y年M月d日", "y年M月d日EEEE"]),
+	("zh", &ZH, ["y/M/d", "y年M月d日", "y年M月d日", "y年M月d日EEEE"]),
+	("ko", &KO, ["yy. M. d.", "y. M. d.", "y년 M월 d일", "y년 M월 d일 EEEE"]),
+];
+
+const STYLES: [&str; 4] = ["short", "medium", "long", "full"];
+
+fn locale(tag: &str) -> Option<&'static (&'static str, &'static Names, [&'static str; 4])> {
+	let tag = tag.to_ascii_lowercase().replace('_', "-");
+	let language = tag.split('-').next().unwrap_or_default();
+
+	LOCALES.iter().find(|(t, ..)| *t == tag).or_else(|| LOCALES.iter().find(|(t, ..)| *t == language))
+}
+
+// The year, month and day of a `YYYY-MM-DD` date, or of the date an RFC 3339 time was written on
+fn parse_date(date: &str) -> Option<(i64, i64, i64)> {
+	let mut parts = date.get(..10)?.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+	let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+
+	((1..=12).contains(&month) && (1..=31).contains(&day)).then_some((year, month, day))
+}
+
+// `date` laid out by a CLDR-style `pattern`: `d`/`dd` the day, `M`/`MM` the month's number, `MMM`
+// and `MMMM` its abbreviated and full name, `y` the year (`yy` its last two digits), `EEEE` the
+// weekday, and anything in single quotes as it is
+fn format(date: (i64, i64, i64), names: &Names, pattern: &str) -> Result<String, String> {
+	let (year, month, day) = date;
+	let weekday = (days_from_civil(year, month, day) + 3).rem_euclid(7) as usize;
+	let mut formatted = String::new();
+	let mut chars = pattern.chars().peekable();
+
+	while let Some(c) = chars.next() {
+		if c == '\'' {
+			match chars.peek() {
+				Some('\'') => {
+					chars.next();
+					formatted.push('\'');
+				},
+				_ => formatted.extend(chars.by_ref().take_while(|c| *c != '\'')),
+			}
+			continue;
+		}
+		if !c.is_ascii_alphabetic() {
+			formatted.push(c);
+			continue;
+		}
+		let mut width = 1;
+		while chars.next_if_eq(&c).is_some() {
+			width += 1;
+		}
+		match (c, width) {
+			('d', 1) => formatted += &day.to_string(),
+			('d', 2) => formatted += &format!("{day:02}"),
+			('M', 1) => formatted += &month.to_string(),
+			('M', 2) => formatted += &format!("{month:02}"),
+			('M', 3) => formatted += names.abbreviated[month as usize - 1],
+			('M', 4) => formatted += names.months[month as usize - 1],
+			('y', 2) => formatted += &format!("{:02}", year.rem_euclid(100)),
+			('y', _) => formatted += &format!("{year:0width$}"),
+			('E', 4) => formatted += names.weekdays[weekday],
+			_ => return Err(format!("`{}` isn't a date pattern field", c.to_string().repeat(width))),
+		}
+	}
+
+	Ok(formatted)
+}
+
+// `{{ page.date | date_format(locale=lang, format="long") }}`: a `YYYY-MM-DD` (or RFC 3339) date
+// in one of the locale's `short`, `medium` (the default), `long` or `full` formats, or in a pattern
+// of the template's own.  The locale defaults to `en`.
+fn date_format(value: &tera::Value, args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+	let date = value.as_str().and_then(parse_date).ok_or_else(|| format!("date_format: {value} isn't a YYYY-MM-DD date"))?;
+	let tag = match args.get("locale") {
+		None => "en",
+		Some(locale) => locale.as_str().ok_or("date_format needs a `locale` string")?,
+	};
+	let format_name = match args.get("format") {
+		None => "medium",
+		Some(format) => format.as_str().ok_or("date_format needs a `format` string")?,
+	};
+	let (_, names, formats) = locale(tag).ok_or_else(|| format!("date_format: no date formats for the locale {tag}"))?;
+	let pattern = STYLES.iter().position(|s| *s == format_name).map_or(format_name, |i| formats[i]);
+
+	Ok(format(date, names, pattern).map_err(|e| format!("date_format: {e}"))?.into())
+}
+
+pub(crate) fn register(templater: &mut tera::Tera) {
+	templater.register_filter("date_format", date_format);
+}
@@ -0,0 +1,508 @@
+mod emoji;
+#[cfg(feature = "kroki")]
+mod kroki;
+mod notes;
+
+use jotdown::{Attributes, Container, Event, Render};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::PathBuf};
+
+pub use notes::FootnotePlacement;
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RawHtml {
+	/// Raw HTML blocks and inlines are emitted as-is
+	Allow,
+	/// Raw HTML is dropped from the output entirely
+	Strip,
+	/// Raw HTML is shown as code, rather than interpreted
+	Escape,
+}
+
+/// Restrictions for rendering untrusted content.  Raw HTML in the source is always dropped, and the
+/// page's HTML (whatever rendered it) then goes through ammonia, which keeps only the tags it
+/// deems safe (and `<section>`), the attributes on the allowlist besides those tags need, and URLs
+/// with allowed schemes.  Template syntax is escaped so the content can't reach into the Tera
+/// context.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Sanitize {
+	attributes: Vec<String>,
+	url_schemes: Vec<String>,
+}
+
+impl Default for Sanitize {
+	fn default() -> Self {
+		Sanitize {
+			attributes: ["class", "id", "lang", "dir", "title"].map(String::from).to_vec(),
+			url_schemes: ["http", "https", "mailto"].map(String::from).to_vec(),
+		}
+	}
+}
+
+impl Sanitize {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn attributes<I, S>(mut self, attributes: I) -> Self where I: IntoIterator<Item = S>, S: Into<String> {
+		self.attributes = attributes.into_iter().map(Into::into).collect();
+		self
+	}
+
+	/// Scheme-less (relative) URLs are always allowed
+	pub fn url_schemes<I, S>(mut self, url_schemes: I) -> Self where I: IntoIterator<Item = S>, S: Into<String> {
+		self.url_schemes = url_schemes.into_iter().map(Into::into).collect();
+		self
+	}
+
+	fn clean(&self, html: &str) -> String {
+		let mut cleaner = ammonia::Builder::default();
+		cleaner.add_tags(["section"])
+			.generic_attributes(self.attributes.iter().map(String::as_str).collect())
+			.url_schemes(self.url_schemes.iter().map(String::as_str).collect())
+			// Footnotes' and citations' ARIA roles, and table columns' alignment
+			.add_tag_attributes("a", ["role"])
+			.add_tag_attributes("section", ["role"])
+			.add_tag_attributes("th", ["style"])
+			.add_tag_attributes("td", ["style"])
+			.filter_style_properties(["text-align"].into())
+			// Only needed for `target`, which never makes it through
+			.link_rel(None);
+
+		// `{` means nothing to HTML, so encoding it leaves the page intact while stopping Tera from
+		// seeing `{{`, `{%` or `{#` in untrusted text
+		cleaner.clean(html).to_string().replace('{', "&#123;")
+	}
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DjotOptions {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	raw_html: Option<RawHtml>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	footnotes: Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	attributes: Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	sanitize: Option<Sanitize>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	footnote_placement: Option<FootnotePlacement>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	footnote_backlink: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	bibliography: Option<PathBuf>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	emoji: Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	emoji_images: Option<String>,
+	#[cfg(feature = "kroki")]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	kroki: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	heading_offset: Option<u16>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	audiences: Option<Vec<String>>,
+}
+
+impl DjotOptions {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn raw_html(mut self, raw_html: RawHtml) -> Self {
+		self.raw_html = Some(raw_html);
+		self
+	}
+
+	/// Whether footnote references and definitions are rendered, or silently dropped
+	pub fn footnotes(mut self, footnotes: bool) -> Self {
+		self.footnotes = Some(footnotes);
+		self
+	}
+
+	/// Whether `{.class #id key=value}` attributes make it through to the HTML
+	pub fn attributes(mut self, attributes: bool) -> Self {
+		self.attributes = Some(attributes);
+		self
+	}
+
+	pub fn sanitize(mut self, sanitize: Sanitize) -> Self {
+		self.sanitize = Some(sanitize);
+		self
+	}
+
+	pub fn footnote_placement(mut self, placement: FootnotePlacement) -> Self {
+		self.footnote_placement = Some(placement);
+		self
+	}
+
+	/// The link text taking readers from a footnote back to its reference; `↩︎` by default
+	pub fn footnote_backlink<S>(mut self, backlink: S) -> Self where S: Into<String> {
+		self.footnote_backlink = Some(backlink.into());
+		self
+	}
+
+	/// A YAML file mapping citation keys to (djot) reference text.  `[key]{.cite}` in a page
+	/// becomes a numbered link to a bibliography of the cited entries at the end of the page.
+	pub fn bibliography<P>(mut self, bibliography: P) -> Self where P: Into<PathBuf> {
+		self.bibliography = Some(bibliography.into());
+		self
+	}
+
+	/// Whether `:shortcode:` symbols become Unicode emoji; on by default
+	pub fn emoji(mut self, emoji: bool) -> Self {
+		self.emoji = Some(emoji);
+		self
+	}
+
+	/// Renders every `:shortcode:` as an image instead, with `{name}` in `url` replaced by the
+	/// shortcode name (e.g. `/images/emoji/{name}.png`), which also allows custom emoji
+	pub fn emoji_images<S>(mut self, url: S) -> Self where S: Into<String> {
+		self.emoji_images = Some(url.into());
+		self
+	}
+
+	/// Renders code blocks in a diagram language (`plantuml`, `d2`, `mermaid`, `graphviz` and the
+	/// rest Kroki knows) as inline SVG, by POSTing them to the Kroki instance at this URL (e.g.
+	/// `https://kroki.io`) during the build, with the `kroki` feature; unless the djot is sanitized,
+	/// as the SVG could then be anything
+	#[cfg(feature = "kroki")]
+	pub fn kroki<S>(mut self, url: S) -> Self where S: Into<String> {
+		self.kroki = Some(url.into());
+		self
+	}
+
+	/// Demotes every heading by this many levels (down to `<h6>` at most), e.g. 1 where the layout
+	/// supplies the page's `<h1>`; a layout can also declare it for its pages with a
+	/// `{# heading_offset: 1 #}` comment
+	pub fn heading_offset(mut self, levels: u16) -> Self {
+		self.heading_offset = Some(levels);
+		self
+	}
+
+	/// Keeps only the regions (blocks, or spans) marked for one of these audiences, dropping any
+	/// with a class like `{.audience-internal}` for another; by default, every region is kept
+	pub fn audiences<I, S>(mut self, audiences: I) -> Self where I: IntoIterator<Item = S>, S: Into<String> {
+		self.audiences = Some(audiences.into_iter().map(Into::into).collect());
+		self
+	}
+
+	// `html` as `sanitize` allows it, if the content is untrusted
+	pub(crate) fn sanitized(&self, html: String) -> String {
+		match &self.sanitize {
+			Some(sanitize) => sanitize.clean(&html),
+			None => html,
+		}
+	}
+
+	pub(crate) fn bibliography_file(&self) -> Option<&PathBuf> {
+		self.bibliography.as_ref()
+	}
+
+	pub(crate) fn merge(&mut self, other: &DjotOptions) {
+		if other.raw_html.is_some() {
+			self.raw_html = other.raw_html;
+		}
+		if other.footnotes.is_some() {
+			self.footnotes = other.footnotes;
+		}
+		if other.attributes.is_some() {
+			self.attributes = other.attributes;
+		}
+		if other.sanitize.is_some() {
+			self.sanitize.clone_from(&other.sanitize);
+		}
+		if other.footnote_placement.is_some() {
+			self.footnote_placement = other.footnote_placement;
+		}
+		if other.footnote_backlink.is_some() {
+			self.footnote_backlink.clone_from(&other.footnote_backlink);
+		}
+		if other.bibliography.is_some() {
+			self.bibliography.clone_from(&other.bibliography);
+		}
+		if other.emoji.is_some() {
+			self.emoji = other.emoji;
+		}
+		if other.emoji_images.is_some() {
+			self.emoji_images.clone_from(&other.emoji_images);
+		}
+		#[cfg(feature = "kroki")]
+		if other.kroki.is_some() {
+			self.kroki.clone_from(&other.kroki);
+		}
+		if other.heading_offset.is_some() {
+			self.heading_offset = other.heading_offset;
+		}
+		if other.audiences.is_some() {
+			self.audiences.clone_from(&other.audiences);
+		}
+	}
+}
+
+/// The heading offset a layout declares with a `{# heading_offset: N #}` comment, if it does
+pub(crate) fn layout_heading_offset(layout: &str) -> Option<u16> {
+	layout.split("{#").skip(1)
+		.filter_map(|rest| rest.split_once("#}"))
+		.find_map(|(comment, _)| comment.trim().strip_prefix("heading_offset:")?.trim().parse().ok())
+}
+
+fn is_raw_html(c: &Container<'_>) -> bool {
+	matches!(c, Container::RawBlock { format: "html" } | Container::RawInline { format: "html" })
+}
+
+// Whether `attrs` mark a region for audiences none of which are `audiences`
+fn other_audience(attrs: &Attributes<'_>, audiences: Option<&[String]>) -> bool {
+	let Some(audiences) = audiences else { return false };
+	let Some(class) = attrs.get("class").map(|c| c.to_string()) else { return false };
+	let mut marked = class.split_whitespace().filter_map(|c| c.strip_prefix("audience-")).peekable();
+
+	marked.peek().is_some() && !marked.any(|audience| audiences.iter().any(|a| a == audience))
+}
+
+fn filter<'s, I>(events: I, options: &DjotOptions) -> Vec<Event<'s>> where I: Iterator<Item = Event<'s>> {
+	let raw_html = if options.sanitize.is_some() { RawHtml::Strip } else { options.raw_html.unwrap_or(RawHtml::Allow) };
+	let footnotes = options.footnotes.unwrap_or(true);
+	let attributes = options.attributes.unwrap_or(true);
+	let heading_offset = options.heading_offset.unwrap_or(0);
+	let audiences = options.audiences.as_deref();
+
+	let mut result = vec![];
+	let mut skipping = 0usize;
+
+	for event in events {
+		if skipping > 0 {
+			match &event {
+				Event::Start(..) => skipping += 1,
+				Event::End(..) => skipping -= 1,
+				_ => (),
+			}
+			continue;
+		}
+
+		let event = match event {
+			Event::Start(_, attrs) if other_audience(&attrs, audiences) => {
+				skipping = 1;
+				continue;
+			},
+			Event::ThematicBreak(attrs) if other_audience(&attrs, audiences) => continue,
+			Event::Start(c, _) if raw_html == RawHtml::Strip && is_raw_html(&c) => {
+				skipping = 1;
+				continue;
+			},
+			Event::Start(Container::Footnote { .. }, _) if !footnotes => {
+				skipping = 1;
+				continue;
+			},
+			Event::FootnoteReference(_) if !footnotes => continue,
+			Event::Start(Container::RawBlock { format: "html" }, attrs) if raw_html == RawHtml::Escape => Event::Start(Container::CodeBlock { language: "html" }, attrs),
+			Event::End(Container::RawBlock { format: "html" }) if raw_html == RawHtml::Escape => Event::End(Container::CodeBlock { language: "html" }),
+			Event::Start(Container::RawInline { format: "html" }, attrs) if raw_html == RawHtml::Escape => Event::Start(Container::Verbatim, attrs),
+			Event::End(Container::RawInline { format: "html" }) if raw_html == RawHtml::Escape => Event::End(Container::Verbatim),
+			e => e,
+		};
+
+		let event = match event {
+			Event::Start(Container::Heading { level, has_section, id }, attrs) if heading_offset > 0 => {
+				Event::Start(Container::Heading { level: (level + heading_offset).min(6), has_section, id }, attrs)
+			},
+			Event::End(Container::Heading { level, has_section, id }) if heading_offset > 0 => {
+				Event::End(Container::Heading { level: (level + heading_offset).min(6), has_section, id })
+			},
+			e => e,
+		};
+
+		let event = match event {
+			Event::Start(c, _) if !attributes => Event::Start(c, Attributes::new()),
+			Event::ThematicBreak(_) if !attributes => Event::ThematicBreak(Attributes::new()),
+			e => e,
+		};
+
+		result.push(event);
+	}
+
+	result
+}
+
+fn expand_symbols<'s>(events: Vec<Event<'s>>, options: &DjotOptions) -> Vec<Event<'s>> {
+	let emoji = options.emoji.unwrap_or(true);
+	let mut result = Vec::with_capacity(events.len());
+
+	for event in events {
+		match event {
+			Event::Symbol(name) if options.emoji_images.is_some() => {
+				let src = options.emoji_images.as_ref().unwrap().replace("{name}", &name);
+				result.extend(notes::raw_inline(format!(r#"<img class="emoji" alt=":{}:" src="{}">"#, notes::escape_attr(&name), notes::escape_attr(&src))));
+			},
+			Event::Symbol(name) if emoji => match emoji::lookup(&name) {
+				Some(e) => result.push(Event::Str(e.into())),
+				None => result.push(Event::Symbol(name)),
+			},
+			e => result.push(e),
+		}
+	}
+
+	result
+}
+
+fn field_name(term: &str) -> String {
+	term.trim().trim_end_matches(':').trim().to_lowercase().chars().map(|c| if c.is_alphanumeric() { c } else { '_' }).collect()
+}
+
+// A description list marked `{.metadata}` (`: Since` / `  1.2`, and so on) is lifted out of the
+// page, with each term/details pair becoming a plain-text field
+fn extract_fields<'s>(events: Vec<Event<'s>>) -> (Vec<Event<'s>>, BTreeMap<String, String>) {
+	let mut fields = BTreeMap::new();
+	let mut result = Vec::with_capacity(events.len());
+	let mut events = events.into_iter();
+
+	while let Some(event) = events.next() {
+		match event {
+			Event::Start(Container::DescriptionList, attrs) if attrs.get("class").map(|c| c.to_string().split_whitespace().any(|c| c == "metadata")).unwrap_or(false) => {
+				let mut depth = 1;
+				let mut term = String::new();
+				let mut details = String::new();
+				let mut in_term = false;
+
+				for e in events.by_ref() {
+					match e {
+						Event::Start(Container::DescriptionTerm, _) => {
+							depth += 1;
+							in_term = true;
+							term.clear();
+						},
+						Event::End(Container::DescriptionTerm) => {
+							depth -= 1;
+							in_term = false;
+							details.clear();
+						},
+						Event::End(Container::DescriptionDetails) => {
+							depth -= 1;
+							fields.insert(field_name(&term), details.trim().to_string());
+						},
+						Event::Start(..) => depth += 1,
+						Event::End(..) => {
+							depth -= 1;
+
+							if depth == 0 {
+								break;
+							}
+						},
+						Event::Str(s) if in_term => term.push_str(&s),
+						Event::Str(s) => details.push_str(&s),
+						Event::Softbreak | Event::Hardbreak | Event::NonBreakingSpace => {
+							if in_term { term.push(' ') } else { details.push(' ') }
+						},
+						_ => (),
+					}
+				}
+			},
+			e => result.push(e),
+		}
+	}
+
+	(result, fields)
+}
+
+pub(crate) fn render_djot(input: &str, options: &DjotOptions) -> Result<(String, BTreeMap<String, String>), String> {
+	render_events(jotdown::Parser::new(input), options)
+}
+
+// Renders a document's events (djot's own, or another markup's as djot's) with `options`
+pub(crate) fn render_events<'s, I>(events: I, options: &DjotOptions) -> Result<(String, BTreeMap<String, String>), String> where I: Iterator<Item = Event<'s>> {
+	let mut body = String::new();
+	let events = filter(events, options);
+	let (events, fields) = extract_fields(events);
+	let events = expand_symbols(events, options);
+	#[cfg(feature = "kroki")]
+	let events = match (&options.kroki, &options.sanitize) {
+		(Some(kroki), None) => kroki::process(events, kroki)?,
+		_ => events,
+	};
+	let events = notes::process(events, options.footnote_placement, options.footnote_backlink.as_deref(), options.bibliography.as_deref())?;
+	jotdown::html::Renderer::default().push(events.into_iter(), &mut body).map_err(|e| format!("djot rendering failed: {e}"))?;
+
+	Ok((options.sanitized(body), fields))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{render_djot, DjotOptions, RawHtml, Sanitize};
+	use std::{env, fs};
+
+	fn sanitized(djot: &str) -> String {
+		render_djot(djot, &DjotOptions::default().sanitize(Sanitize::new())).unwrap().0
+	}
+
+	#[test]
+	fn disallowed_url_schemes_are_dropped() {
+		for url in ["javascript:alert`1`", "JavaScript:alert`1`", "java\tscript:alert`1`", " javascript:alert`1`", "vbscript:msgbox", "data:text/html,hi"] {
+			assert_eq!(sanitized(&format!("[x]({url})")), "<p><a>x</a></p>\n", "for {url:?}");
+		}
+		assert_eq!(sanitized("![x](data:image/svg+xml,hi)"), "<p><img alt=\"x\"></p>\n");
+		assert_eq!(sanitized("<javascript:alert(1)>"), "<p><a>javascript:alert(1)</a></p>\n");
+		assert_eq!(sanitized("[x][r]\n\n[r]: javascript:alert(1)"), "<p><a>x</a></p>\n");
+	}
+
+	#[test]
+	fn allowed_and_relative_urls_are_kept() {
+		assert_eq!(sanitized("[x](/relative?a:b) [y](#frag) [z](https://example.com) [m](mailto:me@example.com)"), "<p><a href=\"/relative?a:b\">x</a> <a href=\"#frag\">y</a> <a href=\"https://example.com\">z</a> <a href=\"mailto:me@example.com\">m</a></p>\n");
+
+		let ftp = DjotOptions::default().sanitize(Sanitize::new().url_schemes(["ftp"]));
+		assert_eq!(render_djot("[x](ftp://example.com) [y](https://example.com)", &ftp).unwrap().0, "<p><a href=\"ftp://example.com\">x</a> <a>y</a></p>\n");
+	}
+
+	#[test]
+	fn raw_html_is_dropped() {
+		assert_eq!(sanitized("`<script>alert(1)</script>`{=html}"), "<p></p>\n");
+		assert_eq!(sanitized("```=html\n<script>alert(1)</script>\n```\n\nafter"), "\n<p>after</p>\n");
+
+		let allow = DjotOptions::default().raw_html(RawHtml::Allow).sanitize(Sanitize::new());
+		assert_eq!(render_djot("`<b>`{=html}", &allow).unwrap().0, "<p></p>\n");
+	}
+
+	#[test]
+	fn html_added_after_parsing_is_cleaned() {
+		assert_eq!(DjotOptions::default().sanitized("<p onclick=\"x\">a<script>alert(1)</script><img src=\"javascript:x\" onerror=\"x\"></p>".into()), "<p onclick=\"x\">a<script>alert(1)</script><img src=\"javascript:x\" onerror=\"x\"></p>");
+		let options = DjotOptions::default().sanitize(Sanitize::new());
+		assert_eq!(options.sanitized("<p onclick=\"x\">a<script>alert(1)</script><img src=\"javascript:x\" onerror=\"x\"><iframe src=\"/\"></iframe></p>".into()), "<p>a<img></p>");
+
+		let emoji = options.clone().emoji_images("javascript:alert('{name}')");
+		assert_eq!(render_djot("a :smile:", &emoji).unwrap().0, "<p>a <img class=\"emoji\" alt=\":smile:\"></p>\n");
+	}
+
+	#[test]
+	fn notes_and_citations_are_cleaned() {
+		let bibliography = env::temp_dir().join(format!("static-builder-bibliography-{}.yaml", std::process::id()));
+		fs::write(&bibliography, "knuth: \"Knuth `<script>alert(1)</script>`{=html} [TAOCP](javascript:alert)\"\n").unwrap();
+		let options = DjotOptions::default().sanitize(Sanitize::new()).bibliography(&bibliography);
+
+		let (html, _) = render_djot("See [knuth]{.cite}.[^1]\n\n[^1]: A `<b onclick=x>`{=html} note.\n", &options).unwrap();
+		let _ = fs::remove_file(&bibliography);
+
+		assert!(!html.contains("script") && !html.contains("javascript") && !html.contains("onclick"), "{html}");
+		assert!(html.contains("role=\"doc-biblioref\"") && html.contains("role=\"doc-bibliography\"") && html.contains("<li id=\"ref-knuth\">Knuth  <a>TAOCP</a></li>"), "{html}");
+		assert!(html.contains("role=\"doc-noteref\"") && html.contains("<section role=\"doc-endnotes\">"), "{html}");
+	}
+
+	#[test]
+	fn only_allowed_attributes_are_kept() {
+		assert_eq!(sanitized("[x]{onclick=\"alert(1)\" style=\"color: red\" .ok #id title=\"t\" lang=en href=\"javascript:x\"}"), "<p><span id=\"id\" title=\"t\" lang=\"en\" class=\"ok\">x</span></p>\n");
+		assert_eq!(sanitized("{onclick=x .rule}\n***"), "<hr class=\"rule\">\n");
+		assert_eq!(sanitized("| a |\n|--:|\n| 1 |"), "<table>\n<tbody><tr>\n<th style=\"text-align:right\">a</th>\n</tr>\n<tr>\n<td style=\"text-align:right\">1</td>\n</tr>\n</tbody></table>\n");
+
+		let none = DjotOptions::default().sanitize(Sanitize::new().attributes::<_, String>([]));
+		assert_eq!(render_djot("[x]{.ok #id}", &none).unwrap().0, "<p><span>x</span></p>\n");
+	}
+
+	#[test]
+	fn template_syntax_is_escaped() {
+		assert_eq!(sanitized("{{ secret }} {# c #}"), "<p>&#123;&#123; secret }} &#123;# c #}</p>\n");
+		assert_eq!(sanitized("`{{ code }}`"), "<p><code>&#123;&#123; code }}</code></p>\n");
+		assert_eq!(sanitized("[x]{title=\"{% raw %}\"}"), "<p><span title=\"&#123;% raw %}\">x</span></p>\n");
+		assert_eq!(render_djot("{{ trusted }}", &DjotOptions::default()).unwrap().0, "<p>{{ trusted }}</p>\n");
+	}
+}
@@ -0,0 +1,206 @@
+// A selection of the GitHub shortcodes authors reach for most; anything else renders as `:name:`
+// Sorted by name, for binary searching
+pub(super) const EMOJI: &[(&str, &str)] = &[
+	("+1", "👍"),
+	("-1", "👎"),
+	("100", "💯"),
+	("1234", "🔢"),
+	("airplane", "✈️"),
+	("alarm_clock", "⏰"),
+	("angry", "😠"),
+	("apple", "🍎"),
+	("arrow_down", "⬇️"),
+	("arrow_left", "⬅️"),
+	("arrow_right", "➡️"),
+	("arrow_up", "⬆️"),
+	("art", "🎨"),
+	("baby", "👶"),
+	("balloon", "🎈"),
+	("bangbang", "‼️"),
+	("bee", "🐝"),
+	("beer", "🍺"),
+	("beers", "🍻"),
+	("bell", "🔔"),
+	("bike", "🚲"),
+	("bird", "🐦"),
+	("blush", "😊"),
+	("bomb", "💣"),
+	("book", "📖"),
+	("books", "📚"),
+	("boom", "💥"),
+	("bouquet", "💐"),
+	("bug", "🐛"),
+	("bulb", "💡"),
+	("bus", "🚌"),
+	("cake", "🍰"),
+	("calendar", "📆"),
+	("camera", "📷"),
+	("car", "🚗"),
+	("cat", "🐱"),
+	("chart_with_downwards_trend", "📉"),
+	("chart_with_upwards_trend", "📈"),
+	("check", "✔️"),
+	("checkered_flag", "🏁"),
+	("christmas_tree", "🎄"),
+	("clap", "👏"),
+	("clipboard", "📋"),
+	("clock1", "🕐"),
+	("cloud", "☁️"),
+	("coffee", "☕"),
+	("computer", "💻"),
+	("confetti_ball", "🎊"),
+	("confused", "😕"),
+	("construction", "🚧"),
+	("cookie", "🍪"),
+	("cool", "🆒"),
+	("cow", "🐮"),
+	("crown", "👑"),
+	("cry", "😢"),
+	("crystal_ball", "🔮"),
+	("dart", "🎯"),
+	("dash", "💨"),
+	("disappointed", "😞"),
+	("dog", "🐶"),
+	("dollar", "💵"),
+	("dragon", "🐉"),
+	("earth_africa", "🌍"),
+	("earth_americas", "🌎"),
+	("earth_asia", "🌏"),
+	("email", "📧"),
+	("envelope", "✉️"),
+	("exclamation", "❗"),
+	("eyes", "👀"),
+	("facepalm", "🤦"),
+	("fire", "🔥"),
+	("fish", "🐟"),
+	("flashlight", "🔦"),
+	("floppy_disk", "💾"),
+	("flower", "🌸"),
+	("frog", "🐸"),
+	("gear", "⚙️"),
+	("gem", "💎"),
+	("ghost", "👻"),
+	("gift", "🎁"),
+	("globe_with_meridians", "🌐"),
+	("grimacing", "😬"),
+	("grin", "😁"),
+	("grinning", "😀"),
+	("hammer", "🔨"),
+	("hammer_and_wrench", "🛠️"),
+	("hand", "✋"),
+	("hankey", "💩"),
+	("heart", "❤️"),
+	("heart_eyes", "😍"),
+	("heavy_check_mark", "✔️"),
+	("heavy_minus_sign", "➖"),
+	("heavy_plus_sign", "➕"),
+	("hourglass", "⌛"),
+	("house", "🏠"),
+	("hugs", "🤗"),
+	("information_source", "ℹ️"),
+	("innocent", "😇"),
+	("joy", "😂"),
+	("key", "🔑"),
+	("kiss", "😘"),
+	("laughing", "😆"),
+	("link", "🔗"),
+	("lock", "🔒"),
+	("loudspeaker", "📢"),
+	("mag", "🔍"),
+	("mailbox", "📫"),
+	("medal_sports", "🏅"),
+	("memo", "📝"),
+	("moneybag", "💰"),
+	("moon", "🌔"),
+	("mortar_board", "🎓"),
+	("muscle", "💪"),
+	("musical_note", "🎵"),
+	("neutral_face", "😐"),
+	("new", "🆕"),
+	("no_entry", "⛔"),
+	("no_entry_sign", "🚫"),
+	("ok", "🆗"),
+	("ok_hand", "👌"),
+	("package", "📦"),
+	("page_facing_up", "📄"),
+	("paperclip", "📎"),
+	("partying_face", "🥳"),
+	("pencil", "📝"),
+	("pencil2", "✏️"),
+	("penguin", "🐧"),
+	("phone", "☎️"),
+	("pig", "🐷"),
+	("pizza", "🍕"),
+	("point_down", "👇"),
+	("point_left", "👈"),
+	("point_right", "👉"),
+	("point_up", "☝️"),
+	("poop", "💩"),
+	("pray", "🙏"),
+	("pushpin", "📌"),
+	("question", "❓"),
+	("rabbit", "🐰"),
+	("rage", "😡"),
+	("rainbow", "🌈"),
+	("raised_hands", "🙌"),
+	("recycle", "♻️"),
+	("red_circle", "🔴"),
+	("relaxed", "☺️"),
+	("relieved", "😌"),
+	("rocket", "🚀"),
+	("rofl", "🤣"),
+	("rose", "🌹"),
+	("rotating_light", "🚨"),
+	("runner", "🏃"),
+	("sad", "😞"),
+	("scissors", "✂️"),
+	("scream", "😱"),
+	("see_no_evil", "🙈"),
+	("shield", "🛡️"),
+	("shrug", "🤷"),
+	("skull", "💀"),
+	("sleeping", "😴"),
+	("slightly_smiling_face", "🙂"),
+	("smile", "😄"),
+	("smiley", "😃"),
+	("smirk", "😏"),
+	("snail", "🐌"),
+	("snake", "🐍"),
+	("snowflake", "❄️"),
+	("sob", "😭"),
+	("sparkles", "✨"),
+	("speech_balloon", "💬"),
+	("star", "⭐"),
+	("star2", "🌟"),
+	("stop_sign", "🛑"),
+	("sun_with_face", "🌞"),
+	("sunglasses", "😎"),
+	("sunny", "☀️"),
+	("sweat_smile", "😅"),
+	("tada", "🎉"),
+	("thinking", "🤔"),
+	("thumbsdown", "👎"),
+	("thumbsup", "👍"),
+	("tired_face", "😫"),
+	("trophy", "🏆"),
+	("truck", "🚚"),
+	("turtle", "🐢"),
+	("umbrella", "☂️"),
+	("unamused", "😒"),
+	("unlock", "🔓"),
+	("v", "✌️"),
+	("warning", "⚠️"),
+	("wave", "👋"),
+	("whale", "🐳"),
+	("white_check_mark", "✅"),
+	("wink", "😉"),
+	("wrench", "🔧"),
+	("x", "❌"),
+	("yum", "😋"),
+	("zap", "⚡"),
+	("zzz", "💤"),
+];
+
+pub(super) fn lookup(name: &str) -> Option<&'static str> {
+	EMOJI.binary_search_by(|(n, _)| (*n).cmp(name)).ok().map(|i| EMOJI[i].1)
+}
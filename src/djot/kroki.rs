@@ -0,0 +1,83 @@
+use jotdown::{Container, Event};
+use std::{env, fs, path::PathBuf, time::Duration};
+
+use crate::assets::fnv1a;
+
+// The diagram languages Kroki renders, as code block languages
+const DIAGRAM_TYPES: &[&str] = &[
+	"actdiag", "blockdiag", "bpmn", "bytefield", "c4plantuml", "d2", "dbml", "ditaa", "erd", "excalidraw", "graphviz", "mermaid",
+	"nomnoml", "nwdiag", "packetdiag", "pikchr", "plantuml", "rackdiag", "seqdiag", "structurizr", "svgbob", "symbolator", "tikz",
+	"umlet", "vega", "vegalite", "wavedrom", "wireviz",
+];
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+// Rendered diagrams are kept by a hash of what went into them, so each is only ever requested once
+fn cache_dir() -> PathBuf {
+	env::var_os("OUT_DIR").map(PathBuf::from).unwrap_or_else(env::temp_dir).join("static-builder-kroki")
+}
+
+fn post(url: &str, body: &str) -> Result<String, String> {
+	let config = ureq::Agent::config_builder().timeout_global(Some(TIMEOUT)).http_status_as_error(false).build();
+	let mut response = ureq::Agent::new_with_config(config).post(url).header("accept", "image/svg+xml").content_type("text/plain").send(body).map_err(|e| format!("{url}: {e}"))?;
+	let status = response.status();
+	let text = response.body_mut().read_to_string().map_err(|e| format!("{url}: {e}"))?;
+
+	if !status.is_success() {
+		return Err(format!("{url}: {status}: {}", text.trim()));
+	}
+	let content_type = response.headers().get("content-type").and_then(|v| v.to_str().ok()).unwrap_or_default();
+	if !content_type.starts_with("image/svg+xml") {
+		return Err(format!("{url}: got {content_type:?} rather than SVG"));
+	}
+
+	Ok(text)
+}
+
+fn render(kroki: &str, language: &str, source: &str) -> Result<String, String> {
+	let url = format!("{}/{language}/svg", kroki.trim_end_matches('/'));
+	let file = cache_dir().join(format!("{:016x}.svg", fnv1a(format!("{url}\n{source}").as_bytes())));
+
+	if let Ok(svg) = fs::read_to_string(&file) {
+		return Ok(svg);
+	}
+
+	let svg = post(&url, source).map_err(|e| format!("diagram rendering failed: {e}"))?;
+
+	// Failing to cache only costs a request next time
+	let _ = fs::create_dir_all(cache_dir()).and_then(|_| fs::write(&file, &svg));
+
+	Ok(svg)
+}
+
+/// Replaces each code block in a diagram language with the SVG the Kroki instance at `kroki` renders it as
+pub(super) fn process<'s>(events: Vec<Event<'s>>, kroki: &str) -> Result<Vec<Event<'s>>, String> {
+	let mut result = Vec::with_capacity(events.len());
+	let mut events = events.into_iter();
+
+	while let Some(event) = events.next() {
+		match event {
+			Event::Start(Container::CodeBlock { language }, attrs) if DIAGRAM_TYPES.contains(&language) => {
+				let mut source = String::new();
+
+				for e in events.by_ref() {
+					match e {
+						Event::End(Container::CodeBlock { .. }) => break,
+						Event::Str(s) => source.push_str(&s),
+						_ => (),
+					}
+				}
+
+				let svg = render(kroki, language, &source)?;
+				// Inline SVG has no use for an XML declaration or doctype
+				let svg = svg.find("<svg").map(|i| &svg[i..]).unwrap_or(&svg);
+				let class = attrs.get("class").map(|c| format!(" {c}")).unwrap_or_default();
+
+				result.extend(super::notes::raw_block(format!(r#"<figure class="diagram diagram-{language}{}">{svg}</figure>"#, super::notes::escape_attr(&class))));
+			},
+			e => result.push(e),
+		}
+	}
+
+	Ok(result)
+}
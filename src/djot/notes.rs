@@ -0,0 +1,218 @@
+use jotdown::{Attributes, Container, Event, Render};
+use serde::{Deserialize, Serialize};
+use std::{collections::{BTreeMap, HashMap}, fs, path::Path};
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FootnotePlacement {
+	/// All notes are gathered at the end of the page
+	Article,
+	/// Notes are placed at the end of the (innermost) heading section that references them
+	Section,
+}
+
+pub(super) fn raw_block<'s>(html: String) -> [Event<'s>; 3] {
+	[
+		Event::Start(Container::RawBlock { format: "html" }, Attributes::new()),
+		Event::Str(html.into()),
+		Event::End(Container::RawBlock { format: "html" }),
+	]
+}
+
+pub(super) fn raw_inline<'s>(html: String) -> [Event<'s>; 3] {
+	[
+		Event::Start(Container::RawInline { format: "html" }, Attributes::new()),
+		Event::Str(html.into()),
+		Event::End(Container::RawInline { format: "html" }),
+	]
+}
+
+fn render_events(events: Vec<Event<'_>>) -> Result<String, String> {
+	let mut html = String::new();
+	jotdown::html::Renderer::default().push(events.into_iter(), &mut html).map_err(|e| format!("djot rendering failed: {e}"))?;
+
+	Ok(html)
+}
+
+pub(super) fn escape_attr(s: &str) -> String {
+	s.replace('&', "&amp;").replace('"', "&quot;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+struct Footnotes<'s> {
+	placement: FootnotePlacement,
+	backlink: String,
+	definitions: HashMap<&'s str, Vec<Event<'s>>>,
+	numbers: HashMap<&'s str, usize>,
+	pending: Vec<&'s str>,
+}
+
+impl<'s> Footnotes<'s> {
+	fn reference(&mut self, label: &'s str) -> String {
+		let next = self.numbers.len() + 1;
+		let n = *self.numbers.entry(label).or_insert_with(|| {
+			self.pending.push(label);
+			next
+		});
+
+		format!(r##"<a id="fnref{n}" href="#fn{n}" role="doc-noteref"><sup>{n}</sup></a>"##)
+	}
+
+	fn flush(&mut self) -> Result<Option<String>, String> {
+		if self.pending.is_empty() {
+			return Ok(None);
+		}
+
+		let first = self.numbers[self.pending[0]];
+		let mut html = String::from("<section role=\"doc-endnotes\">\n<hr>\n");
+		html.push_str(&if first == 1 { "<ol>\n".to_string() } else { format!("<ol start=\"{first}\">\n") });
+
+		for label in self.pending.drain(..) {
+			let n = self.numbers[label];
+			let backlink = format!(r##"<a href="#fnref{n}" role="doc-backlink">{}</a>"##, self.backlink);
+			let mut body = render_events(self.definitions.remove(label).unwrap_or_default())?;
+
+			match body.trim_end().strip_suffix("</p>") {
+				Some(rest) => body = format!("{rest}{backlink}</p>"),
+				None => body.push_str(&format!("\n<p>{backlink}</p>")),
+			}
+
+			html.push_str(&format!("<li id=\"fn{n}\">\n{}\n</li>\n", body.trim()));
+		}
+
+		html.push_str("</ol>\n</section>\n");
+
+		Ok(Some(html))
+	}
+}
+
+fn is_citation(attrs: &Attributes<'_>) -> bool {
+	attrs.get("class").map(|c| c.to_string().split_whitespace().any(|c| c == "cite")).unwrap_or(false)
+}
+
+struct Citations {
+	entries: BTreeMap<String, String>,
+	cited: Vec<String>,
+}
+
+impl Citations {
+	fn load(bibliography: &Path) -> Result<Self, String> {
+		let entries = serde_yaml::from_str(&fs::read_to_string(bibliography).map_err(|e| format!("failed to read bibliography {}: {e}", bibliography.display()))?)
+			.map_err(|e| format!("failed to parse bibliography {}: {e}", bibliography.display()))?;
+
+		Ok(Citations { entries, cited: vec![] })
+	}
+
+	fn cite(&mut self, key: &str) -> Result<String, String> {
+		if !self.entries.contains_key(key) {
+			return Err(format!("unknown citation key {key:?}"));
+		}
+
+		let n = match self.cited.iter().position(|k| k == key) {
+			Some(i) => i + 1,
+			None => {
+				self.cited.push(key.to_string());
+				self.cited.len()
+			},
+		};
+
+		Ok(format!(r##"<a href="#ref-{}" role="doc-biblioref">[{n}]</a>"##, escape_attr(key)))
+	}
+
+	fn bibliography(&self) -> Result<Option<String>, String> {
+		if self.cited.is_empty() {
+			return Ok(None);
+		}
+
+		let mut html = String::from("<section role=\"doc-bibliography\">\n<ol>\n");
+
+		for key in &self.cited {
+			let entry = render_events(jotdown::Parser::new(&self.entries[key]).collect())?;
+			let entry = entry.trim().strip_prefix("<p>").and_then(|e| e.strip_suffix("</p>")).unwrap_or(entry.trim());
+
+			html.push_str(&format!("<li id=\"ref-{}\">{entry}</li>\n", escape_attr(key)));
+		}
+
+		html.push_str("</ol>\n</section>\n");
+
+		Ok(Some(html))
+	}
+}
+
+// Takes over footnote rendering from jotdown (whose endnotes are fixed in form and position) when
+// `placement` or `backlink` are given, and resolves `[key]{.cite}` spans against `bibliography`.
+pub(super) fn process<'s>(events: Vec<Event<'s>>, placement: Option<FootnotePlacement>, backlink: Option<&str>, bibliography: Option<&Path>) -> Result<Vec<Event<'s>>, String> {
+	let mut footnotes = (placement.is_some() || backlink.is_some()).then(|| Footnotes {
+		placement: placement.unwrap_or(FootnotePlacement::Article),
+		backlink: backlink.unwrap_or("↩︎").to_string(),
+		definitions: HashMap::new(),
+		numbers: HashMap::new(),
+		pending: vec![],
+	});
+	let mut citations = bibliography.map(Citations::load).transpose()?;
+
+	let mut main = vec![];
+	let mut events = events.into_iter();
+
+	while let Some(event) = events.next() {
+		match (&mut footnotes, event) {
+			(Some(footnotes), Event::Start(Container::Footnote { label }, _)) => {
+				let mut depth = 1;
+				let definition = events.by_ref().take_while(|e| {
+					match e {
+						Event::Start(..) => depth += 1,
+						Event::End(..) => depth -= 1,
+						_ => (),
+					}
+					depth > 0
+				}).collect();
+
+				footnotes.definitions.insert(label, definition);
+			},
+			(_, event) => main.push(event),
+		}
+	}
+
+	let mut result = vec![];
+	let mut events = main.into_iter();
+
+	while let Some(event) = events.next() {
+		match event {
+			Event::FootnoteReference(label) if footnotes.is_some() => {
+				result.extend(raw_inline(footnotes.as_mut().unwrap().reference(label)));
+			},
+			Event::Start(Container::Span, attrs) if citations.is_some() && is_citation(&attrs) => {
+				let mut key = String::new();
+
+				for e in events.by_ref() {
+					match e {
+						Event::End(Container::Span) => break,
+						Event::Str(s) => key.push_str(&s),
+						_ => (),
+					}
+				}
+
+				result.extend(raw_inline(citations.as_mut().unwrap().cite(key.trim())?));
+			},
+			Event::End(Container::Section { id }) => {
+				if let Some(footnotes) = footnotes.as_mut().filter(|f| f.placement == FootnotePlacement::Section) {
+					if let Some(html) = footnotes.flush()? {
+						result.extend(raw_block(html));
+					}
+				}
+
+				result.push(Event::End(Container::Section { id }));
+			},
+			e => result.push(e),
+		}
+	}
+
+	if let Some(html) = footnotes.as_mut().map(Footnotes::flush).transpose()?.flatten() {
+		result.extend(raw_block(html));
+	}
+
+	if let Some(html) = citations.as_ref().map(Citations::bibliography).transpose()?.flatten() {
+		result.extend(raw_block(html));
+	}
+
+	Ok(result)
+}
@@ -0,0 +1,146 @@
+use crate::{sitemap::xml_escape, RenderedResource};
+
+/// An Atom feed of the dated pages (those with a `date:` in their frontmatter), newest first.
+#[derive(Clone, Debug)]
+pub struct Feed {
+	pub(crate) route: String,
+	title: String,
+	pub(crate) base_url: String,
+	section: Option<String>,
+	tag: Option<String>,
+	limit: Option<usize>,
+	full_content: bool,
+}
+
+impl Feed {
+	pub fn new<R, T, U>(route: R, title: T, base_url: U) -> Self where R: Into<String>, T: Into<String>, U: Into<String> {
+		Feed {
+			route: route.into(),
+			title: title.into(),
+			base_url: base_url.into().trim_end_matches('/').to_string(),
+			section: None,
+			tag: None,
+			limit: None,
+			full_content: false,
+		}
+	}
+
+	/// Only pages served under this URL prefix, e.g. `/blog/`
+	pub fn section<S>(mut self, prefix: S) -> Self where S: Into<String> {
+		self.section = Some(prefix.into());
+		self
+	}
+
+	/// Only pages listing this tag in their frontmatter `tags:`
+	pub fn tag<S>(mut self, tag: S) -> Self where S: Into<String> {
+		self.tag = Some(tag.into());
+		self
+	}
+
+	/// At most this many (of the newest) entries
+	pub fn limit(mut self, limit: usize) -> Self {
+		self.limit = Some(limit);
+		self
+	}
+
+	/// Entries carry the whole page content, instead of a summary: the frontmatter `summary:`, or
+	/// failing that the first paragraph
+	pub fn full_content(mut self, full_content: bool) -> Self {
+		self.full_content = full_content;
+		self
+	}
+
+	// `mount_prefix` is already on the pages' paths, but not on the section
+	fn includes(&self, page: &RenderedResource, mount_prefix: &str) -> bool {
+		let has_tag = |tag: &str| page.metadata.as_ref()
+			.and_then(|m| m.get("tags"))
+			.and_then(|t| t.as_sequence())
+			.is_some_and(|tags| tags.iter().any(|t| t.as_str() == Some(tag)));
+
+		page.content_type.starts_with("text/html")
+			&& self.section.as_ref().is_none_or(|s| page.paths[0].starts_with(&format!("{mount_prefix}{s}")))
+			&& self.tag.as_ref().is_none_or(|t| has_tag(t))
+	}
+
+	pub(crate) fn render<'a, I>(&self, pages: I, mount_prefix: &str) -> Result<Vec<u8>, String> where I: IntoIterator<Item = &'a RenderedResource> {
+		let mut entries = vec![];
+
+		for page in pages.into_iter().filter(|p| self.includes(p, mount_prefix)) {
+			let Some(date) = page.metadata.as_ref().and_then(|m| m.get("date")) else { continue };
+			let date = date.as_str().ok_or_else(|| format!("{}: date must be a YYYY-MM-DD or RFC 3339 string", page.source.display()))?;
+			let updated = if date.len() == 10 { format!("{date}T00:00:00Z") } else { date.to_string() };
+
+			entries.push((updated, page));
+		}
+
+		entries.sort_by(|a, b| b.0.cmp(&a.0));
+		entries.truncate(self.limit.unwrap_or(usize::MAX));
+
+		let feed_url = format!("{}{mount_prefix}{}", self.base_url, self.route);
+		let updated = entries.first().map(|(u, _)| u.as_str()).unwrap_or("1970-01-01T00:00:00Z");
+
+		let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+		xml.push_str(&format!("\t<id>{}</id>\n\t<title>{}</title>\n\t<updated>{updated}</updated>\n", xml_escape(&feed_url), xml_escape(&self.title)));
+		xml.push_str(&format!("\t<link rel=\"self\" href=\"{}\"/>\n", xml_escape(&feed_url)));
+
+		for (updated, page) in entries {
+			let url = format!("{}{}", self.base_url, page.paths[0]);
+			let title = page.title().unwrap_or(&page.paths[0]);
+			let content = page.content.as_deref().unwrap_or_default();
+
+			xml.push_str(&format!("\t<entry>\n\t\t<id>{0}</id>\n\t\t<title>{1}</title>\n\t\t<updated>{updated}</updated>\n\t\t<link rel=\"alternate\" href=\"{0}\"/>\n", xml_escape(&url), xml_escape(title)));
+
+			if self.full_content {
+				xml.push_str(&format!("\t\t<content type=\"html\">{}</content>\n", xml_escape(&rebase(content, &self.base_url, &url))));
+			} else if let Some(summary) = page.metadata.as_ref().and_then(|m| m.get("summary")).and_then(|s| s.as_str()) {
+				xml.push_str(&format!("\t\t<summary>{}</summary>\n", xml_escape(summary)));
+			} else if let Some(paragraph) = first_paragraph(content) {
+				xml.push_str(&format!("\t\t<summary type=\"html\">{}</summary>\n", xml_escape(&rebase(paragraph, &self.base_url, &url))));
+			}
+
+			xml.push_str("\t</entry>\n");
+		}
+
+		xml.push_str("</feed>\n");
+
+		Ok(xml.into_bytes())
+	}
+}
+
+pub(crate) fn first_paragraph(html: &str) -> Option<&str> {
+	let start = html.find("<p>").or_else(|| html.find("<p "))?;
+	let end = html[start..].find("</p>")? + start + "</p>".len();
+
+	Some(&html[start..end])
+}
+
+// Feed readers don't show entries at the page's URL, so every link and image in them needs to be absolute
+fn rebase(html: &str, base_url: &str, page_url: &str) -> String {
+	let page_dir = &page_url[..page_url.rfind('/').map(|i| i + 1).unwrap_or(page_url.len())];
+	let mut rebased = String::new();
+	let mut rest = html;
+
+	while let Some(i) = [" href=\"", " src=\""].iter().filter_map(|attr| rest.find(attr).map(|i| i + attr.len())).min() {
+		rebased.push_str(&rest[..i]);
+		rest = &rest[i..];
+
+		let end = rest.find('"').unwrap_or(rest.len());
+		let url = &rest[..end];
+		let has_scheme = url.find(':').is_some_and(|colon| !url[..colon].contains('/'));
+
+		if url.starts_with("//") || has_scheme {
+			rebased.push_str(url);
+		} else if url.starts_with('/') {
+			rebased.push_str(&format!("{base_url}{url}"));
+		} else if url.starts_with('#') {
+			rebased.push_str(&format!("{page_url}{url}"));
+		} else {
+			rebased.push_str(&format!("{page_dir}{url}"));
+		}
+
+		rest = &rest[end..];
+	}
+
+	rebased.push_str(rest);
+	rebased
+}
@@ -0,0 +1,84 @@
+use std::time::SystemTime;
+
+use crate::RenderedResource;
+
+// Days since the Unix epoch of a proleptic Gregorian date (Howard Hinnant's `days_from_civil`)
+pub(crate) fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+	let year = if month <= 2 { year - 1 } else { year };
+	let era = year.div_euclid(400);
+	let year_of_era = year - era * 400;
+	let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+	let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+	era * 146097 + day_of_era - 719468
+}
+
+// The day of a `YYYY-MM-DD` (or RFC 3339) date
+fn parse_day(date: &str) -> Option<i64> {
+	let mut parts = date.get(..10)?.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+	let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+
+	((1..=12).contains(&month) && (1..=31).contains(&day)).then(|| days_from_civil(year, month, day))
+}
+
+/// Seconds since the Unix epoch of an RFC 3339 time (`2026-12-24T18:00:00+01:00`, with or without
+/// the seconds), or of midnight UTC at the start of a `YYYY-MM-DD` date
+pub(crate) fn parse_time(time: &str) -> Option<u64> {
+	let day = parse_day(time)?;
+	let rest = &time[10..];
+	let seconds = match rest.strip_prefix(['T', 't', ' ']) {
+		None if rest.is_empty() => 0,
+		None => return None,
+		Some(clock) => {
+			let (clock, offset) = match clock.find(['Z', 'z', '+', '-']) {
+				Some(i) => clock.split_at(i),
+				None => (clock, ""),
+			};
+			let mut parts = clock.splitn(3, ':').map(|p| p.split('.').next().unwrap_or(p).parse::<i64>().ok());
+			let (hours, minutes, seconds) = (parts.next()??, parts.next()??, parts.next().unwrap_or(Some(0))?);
+			let offset = match offset {
+				"" | "Z" | "z" => 0,
+				_ => {
+					let (sign, offset) = offset.split_at(1);
+					let (h, m) = offset.split_once(':')?;
+					(h.parse::<i64>().ok()? * 3600 + m.parse::<i64>().ok()? * 60) * if sign == "-" { -1 } else { 1 }
+				},
+			};
+			if hours > 23 || minutes > 59 || seconds > 60 {
+				return None;
+			}
+			hours * 3600 + minutes * 60 + seconds - offset
+		},
+	};
+
+	u64::try_from(day * 86400 + seconds).ok()
+}
+
+fn today() -> i64 {
+	SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|d| (d.as_secs() / 86400) as i64).unwrap_or_default()
+}
+
+/// Warns about every HTML page under one of `thresholds`' path prefixes whose frontmatter
+/// `updated:` (or failing that `date:`) is more days ago than the longest matching prefix allows
+pub(crate) fn check<'a, I>(thresholds: &[(String, u32)], pages: I, mount_prefix: &str) where I: IntoIterator<Item = &'a RenderedResource> {
+	let today = today();
+
+	for page in pages.into_iter().filter(|p| p.content_type.starts_with("text/html")) {
+		let Some(max_age) = thresholds.iter()
+			.filter(|(prefix, _)| page.paths[0].starts_with(&format!("{mount_prefix}{prefix}")))
+			.max_by_key(|(prefix, _)| prefix.len())
+			.map(|(_, days)| i64::from(*days))
+		else { continue };
+
+		let frontmatter = page.metadata.as_ref();
+		let Some(date) = frontmatter.and_then(|m| m.get("updated").or_else(|| m.get("date"))).and_then(|d| d.as_str()) else { continue };
+
+		match parse_day(date) {
+			Some(day) if today - day > max_age => {
+				println!("cargo::warning={}: last updated {date}, {} days ago (more than {max_age}); time to review it?", page.source.display(), today - day);
+			},
+			Some(_) => (),
+			None => println!("cargo::warning={}: can't read date {date:?}", page.source.display()),
+		}
+	}
+}
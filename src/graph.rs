@@ -0,0 +1,112 @@
+use serde_json::json;
+use std::{collections::{BTreeSet, HashMap}, fs, path::Path};
+
+use crate::{anchors::{attr_values, resolve}, RenderedResource, Resource};
+
+// The targets of every `url(...)` in a stylesheet
+fn css_urls(css: &str) -> Vec<&str> {
+	css.split("url(").skip(1).filter_map(|rest| rest.split_once(')')).map(|(url, _)| url.trim().trim_matches(['"', '\''])).collect()
+}
+
+// Every path `page` refers to: links, embeds and stylesheet `url()`s
+fn references(page: &RenderedResource) -> Vec<String> {
+	let text = String::from_utf8_lossy(&page.body);
+	let urls = if page.content_type.starts_with("text/html") {
+		let candidates = attr_values(&text, "srcset").into_iter().flat_map(|set| set.split(',').filter_map(|c| c.split_whitespace().next()));
+		["href", "src", "poster", "data"].into_iter().flat_map(|attr| attr_values(&text, attr)).chain(candidates).chain(css_urls(&text)).collect()
+	} else if page.content_type.starts_with("text/css") {
+		css_urls(&text)
+	} else {
+		vec![]
+	};
+
+	urls.into_iter().filter_map(|url| resolve(&page.paths[0], url)).collect()
+}
+
+/// Which rendered resources refer to which, by index into the rendered list: pages link to pages,
+/// and use everything else
+pub(crate) struct Graph {
+	edges: Vec<BTreeSet<usize>>,
+}
+
+impl Graph {
+	pub(crate) fn new(rendered: &[(Resource, RenderedResource)]) -> Self {
+		let mut by_path: HashMap<&str, usize> = HashMap::new();
+		for (i, (_, page)) in rendered.iter().enumerate() {
+			for path in page.paths.iter().chain(&page.redirects) {
+				by_path.entry(path).or_insert(i);
+			}
+		}
+
+		// Less fragment links within a page, which don't lead anywhere else
+		let edges = rendered.iter().enumerate()
+			.map(|(i, (_, page))| references(page).iter().filter_map(|target| by_path.get(target.as_str()).copied()).filter(|j| *j != i).collect())
+			.collect();
+
+		Graph { edges }
+	}
+
+	/// Everything reachable from `roots`, themselves included
+	pub(crate) fn reached<I>(&self, roots: I) -> BTreeSet<usize> where I: IntoIterator<Item = usize> {
+		let mut reached: BTreeSet<usize> = roots.into_iter().collect();
+		let mut queue: Vec<usize> = reached.iter().copied().collect();
+
+		while let Some(i) = queue.pop() {
+			for j in &self.edges[i] {
+				if reached.insert(*j) {
+					queue.push(*j);
+				}
+			}
+		}
+
+		reached
+	}
+}
+
+fn is_page(page: &RenderedResource) -> bool {
+	page.content_type.starts_with("text/html")
+}
+
+fn json(graph: &Graph, rendered: &[(Resource, RenderedResource)]) -> String {
+	let nodes: Vec<_> = rendered.iter().map(|(r, page)| json!({
+		"path": page.paths[0],
+		"source": (!r.is_virtual()).then(|| page.source.display().to_string()),
+		"content_type": page.content_type,
+	})).collect();
+	let edges: Vec<_> = graph.edges.iter().enumerate().flat_map(|(i, targets)| targets.iter().map(move |j| (i, *j))).map(|(i, j)| json!({
+		"from": rendered[i].1.paths[0],
+		"to": rendered[j].1.paths[0],
+		"kind": if is_page(&rendered[j].1) { "links" } else { "uses" },
+	})).collect();
+
+	serde_json::to_string_pretty(&json!({ "nodes": nodes, "edges": edges })).unwrap()
+}
+
+fn dot(graph: &Graph, rendered: &[(Resource, RenderedResource)]) -> String {
+	let id = |i: usize| format!("\"{}\"", rendered[i].1.paths[0].replace('\\', "\\\\").replace('"', "\\\""));
+	let mut dot = String::from("digraph references {\n");
+
+	for (i, (_, page)) in rendered.iter().enumerate() {
+		dot.push_str(&format!("\t{}{};\n", id(i), if is_page(page) { "" } else { " [shape=box]" }));
+	}
+	for (i, targets) in graph.edges.iter().enumerate() {
+		for j in targets {
+			dot.push_str(&format!("\t{} -> {}{};\n", id(i), id(*j), if is_page(&rendered[*j].1) { "" } else { " [style=dashed]" }));
+		}
+	}
+	dot.push_str("}\n");
+
+	dot
+}
+
+/// Writes the graph of `rendered` to `file`, as Graphviz DOT if it's a `.dot` (or `.gv`) file and
+/// JSON otherwise
+pub(crate) fn write(file: &Path, rendered: &[(Resource, RenderedResource)]) -> Result<(), String> {
+	let graph = Graph::new(rendered);
+	let out = match file.extension().is_some_and(|ext| ext == "dot" || ext == "gv") {
+		true => dot(&graph, rendered),
+		false => json(&graph, rendered),
+	};
+
+	fs::write(file, out).map_err(|e| format!("failed to write {}: {e}", file.display()))
+}
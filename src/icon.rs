@@ -0,0 +1,58 @@
+use std::path::{Path, PathBuf};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// How long browsers may keep a generated `/favicon.ico` without asking again: a month, as it
+/// isn't content-addressed, but very rarely changes
+pub(crate) const CACHE_CONTROL: &str = "public, max-age=2592000";
+
+// A PNG's width and height, from its header
+fn png_size(source: &Path, png: &[u8]) -> Result<(u32, u32), String> {
+	if png.len() < 24 || !png.starts_with(PNG_SIGNATURE) || &png[12..16] != b"IHDR" {
+		return Err(format!("{} isn't a PNG", source.display()));
+	}
+	let dimension = |at: usize| u32::from_be_bytes([png[at], png[at + 1], png[at + 2], png[at + 3]]);
+
+	Ok((dimension(16), dimension(20)))
+}
+
+/// An ICO holding each of `pngs` (one per size, up to 256 pixels square) as is, which every browser
+/// since IE 7 reads, smallest first
+pub(crate) fn ico(pngs: &[(PathBuf, Vec<u8>)]) -> Result<Vec<u8>, String> {
+	let mut images = vec![];
+
+	for (source, png) in pngs {
+		let (width, height) = png_size(source, png)?;
+
+		if width == 0 || height == 0 || width > 256 || height > 256 {
+			return Err(format!("{} is {width}x{height}, but icon images can be at most 256x256", source.display()));
+		}
+		if images.iter().any(|(w, h, _)| (*w, *h) == (width, height)) {
+			return Err(format!("{} is {width}x{height}, like another of the icon's images", source.display()));
+		}
+		images.push((width, height, png));
+	}
+	if images.is_empty() {
+		return Err("an icon needs at least one image".to_string());
+	}
+	images.sort_by_key(|(width, height, _)| (*width, *height));
+
+	let count = images.len() as u16;
+	let mut ico = [0u16, 1, count].iter().flat_map(|n| n.to_le_bytes()).collect::<Vec<u8>>();
+	let mut offset = 6 + 16 * images.len() as u32;
+
+	for (width, height, png) in &images {
+		// A dimension of 256 is written as 0
+		ico.extend([*width as u8, *height as u8, 0, 0]);
+		ico.extend(1u16.to_le_bytes());
+		ico.extend(32u16.to_le_bytes());
+		ico.extend((png.len() as u32).to_le_bytes());
+		ico.extend(offset.to_le_bytes());
+		offset += png.len() as u32;
+	}
+	for (_, _, png) in images {
+		ico.extend_from_slice(png);
+	}
+
+	Ok(ico)
+}
@@ -0,0 +1,87 @@
+use globset::GlobBuilder;
+use std::path::Path;
+
+use crate::{command, RenderedResource, Resource};
+
+/// What `Builder::image_variants` can also serve raster images as
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+	Webp,
+	Avif,
+}
+
+impl ImageFormat {
+	pub fn media_type(self) -> &'static str {
+		match self {
+			ImageFormat::Webp => "image/webp",
+			ImageFormat::Avif => "image/avif",
+		}
+	}
+}
+
+pub(crate) type ImageEncoder = Box<dyn FnMut(&Path, &[u8]) -> Result<Vec<u8>, String>>;
+
+// Adds `format` to the formats of every raster image whose source (relative to `base_path`)
+// matches `glob`, where it's smaller than the image and what it has already
+pub(crate) fn add(rendered: &mut [(Resource, RenderedResource)], base_path: &Path, glob: &str, format: ImageFormat, encode: &mut ImageEncoder) -> Result<(), String> {
+	let matcher = GlobBuilder::new(glob).literal_separator(true).build().map_err(|e| format!("invalid glob {glob:?}: {e}"))?.compile_matcher();
+	let media_type = format.media_type();
+	let mut matched = false;
+
+	for (r, image) in rendered.iter_mut().filter(|(r, image)| !r.is_virtual() && image.content_type.starts_with("image/") && image.content_type != "image/svg+xml") {
+		if image.content_type == media_type || !r.source.strip_prefix(base_path).is_ok_and(|source| matcher.is_match(source)) {
+			continue;
+		}
+		matched = true;
+		// Encoded here whether or not the image itself is embedded
+		println!("cargo::rerun-if-changed={}", r.source.display());
+
+		let body = encode(&r.source, &image.body).map_err(|e| format!("failed to encode {} as {media_type}: {e}", r.source.display()))?;
+		if body.len() < image.body.len() {
+			image.formats.retain(|(other, _)| other != media_type);
+			image.formats.push((media_type.to_string(), body));
+			image.formats.sort_by_key(|(_, body)| body.len());
+		}
+	}
+
+	if !matched {
+		println!("cargo::warning=static-builder: no image matches {media_type} glob {glob:?}");
+	}
+
+	Ok(())
+}
+
+/// An encoder for `Builder::image_variants` that runs `program` with `args` for each image,
+/// writing the image to its standard input and reading the new one from its standard output, with
+/// `{source}` in an argument standing for the image's path, e.g.
+/// `image_command("cwebp", ["-quiet", "{source}", "-o", "-"])`
+pub fn image_command<P, I, S>(program: P, args: I) -> impl FnMut(&Path, &[u8]) -> Result<Vec<u8>, String> where P: Into<String>, I: IntoIterator<Item = S>, S: Into<String> {
+	let command = (program.into(), args.into_iter().map(Into::into).collect());
+
+	move |source, image| command::run(&command, source, image.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{add, ImageEncoder};
+	use crate::{ImageFormat, RenderedResource, Resource};
+	use std::{collections::BTreeMap, path::{Path, PathBuf}};
+
+	fn rendered(source: &str) -> (Resource, RenderedResource) {
+		let r = Resource::new(PathBuf::from("content").join(source), PathBuf::from(source));
+		let page = RenderedResource { source: r.source.clone(), paths: vec![format!("/{source}")], redirects: vec![], content_type: r.content_type().to_string(), body: vec![0; 64], metadata: None, fields: BTreeMap::new(), content: None, formats: vec![] };
+
+		(r, page)
+	}
+
+	#[test]
+	fn jpegs_get_variants_and_svgs_dont() {
+		let mut rendered = [rendered("photo.jpg"), rendered("logo.svg"), rendered("anim.gif")];
+		let mut encode: ImageEncoder = Box::new(|_, image| Ok(image[..image.len() / 2].to_vec()));
+
+		add(&mut rendered, Path::new("content"), "*", ImageFormat::Webp, &mut encode).unwrap();
+
+		let formats: Vec<_> = rendered.iter().map(|(_, page)| (page.content_type.as_str(), page.formats.iter().map(|(format, body)| (format.as_str(), body.len())).collect::<Vec<_>>())).collect();
+		assert_eq!(formats, [("image/jpeg", vec![("image/webp", 32)]), ("image/svg+xml", vec![]), ("image/gif", vec![("image/webp", 32)])]);
+	}
+}
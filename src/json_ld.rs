@@ -0,0 +1,90 @@
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::{with_head_tag, RenderedResource, Resource};
+
+fn article(page: &RenderedResource, url: &str) -> Option<Value> {
+	let frontmatter = page.metadata.as_ref()?;
+	let setting = |key| frontmatter.get(key).and_then(|v| v.as_str());
+	let published = setting("date")?;
+
+	let mut article = json!({ "@type": "Article", "url": url, "datePublished": published });
+	if let Some(title) = page.title() {
+		article["headline"] = title.into();
+	}
+	if let Some(modified) = setting("updated") {
+		article["dateModified"] = modified.into();
+	}
+	if let Some(description) = setting("description") {
+		article["description"] = description.into();
+	}
+	if let Some(author) = setting("author") {
+		article["author"] = json!({ "@type": "Person", "name": author });
+	}
+
+	Some(article)
+}
+
+// The site's root, each directory from there down to `path`, and `path` itself, by title where a
+// page there has one
+fn breadcrumbs(path: &str, site_name: &str, mount_prefix: &str, base_url: &str, titles: &HashMap<String, String>) -> Value {
+	let relative = path.strip_prefix(mount_prefix).unwrap_or(path);
+	let name = |path: &str, relative: &str| titles.get(path).cloned().unwrap_or_else(|| relative.trim_end_matches('/').rsplit('/').next().unwrap_or_default().to_string());
+	let mut crumbs = vec![(format!("{mount_prefix}/"), site_name.to_string())];
+	let mut end = 0;
+
+	while let Some(i) = relative[end + 1..].find('/') {
+		end += i + 1;
+		let dir = format!("{mount_prefix}{}", &relative[..=end]);
+		let name = name(&dir, &relative[..=end]);
+		crumbs.push((dir, name));
+	}
+
+	if !relative.ends_with('/') {
+		crumbs.push((path.to_string(), name(path, relative)));
+	}
+
+	let items: Vec<_> = crumbs.into_iter().enumerate().map(|(i, (path, name))| json!({
+		"@type": "ListItem",
+		"position": i + 1,
+		"name": name,
+		"item": format!("{base_url}{path}"),
+	})).collect();
+
+	json!({ "@type": "BreadcrumbList", "itemListElement": items })
+}
+
+/// Adds a schema.org JSON-LD script to each HTML page that doesn't have one already: `WebSite` on
+/// the site's root, `BreadcrumbList` everywhere else, and `Article` on pages whose frontmatter has a
+/// `date:` (with its `title:`, `updated:`, `description:` and `author:`)
+pub(crate) fn inject(rendered: &mut [(Resource, RenderedResource)], site_name: &str, base_url: &str, mount_prefix: &str) {
+	let base_url = base_url.trim_end_matches('/');
+	let root = format!("{mount_prefix}/");
+
+	let titles: HashMap<String, String> = rendered.iter()
+		.filter_map(|(_, page)| Some((page, page.title()?)))
+		.flat_map(|(page, title)| page.paths.iter().map(move |path| (path.clone(), title.to_string())))
+		.collect();
+
+	for (_, page) in rendered.iter_mut().filter(|(_, page)| page.content_type.starts_with("text/html")) {
+		let Ok(html) = std::str::from_utf8(&page.body) else { continue };
+		if html.contains("application/ld+json") {
+			continue;
+		}
+
+		let path = &page.paths[0];
+		let url = format!("{base_url}{path}");
+		let mut graph = vec![];
+
+		if page.paths.contains(&root) {
+			graph.push(json!({ "@type": "WebSite", "name": site_name, "url": format!("{base_url}{root}") }));
+		} else {
+			graph.push(breadcrumbs(path, site_name, mount_prefix, base_url, &titles));
+		}
+		graph.extend(article(page, &url));
+
+		// Nothing in the JSON can end the script early
+		let json = json!({ "@context": "https://schema.org", "@graph": graph }).to_string().replace("</", "<\\/");
+		page.body = with_head_tag(html, &format!(r#"<script type="application/ld+json">{json}</script>"#)).into_bytes();
+	}
+}
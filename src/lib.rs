@@ -1,65 +1,528 @@
-use jotdown::Render;
-use proc_macro2::TokenStream;
-use quote::quote;
+mod allowlist;
+mod anchors;
+#[cfg(feature = "asciidoc")]
+mod asciidoc;
+mod assets;
+mod axum;
+mod builder;
+mod codegen;
+mod command;
+mod compress;
+mod config;
+mod dates;
+mod djot;
+mod feed;
+mod freshness;
+mod graph;
+mod icon;
+mod images;
+mod json_ld;
+mod manifest;
+mod map;
+mod markdown;
+mod minify;
+mod old_urls;
+mod orphans;
+mod page_index;
+mod pdf;
+mod poem;
+mod portable;
+mod profile;
+mod renderer;
+mod rocket;
+#[cfg(feature = "sass")]
+mod sass;
+mod search;
+mod site;
+pub mod serve;
+mod sitemap;
+mod spellcheck;
+mod tidy;
+mod warp;
+mod watch;
+
+pub use allowlist::Allowlist;
+pub use builder::{Backend, Builder, Progress, RootIndex};
+pub use compress::Encoding;
+pub use config::{Rule, Stage, Store};
+pub use djot::{DjotOptions, FootnotePlacement, RawHtml, Sanitize};
+pub use feed::Feed;
+pub use images::{image_command, ImageFormat};
+pub use pdf::pdf_command;
+pub use profile::{build_profile, build_target};
+pub use renderer::ContentRenderer;
+pub use watch::watch_for_rebuild;
+#[cfg(feature = "watch")]
+pub use watch::{watch, RouteChange};
+
+use assets::AssetUrls;
+use config::{Rules, DIRECTORY_CONFIG};
+use djot::render_djot;
 use serde::Deserialize;
-use std::{env, fs::{self, File}, io, io::Write as _, path::{Path, PathBuf}};
+use std::{collections::{BTreeMap, HashMap}, env, fs::{self, File}, io, path::{Path, PathBuf}, sync::{Arc, LazyLock, Mutex, PoisonError}, time::SystemTime};
 use tera::Tera;
 use walkdir::{DirEntry, WalkDir};
 use yaml_front_matter::{Document, YamlFrontMatter};
 
-#[derive(Deserialize)]
-struct DjotMetadata {
+// Which of a directory index's routes (`/dir/index.html`, `/dir/`, `/dir`) is the real one
+#[derive(Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Canonical {
+	Index,
+	Directory,
+	Bare,
+}
+
+#[derive(Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Aliases {
+	#[default]
+	Serve,
+	Redirect,
+}
+
+#[derive(Default, Deserialize)]
+struct PageMetadata {
 	title: Option<String>,
 	layout: Option<String>,
+	#[serde(default)]
+	blocks: BTreeMap<String, String>,
+	canonical: Option<Canonical>,
+	#[serde(default)]
+	aliases: Aliases,
+	#[serde(default)]
+	noindex: bool,
+	// Paths, such as an old site's, that redirect here
+	#[serde(default)]
+	redirect_from: Vec<String>,
+}
+
+struct Page {
+	metadata: PageMetadata,
+	frontmatter: Option<serde_yaml::Value>,
+	fields: BTreeMap<String, String>,
+	content: String,
+	// The rendered page before any layout is applied
+	fragment: Option<String>,
+}
+
+impl Page {
+	fn parse(input: &str) -> Result<Self, String> {
+		if !input.starts_with("---") {
+			return Ok(Page { metadata: PageMetadata::default(), frontmatter: None, fields: BTreeMap::new(), content: input.to_string(), fragment: None });
+		}
+
+		let lines: Vec<_> = input.lines().skip(1).collect();
+		let end = lines.iter().position(|l| l.trim_end() == "---");
+
+		if lines[..end.unwrap_or(lines.len())].iter().all(|l| l.trim().is_empty()) {
+			let end = end.ok_or("frontmatter has no closing ---")?;
+
+			// An empty block is just no settings, though it still marks the page as a page
+			return Ok(Page { metadata: PageMetadata::default(), frontmatter: Some(serde_yaml::Mapping::new().into()), fields: BTreeMap::new(), content: lines[end + 1..].join("\n"), fragment: None });
+		}
+
+		let doc: Document<serde_yaml::Value> = YamlFrontMatter::parse::<serde_yaml::Value>(input).map_err(|e| format!("frontmatter parsing failed: {e}"))?;
+		let metadata = serde_yaml::from_value(doc.metadata.clone()).map_err(|e| format!("frontmatter parsing failed: {e}"))?;
+
+		Ok(Page { metadata, frontmatter: Some(doc.metadata), fields: BTreeMap::new(), content: doc.content, fragment: None })
+	}
+
+	fn template(&self, body: &str, djot: &DjotOptions) -> Result<String, String> {
+		let mut result = String::new();
+
+		if let Some(layout) = &self.metadata.layout {
+			result.push_str(&format!(r#"{{% extends "{layout}.html" %}}"#));
+			result.push('\n');
+		}
+
+		if let Some(title) = &self.metadata.title {
+			result.push_str(&format!("{{% block headtitle %}}{title}{{% endblock headtitle %}}"));
+			result.push_str(&format!("{{% block pagetitle %}}{title}{{% endblock pagetitle %}}"));
+			result.push('\n');
+		}
+
+		for (name, source) in &self.metadata.blocks {
+			if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') || name == "content" {
+				return Err(format!("invalid block name {name:?}"));
+			}
+
+			result.push_str(&format!("{{% block {name} %}}\n"));
+			result.push_str(&render_djot(source, djot)?.0);
+			result.push_str(&format!("{{% endblock {name} %}}\n"));
+		}
+
+		result.push_str("{% block content %}\n");
+		result.push_str(body);
+		result.push_str("{% endblock content %}\n");
+
+		Ok(result)
+	}
+
+	fn context(&self) -> tera::Context {
+		let mut ctx = tera::Context::new();
+
+		if let Some(frontmatter) = &self.frontmatter {
+			ctx.insert("page", frontmatter);
+		}
+
+		ctx.insert("fields", &self.fields);
+
+		ctx
+	}
+}
+
+fn valid_static_file(entry: &DirEntry) -> bool {
+	!entry.file_name()
+		.to_str()
+		.map(|s| s.starts_with("."))
+		.unwrap_or(false)
+}
+
+// Layouts are named relative to `layouts/`, so pages say `extends "default.html"`; partials keep
+// their directory prefix (`include "partials/nav.html"`) so they stay out of the layout namespace
+fn load_templater(layouts: &Path, partials: &Path) -> Result<Tera, String> {
+	let mut files = vec![];
+
+	for (dir, prefix, html_only) in [(layouts, "", true), (partials, "partials/", false)] {
+		if !dir.is_dir() {
+			continue;
+		}
+
+		for entry in WalkDir::new(dir).into_iter().filter_entry(valid_static_file) {
+			let entry = entry.map_err(|e| format!("failed to scan {}: {e}", dir.display()))?;
+
+			if !entry.file_type().is_file() || (html_only && entry.path().extension().map(|v| v != "html").unwrap_or(true)) {
+				continue;
+			}
+
+			let name = format!("{prefix}{}", entry.path().strip_prefix(dir).unwrap().to_str().unwrap());
+			files.push((entry.path().to_path_buf(), Some(name)));
+		}
+	}
+
+	let mut templater = Tera::default();
+	templater.add_template_files(files).map_err(|e| format!("failed to load templates: {e}"))?;
+
+	Ok(templater)
+}
+
+// The newest modification time of anything in `dirs`, directories included so that removing a
+// file counts as a change
+fn newest_modification<'a, I>(dirs: I) -> Option<SystemTime> where I: IntoIterator<Item = &'a Path> {
+	dirs.into_iter()
+		.flat_map(|dir| WalkDir::new(dir).into_iter().filter_map(Result::ok))
+		.filter_map(|entry| entry.metadata().ok().and_then(|m| m.modified().ok()))
+		.max()
+}
+
+type TemplaterCache = Mutex<HashMap<(PathBuf, PathBuf), (Option<SystemTime>, Tera)>>;
+static TEMPLATERS: LazyLock<TemplaterCache> = LazyLock::new(Default::default);
+
+// Every page sharing a set of layouts and partials shares one parse of them, until any of them change
+fn templater(rule: &Rule) -> Result<Tera, String> {
+	let layouts = rule.layouts.as_deref().unwrap_or(Path::new("layouts"));
+	let partials = rule.partials.as_deref().unwrap_or(Path::new("partials"));
+	let key = (layouts.to_path_buf(), partials.to_path_buf());
+	let freshness = newest_modification([layouts, partials]);
+
+	if let Some((loaded_at, templater)) = TEMPLATERS.lock().unwrap_or_else(PoisonError::into_inner).get(&key) {
+		if *loaded_at == freshness && freshness.is_some() {
+			return Ok(templater.clone());
+		}
+	}
+
+	let templater = load_templater(layouts, partials)?;
+	TEMPLATERS.lock().unwrap_or_else(PoisonError::into_inner).insert(key, (freshness, templater.clone()));
+
+	Ok(templater)
 }
 
-fn render_djot(input: &str) -> Result<String, String> {
-	let doc: Document<DjotMetadata> = YamlFrontMatter::parse::<DjotMetadata>(input).map_err(|e| format!("frontmatter parsing failed: {e}"))?;
+// The rule's djot options, with any heading offset the page's layout declares
+fn page_djot_options(page: &Page, rule: &Rule) -> DjotOptions {
+	let mut djot = rule.djot.clone();
+	let layouts = rule.layouts.as_deref().unwrap_or(Path::new("layouts"));
+	let layout = page.metadata.layout.as_ref().and_then(|layout| fs::read_to_string(layouts.join(format!("{layout}.html"))).ok());
+
+	if let Some(offset) = layout.as_deref().and_then(djot::layout_heading_offset) {
+		djot = djot.heading_offset(offset);
+	}
+
+	djot
+}
 
-	let mut result = String::new();
+// `renderer` (and `source`, which it's given) are for `Stage::Custom`, and `pages` for `.tera`
+// templates
+fn render_pipeline(stages: &[Stage], input: &str, rule: &Rule, assets: Option<&Arc<AssetUrls>>, source: &Path, renderer: Option<&dyn ContentRenderer>, pages: Option<&serde_json::Value>) -> Result<(String, Page), String> {
+	let mut page = Page::parse(input)?;
+	let mut body = page.content.clone();
+	// A `.tera` template's frontmatter is only there for its context, as it has no layout
+	let mut templated = page.frontmatter.is_some() && !is_template(source);
+	let djot = page_djot_options(&page, rule);
+
+	for stage in stages {
+		match stage {
+			#[cfg(feature = "asciidoc")]
+			Stage::AsciiDoc => {
+				let (html, fields) = render_djot(&asciidoc::to_djot(&body), &djot)?;
+				body = html;
+				page.fragment = Some(body.clone());
+				page.fields.extend(fields);
+				templated = true;
+			},
+			Stage::Custom => {
+				let renderer = renderer.ok_or("no renderer is registered for its extension")?;
+				let (html, fields) = renderer.render(source, &body)?;
+				body = djot.sanitized(html);
+				page.fragment = Some(body.clone());
+				page.fields.extend(fields);
+				templated = true;
+			},
+			#[cfg(feature = "sass")]
+			Stage::Sass => body = sass::compile(source, &body)?.0,
+			Stage::Djot | Stage::Markdown => {
+				let (html, fields) = match stage {
+					Stage::Markdown => markdown::render(&body, &djot)?,
+					_ => render_djot(&body, &djot)?,
+				};
+				body = html;
+				page.fragment = Some(body.clone());
+				page.fields.extend(fields);
+				templated = true;
+			},
+			Stage::Tera => {
+				let template = if templated {
+					page.fragment = Some(body.clone());
+					page.template(&body, &djot)?
+				} else {
+					body
+				};
+				let mut templater = templater(rule)?;
+				assets::register(&mut templater, assets.cloned());
+				dates::register(&mut templater);
+
+				let mut context = page.context();
+				if let Some(lang) = &rule.lang {
+					context.insert("lang", lang);
+				}
+				if let Some(site) = &rule.site {
+					context.insert("site", site);
+				}
+				if let Some(pages) = pages {
+					context.insert("pages", pages);
+				}
 
-	if let Some(layout) = doc.metadata.layout {
-		result.push_str(&format!(r#"{{% extends "{layout}.html" %}}"#));
-		result.push_str("\n");
+				body = templater.render_str(&template, &context).map_err(|e| format!("template rendering failed: {e}"))?;
+				templated = false;
+			},
+		}
 	}
 
-	if let Some(title) = doc.metadata.title {
-		result.push_str(&format!("{{% block headtitle %}}{title}{{% endblock headtitle %}}"));
-		result.push_str(&format!("{{% block pagetitle %}}{title}{{% endblock pagetitle %}}"));
-		result.push_str("\n");
+	Ok((body, page))
+}
+
+// A source rendered as a template of whatever it's served as, e.g. `feed.xml.tera`
+fn is_template(source: &Path) -> bool {
+	source.extension().is_some_and(|ext| ext == "tera")
+}
+
+// Adds `tag` to the end of an HTML page's `<head>`, or the start of a page without one
+fn with_head_tag(html: &str, tag: &str) -> String {
+	match html.find("</head>") {
+		Some(i) => format!("{}{tag}\n{}", &html[..i], &html[i..]),
+		None => format!("{tag}\n{html}"),
 	}
+}
 
-	result.push_str("{% block content %}\n");
-	let events = jotdown::Parser::new(&doc.content);
-	jotdown::html::Renderer::default().push(events, &mut result).map_err(|e| format!("djot rendering failed: {e}"))?;
-	result.push_str("{% endblock content %}\n");
+// Gives an HTML page's `<html>` tag a `lang`, unless the layout already did
+fn with_lang(html: &str, lang: &str) -> String {
+	let lower = html.to_ascii_lowercase();
+	let Some(i) = lower.find("<html").filter(|i| lower[i + 5..].starts_with(['>', ' ', '\t', '\n', '\r'])) else { return html.to_string() };
+	let tag_end = lower[i..].find('>').map(|e| i + e).unwrap_or(lower.len());
 
-	Ok(result)
+	if lower[i..tag_end].contains(" lang=") {
+		return html.to_string();
+	}
+
+	format!("{} lang=\"{}\"{}", &html[..i + 5], lang.replace('"', "&quot;"), &html[i + 5..])
 }
 
+// Dev-mode renders, by source, along with the freshness they were rendered at
+type RenderCache = Mutex<HashMap<PathBuf, (SystemTime, Vec<u8>)>>;
+static RENDER_CACHE: LazyLock<RenderCache> = LazyLock::new(Default::default);
+
+// A page (by source) a scheduled variant is served in place of, from one time until another, in
+// seconds since the Unix epoch
+pub(crate) type Window = (PathBuf, u64, u64);
+
 pub struct Resource {
 	source: PathBuf,
 	path: PathBuf,
+	rule: Rule,
+	bytes: Option<Vec<u8>>,
+	content_type: Option<String>,
+	assets: Option<Arc<AssetUrls>>,
+	// When this is served in place of other pages, if it's a scheduled variant
+	windows: Vec<Window>,
+	// The extension a `ContentRenderer` is registered for that the source has, and the renderer
+	renderer: Option<(String, Arc<dyn ContentRenderer>)>,
+	// Every HTML page on the site, for a `.tera` template (see `page_index::template_pages`)
+	pages: Option<Arc<serde_json::Value>>,
+	// The `Builder::command` whose output is served in place of the source
+	command: Option<command::ExternalCommand>,
 }
 
 impl Resource {
 	pub fn new(source: PathBuf, path: PathBuf) -> Self {
-		Resource { source, path }
+		Resource { source, path, rule: Rule::default(), bytes: None, content_type: None, assets: None, windows: vec![], renderer: None, pages: None, command: None }
+	}
+
+	/// Virtual resources take their extension (and so their default pipeline and media type) from
+	/// the served path
+	pub fn from_bytes(path: PathBuf, bytes: Vec<u8>) -> Self {
+		Resource { source: path.clone(), path, rule: Rule::default(), bytes: Some(bytes), content_type: None, assets: None, windows: vec![], renderer: None, pages: None, command: None }
+	}
+
+	pub fn with_content_type<S>(mut self, content_type: S) -> Self where S: Into<String> {
+		self.content_type = Some(content_type.into());
+		self
+	}
+
+	pub fn is_virtual(&self) -> bool {
+		self.bytes.is_some()
+	}
+
+	pub fn with_rule(mut self, rule: Rule) -> Self {
+		self.rule = rule;
+		self
+	}
+
+	pub(crate) fn with_assets(mut self, assets: Arc<AssetUrls>) -> Self {
+		self.assets = Some(assets);
+		self
+	}
+
+	pub(crate) fn with_windows(mut self, windows: Vec<Window>) -> Self {
+		self.windows = windows;
+		self
+	}
+
+	pub(crate) fn windows(&self) -> &[Window] {
+		&self.windows
+	}
+
+	// Whichever of `renderers` is for the source's extension (or, as for djot, its second-to-last)
+	pub(crate) fn with_renderers(mut self, renderers: &[(String, Arc<dyn ContentRenderer>)]) -> Self {
+		let extensions = [self.source.extension(), self.source.file_stem().and_then(|stem| Path::new(stem).extension())];
+		self.renderer = extensions.iter().flatten().find_map(|ext| renderers.iter().find(|(e, _)| ext.to_str() == Some(e.as_str()))).cloned();
+		self
+	}
+
+	// Custom renderers, external commands and the Sass compiler only run in the build script, as do
+	// templates, which need every other page rendered first, so what they render is always embedded
+	pub(crate) fn renders_at_build_time(&self) -> bool {
+		self.renderer.is_some() || self.command.is_some() || self.is_sass() || self.is_template()
+	}
+
+	pub(crate) fn with_command(mut self, command: command::ExternalCommand) -> Self {
+		self.command = Some(command);
+		self
+	}
+
+	// Whether what's served is only known once it's rendered, rather than being the source as it is
+	pub(crate) fn is_generated(&self) -> bool {
+		self.command.is_some() || self.is_sass()
+	}
+
+	pub(crate) fn is_template(&self) -> bool {
+		!self.is_raw() && is_template(&self.source)
+	}
+
+	pub(crate) fn with_pages(mut self, pages: Arc<serde_json::Value>) -> Self {
+		self.pages = Some(pages);
+		self
+	}
+
+	// A stylesheet compiled to CSS, unless its rule says how to render it
+	pub(crate) fn is_sass(&self) -> bool {
+		cfg!(feature = "sass") && !self.is_raw() && self.rule.render.is_none() && matches!(self.source.extension().and_then(|v| v.to_str()), Some("scss" | "sass"))
+	}
+
+	// Partials (`_variables.scss`) are only there to be loaded by other stylesheets
+	fn is_sass_partial(&self) -> bool {
+		self.is_sass() && self.source.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with('_'))
+	}
+
+	// The files rendering the source reads besides it, layouts and partials aside: those a Sass
+	// stylesheet loads
+	pub(crate) fn dependencies(&self) -> Result<Vec<PathBuf>, String> {
+		#[cfg(feature = "sass")]
+		if self.is_sass() && !self.is_virtual() {
+			let text = fs::read_to_string(&self.source).map_err(|e| format!("failed to read {}: {e}", self.source.display()))?;
+			return sass::compile(&self.source, &text).map(|(_, loaded)| loaded).map_err(|e| format!("{}: {e}", self.source.display()));
+		}
+
+		Ok(vec![])
+	}
+
+	pub fn rule(&self) -> &Rule {
+		&self.rule
 	}
 
 	pub fn source(&self) -> PathBuf {
 		self.source.clone()
 	}
 
+	fn is_raw(&self) -> bool {
+		matches!(self.source.file_stem().and_then(|v| Path::new(v).extension()).map(|v| v.to_str().unwrap()), Some("raw"))
+	}
+
+	// Which markup a page is written in (djot, Markdown or a custom renderer's), and how many of its
+	// trailing extensions say so: one for `page.dj`, two for `page.dj.txt`, for editors that fight
+	// unfamiliar extensions
+	fn markup_suffix(&self, path: &Path) -> Option<(Stage, usize)> {
+		let markup = |p: &Path| p.extension().and_then(|v| v.to_str()).and_then(|ext| match ext {
+			_ if self.renderer.as_ref().is_some_and(|(e, _)| e == ext) => Some(Stage::Custom),
+			_ if self.rule.is_djot_extension(ext) => Some(Stage::Djot),
+			_ if self.rule.is_markdown_extension(ext) => Some(Stage::Markdown),
+			#[cfg(feature = "asciidoc")]
+			_ if self.rule.is_asciidoc_extension(ext) => Some(Stage::AsciiDoc),
+			_ => None,
+		});
+
+		markup(path).map(|stage| (stage, 1)).or_else(|| path.file_stem().and_then(|stem| markup(Path::new(stem))).map(|stage| (stage, 2)))
+	}
+
+	fn is_djot(&self) -> bool {
+		self.markup_suffix(&self.source).is_some()
+	}
+
 	pub fn paths(&self) -> Vec<PathBuf> {
 		let mut path = self.path.clone();
 
-		if let Some("dj") = path.extension().map(|v| v.to_str().unwrap()) {
+		if self.is_raw() {
+			let ext = path.extension().unwrap().to_os_string();
+			path.set_extension("");
+			path.set_extension(ext);
+		}
+
+		if let Some((_, n)) = self.markup_suffix(&path) {
+			if n == 2 {
+				path.set_extension("");
+			}
 			path.set_extension("html");
 		}
 
+		if self.is_sass() {
+			path.set_extension("css");
+		}
+
+		if self.is_template() {
+			path.set_extension("");
+		}
+
 		if let Some("index.html") = path.file_name().map(|v| v.to_str().unwrap()) {
-			if path == PathBuf::from("/index.html") {
+			if path == Path::new("/index.html") {
 				vec![path.clone(), PathBuf::from("/")]
 			} else {
 				vec![path.clone(), path.parent().unwrap().to_path_buf(), path.parent().unwrap().join("")]
@@ -69,156 +532,334 @@ impl Resource {
 		}
 	}
 
-	pub fn content(&self) -> Vec<u8> {
-		let mut templater = Tera::new("layouts/**/*.html").unwrap();
-		let empty_render_ctx = tera::Context::new();
+	fn pipeline(&self) -> Vec<Stage> {
+		if self.is_raw() || self.command.is_some() {
+			return vec![];
+		}
 
-		match self.source.extension().map(|v| v.to_str().unwrap()) {
-			Some("html") => templater.render_str(&fs::read_to_string(&self.source).unwrap(), &empty_render_ctx).unwrap().into(),
-			Some("dj") => templater.render_str(&render_djot(&fs::read_to_string(&self.source).unwrap()).unwrap(), &empty_render_ctx).unwrap().into(),
-			_ => fs::read(&self.source).unwrap(),
+		if let Some(stages) = &self.rule.render {
+			return stages.clone();
+		}
+
+		if let Some((markup, _)) = self.markup_suffix(&self.source) {
+			return vec![markup, Stage::Tera];
+		}
+
+		#[cfg(feature = "sass")]
+		if self.is_sass() {
+			return vec![Stage::Sass];
+		}
+
+		if self.is_template() {
+			return vec![Stage::Tera];
 		}
-	}
 
-	pub fn media_type(&self) -> TokenStream {
 		match self.source.extension().map(|v| v.to_str().unwrap()) {
-			Some("html") | Some("dj") => quote! { ::mime::TEXT_HTML_UTF_8 },
-			Some("css") => quote! { ::mime::TEXT_CSS },
-			Some("cer") => quote! { "application/pkix-cert".parse::<::mime::Mime>().unwrap() },
-			Some("der") => quote! { ::mime::APPLICATION_OCTET_STREAM },
-			Some("gpg") => quote! { "application/pgp-keys".parse::<::mime::Mime>().unwrap() },
-			Some("ico") => quote! { "image/vnd.microsoft.icon".parse::<::mime::Mime>().unwrap() },
-			Some("js")  => quote! { ::mime::APPLICATION_JSON },
-			Some("pem") => quote! { ::mime::TEXT_PLAIN },
-			Some("pkbf") => quote! { ::mime::APPLICATION_OCTET_STREAM },
-			Some("png") => quote! { ::mime::IMAGE_PNG },
-			Some("txt") => quote! { ::mime::TEXT_PLAIN },
-			Some(ext) => panic!("Unmimeable file extension: {ext:?}"),
-			None      => quote! { ::mime::APPLICATION_OCTET_STREAM },
+			Some("html") => vec![Stage::Tera],
+			_ => vec![],
 		}
 	}
-}
 
-fn scan_resources<P>(base_path: P) -> Vec<Resource> where P: AsRef<Path> {
-	let mut resources: Vec<Resource> = vec![];
+	pub fn content(&self) -> Vec<u8> {
+		self.try_content().unwrap()
+	}
 
-	fn valid_static_file(entry: &DirEntry) -> bool {
-		!entry.file_name()
-			.to_str()
-			.map(|s| s.starts_with("."))
-			.unwrap_or(false)
+	pub fn try_content(&self) -> Result<Vec<u8>, String> {
+		Ok(self.render()?.body)
 	}
 
-	for entry in WalkDir::new(&base_path).into_iter().filter_entry(valid_static_file) {
-		let entry = entry.unwrap();
-		dbg!(&entry);
+	// The newest modification time of everything the rendered content depends on
+	pub(crate) fn freshness(&self) -> Option<SystemTime> {
+		let mut newest = fs::metadata(&self.source).and_then(|m| m.modified()).ok()?;
+		let layouts = self.rule.layouts.as_deref().unwrap_or(Path::new("layouts"));
+		let partials = self.rule.partials.as_deref().unwrap_or(Path::new("partials"));
 
-		if env::var("PROFILE").unwrap() == "release" || entry.file_type().is_dir() {
-			println!("cargo::rerun-if-changed={}", entry.path().display());
+		if let Some(modified) = newest_modification([layouts, partials]) {
+			newest = newest.max(modified);
 		}
 
-		if entry.file_type().is_file() {
-			resources.push(Resource::new(entry.path().to_path_buf(), PathBuf::from("/").join(entry.path().to_path_buf().strip_prefix(&base_path).unwrap())));
+		if let Some(modified) = self.rule.djot.bibliography_file().and_then(|b| fs::metadata(b).and_then(|m| m.modified()).ok()) {
+			newest = newest.max(modified);
 		}
+
+		Some(newest)
 	}
 
-	resources
-}
+	/// Like `content`, but re-renders only when the source, a layout or partial, or the
+	/// bibliography has changed since the last call, for fast reloads in dev builds
+	pub fn cached_content(&self) -> Vec<u8> {
+		self.try_cached_content().unwrap()
+	}
 
-pub fn write_static_content_module<P>(fd: &mut File, base_path: P) -> Result<(), io::Error> where P: AsRef<Path> {
-	let resources = scan_resources(base_path);
-	let mut resource_paths = vec![];
-	let mut resource_responses = vec![];
-
-	for r in resources {
-		let source = r.source().display().to_string();
-		let content = r.content();
-		let media_type = r.media_type();
-
-		for p in r.paths() {
-			let path = p.display().to_string();
-
-			if env::var("PROFILE").unwrap() == "release" {
-				resource_responses.push(
-					quote! {
-						#path => ::actix_web::HttpResponse::Ok()
-							.insert_header(::actix_web::http::header::ContentType(#media_type))
-								.body(vec![#(#content),*]),
-					}
-				);
-			} else {
-				resource_responses.push(
-					quote! {
-						#path => {
-							let r = ::static_builder::Resource::new(::std::path::PathBuf::from(#source), ::std::path::PathBuf::from(#path));
-
-							::actix_web::HttpResponse::Ok()
-							.insert_header(::actix_web::http::header::ContentType(#media_type))
-								.body(r.content())
-						},
-					}
-				);
+	pub fn try_cached_content(&self) -> Result<Vec<u8>, String> {
+		let Some(freshness) = self.freshness() else { return self.try_content() };
+
+		if let Some((rendered_at, body)) = RENDER_CACHE.lock().unwrap_or_else(PoisonError::into_inner).get(&self.source) {
+			if *rendered_at == freshness {
+				return Ok(body.clone());
 			}
+		}
+
+		let body = self.try_content()?;
+		RENDER_CACHE.lock().unwrap_or_else(PoisonError::into_inner).insert(self.source.clone(), (freshness, body.clone()));
 
-			resource_paths.push(path);
+		Ok(body)
+	}
+
+	pub fn content_type(&self) -> &str {
+		if let Some(content_type) = &self.content_type {
+			return content_type;
+		}
+
+		if self.is_djot() {
+			return "text/html; charset=utf-8";
+		}
+
+		if self.is_sass() {
+			return "text/css";
+		}
+
+		// A file served encoded is of the type it is decoded (`.tar` for `.tar.gz`), and a template
+		// of the type it renders (`.json` for `.json.tera`)
+		let ext = match self.rule().content_encoding {
+			Some(_) => self.source.file_stem().map(Path::new).and_then(Path::extension),
+			None if self.is_template() => self.source.file_stem().map(Path::new).and_then(Path::extension),
+			None => self.source.extension(),
+		};
+
+		match ext.map(|v| v.to_str().unwrap()) {
+			Some("html") => "text/html; charset=utf-8",
+			Some("css") => "text/css",
+			Some("avif") => "image/avif",
+			Some("cer") => "application/pkix-cert",
+			Some("der") => "application/octet-stream",
+			Some("gif") => "image/gif",
+			Some("gpg") => "application/pgp-keys",
+			Some("gz") => "application/gzip",
+			Some("ico") => "image/vnd.microsoft.icon",
+			Some("jpg" | "jpeg") => "image/jpeg",
+			Some("js")  => "text/javascript",
+			Some("json") => "application/json",
+			Some("pdf") => "application/pdf",
+			Some("pem") => "text/plain",
+			Some("pkbf") => "application/octet-stream",
+			Some("png") => "image/png",
+			Some("svg") => "image/svg+xml",
+			Some("tar") => "application/x-tar",
+			Some("txt") => "text/plain",
+			Some("webmanifest") => "application/manifest+json",
+			Some("webp") => "image/webp",
+			Some("xml") => "application/xml",
+			Some(ext) => panic!("Unmimeable file extension: {ext:?}"),
+			None      => "application/octet-stream",
 		}
 	}
 
-	let quoted_code = quote! {
-		pub(crate) struct StaticContent;
+	pub fn render(&self) -> Result<RenderedResource, String> {
+		let stages = self.pipeline();
+		let raw = match &self.bytes {
+			Some(bytes) => bytes.clone(),
+			None => fs::read(&self.source).map_err(|e| format!("failed to read {}: {e}", self.source.display()))?,
+		};
+		let raw = match &self.command {
+			Some(command) => command::run(command, &self.source, raw).map_err(|e| format!("{}: {e}", self.source.display()))?,
+			None => raw,
+		};
+
+		let mut paths: Vec<String> = self.paths().iter().map(|p| p.display().to_string()).collect();
+		let mut redirects = vec![];
+
+		let (body, metadata, fields, content) = if stages.is_empty() {
+			(raw, None, BTreeMap::new(), None)
+		} else {
+			let input = String::from_utf8(raw).map_err(|e| format!("{}: not valid UTF-8: {e}", self.source.display()))?;
+			let renderer = self.renderer.as_ref().map(|(_, renderer)| renderer.as_ref());
+			let (body, page) = render_pipeline(&stages, &input, &self.rule, self.assets.as_ref(), &self.source, renderer, self.pages.as_deref()).map_err(|e| format!("{}: {e}", self.source.display()))?;
+
+			if paths.len() > 1 {
+				if let Some(canonical) = page.metadata.canonical {
+					let i = match canonical {
+						Canonical::Index => 0,
+						// The root index only has `/` as its alias, which is all three at once
+						Canonical::Directory | Canonical::Bare if paths.len() == 2 => 1,
+						Canonical::Bare => 1,
+						Canonical::Directory => 2,
+					};
+					let path = paths.remove(i);
+					paths.insert(0, path);
+				}
 
-		impl StaticContent {
-			#[allow(clippy::panic, clippy::unwrap_used)]  // Things that go wrong in here are worth exploding for
-			#[allow(clippy::too_many_lines)]  // Autogenerated code has different notions of style
-			fn response(path: &str) -> ::actix_web::HttpResponse {
-				match path {
-					#(#resource_responses)*
-					p => panic!("Where the heck did we get {p} from?!?"),
+				if page.metadata.aliases == Aliases::Redirect {
+					redirects = paths.split_off(1);
 				}
 			}
-		}
+			for path in page.metadata.redirect_from {
+				if !path.starts_with('/') {
+					return Err(format!("{}: redirect_from paths must start with /, unlike {path:?}", self.source.display()));
+				}
+				if !paths.contains(&path) && !redirects.contains(&path) {
+					redirects.push(path);
+				}
+			}
+
+			let html = self.content_type().starts_with("text/html");
+			let body = if page.metadata.noindex && html {
+				with_head_tag(&body, r#"<meta name="robots" content="noindex">"#)
+			} else {
+				body
+			};
+			let body = match &self.rule.lang {
+				Some(lang) if html => with_lang(&body, lang),
+				_ => body,
+			};
+
+			(body.into(), page.frontmatter, page.fields, page.fragment)
+		};
+
+		Ok(RenderedResource {
+			source: self.source.clone(),
+			paths,
+			redirects,
+			content_type: self.content_type().to_string(),
+			body,
+			metadata,
+			fields,
+			content,
+			formats: vec![],
+		})
+	}
+}
 
-		impl ::actix_web::dev::HttpServiceFactory for StaticContent {
-			fn register(self, config: &mut ::actix_web::dev::AppService) {
-				let mut res_def = ::actix_web::dev::ResourceDef::new(vec![#(#resource_paths),*]);
-				res_def.set_name("StaticContent");
+#[derive(Clone, Debug)]
+pub struct RenderedResource {
+	pub source: PathBuf,
+	/// The routes serving this resource, canonical first
+	pub paths: Vec<String>,
+	/// Alias routes that permanently redirect to the canonical route
+	pub redirects: Vec<String>,
+	pub content_type: String,
+	pub body: Vec<u8>,
+	/// The page's frontmatter, for sources that had any
+	pub metadata: Option<serde_yaml::Value>,
+	/// Fields from a djot page's `{.metadata}` description list
+	pub fields: BTreeMap<String, String>,
+	/// An HTML page's own content, before its layout wrapped it
+	pub content: Option<String>,
+	/// The image in other formats (by media type, smallest first), for the clients that accept them
+	pub formats: Vec<(String, Vec<u8>)>,
+}
 
-				config.register_service(res_def, None, self, None);
+impl RenderedResource {
+	pub fn title(&self) -> Option<&str> {
+		self.metadata.as_ref().and_then(|m| m.get("title")).and_then(|t| t.as_str())
+	}
+
+	/// Whether the page's frontmatter asks search engines not to index it
+	pub fn noindex(&self) -> bool {
+		self.metadata.as_ref().and_then(|m| m.get("noindex")).and_then(|n| n.as_bool()).unwrap_or(false)
+	}
+}
+
+pub fn render_tree<P>(base_path: P) -> Result<Vec<RenderedResource>, String> where P: AsRef<Path> {
+	Builder::new(base_path).render_tree()
+}
+
+struct Scan {
+	resources: Vec<Resource>,
+	// Directories and config files whose changes invalidate the scan itself
+	dependencies: Vec<PathBuf>,
+}
+
+fn scan_resources<P>(base_path: P, builder_rules: &[(String, Rule)]) -> Result<Scan, String> where P: AsRef<Path> {
+	let mut resources: Vec<Resource> = vec![];
+	let mut dependencies = vec![];
+	let mut rules = Rules::default();
+
+	for (glob, rule) in builder_rules {
+		rules.push(glob, rule.clone())?;
+	}
+
+	if !base_path.as_ref().is_dir() {
+		let cwd = env::current_dir().map(|d| d.display().to_string()).unwrap_or_default();
+		return Err(format!("content directory {} doesn't exist (looking from {cwd})", base_path.as_ref().display()));
+	}
+
+	for entry in WalkDir::new(&base_path).into_iter().filter_entry(valid_static_file) {
+		let entry = entry.map_err(|e| format!("failed to scan {}: {e}", base_path.as_ref().display()))?;
+
+		if entry.file_type().is_dir() {
+			dependencies.push(entry.path().to_path_buf());
+
+			let config_file = entry.path().join(DIRECTORY_CONFIG);
+
+			if config_file.exists() {
+				rules.load_directory_config(entry.path().strip_prefix(&base_path).unwrap(), &config_file)?;
+				dependencies.push(config_file);
 			}
 		}
 
-		impl ::actix_web::dev::ServiceFactory<::actix_web::dev::ServiceRequest> for StaticContent {
-			type Response = ::actix_web::dev::ServiceResponse;
-			type Error = ::actix_web::Error;
-			type InitError = ();
-			type Config = ();
-			type Service = StaticContent;
-			type Future = ::std::future::Ready<Result<Self::Service, ()>>;
+		if entry.file_type().is_file() {
+			resources.push(Resource::new(entry.path().to_path_buf(), PathBuf::from("/").join(entry.path().to_path_buf().strip_prefix(&base_path).unwrap())));
+		}
+	}
 
-			fn new_service(&self, _cfg: Self::Config) -> Self::Future {
-				::std::future::ready(Ok(StaticContent))
+	let resources: Vec<Resource> = resources.into_iter().map(|r| {
+		let rule = rules.resolve(r.path.strip_prefix("/").unwrap());
+		r.with_rule(rule)
+	}).filter(|r| r.rule.enabled() && !r.is_sass_partial()).collect();
+
+	for r in &resources {
+		if let Some(bibliography) = r.rule.djot.bibliography_file() {
+			if !dependencies.contains(bibliography) {
+				dependencies.push(bibliography.clone());
 			}
 		}
+	}
 
-		impl ::actix_web::dev::Service<::actix_web::dev::ServiceRequest> for StaticContent {
-			type Response = ::actix_web::dev::ServiceResponse;
-			type Error = ::actix_web::Error;
-			type Future = ::std::future::Ready<Result<Self::Response, Self::Error>>;
+	Ok(Scan { resources, dependencies })
+}
 
-			::actix_web::dev::always_ready!();
+pub fn write_static_content_module<P>(fd: &mut File, base_path: P) -> Result<(), io::Error> where P: AsRef<Path> {
+	Builder::new(base_path).write(fd)
+}
 
-			fn call(&self, req: ::actix_web::dev::ServiceRequest) -> Self::Future {
-				if !matches!(*req.method(), ::actix_web::http::Method::HEAD | ::actix_web::http::Method::GET) {
-					return ::std::future::ready(Ok(req.into_response(::actix_web::HttpResponse::MethodNotAllowed())));
-				}
+/// Like `write_static_content_module`, but for an axum application
+pub fn write_static_content_module_axum<P>(fd: &mut File, base_path: P) -> Result<(), io::Error> where P: AsRef<Path> {
+	Builder::new(base_path).backend(Backend::Axum).write(fd)
+}
 
-				let res = StaticContent::response(req.path());
-				::std::future::ready(Ok(req.into_response(res)))
-			}
-		}
-	};
-	let syntax_tree = syn::parse2(quoted_code).unwrap();
-	writeln!(fd, "{}", prettyplease::unparse(&syntax_tree))?;
+/// Like `write_static_content_module`, but for a Rocket application
+pub fn write_static_content_module_rocket<P>(fd: &mut File, base_path: P) -> Result<(), io::Error> where P: AsRef<Path> {
+	Builder::new(base_path).backend(Backend::Rocket).write(fd)
+}
 
-	Ok(())
+/// Like `write_static_content_module`, but for a Poem application
+pub fn write_static_content_module_poem<P>(fd: &mut File, base_path: P) -> Result<(), io::Error> where P: AsRef<Path> {
+	Builder::new(base_path).backend(Backend::Poem).write(fd)
+}
+
+/// Like `write_static_content_module`, but for a warp application
+pub fn write_static_content_module_warp<P>(fd: &mut File, base_path: P) -> Result<(), io::Error> where P: AsRef<Path> {
+	Builder::new(base_path).backend(Backend::Warp).write(fd)
+}
+
+/// Like `write_static_content_module`, but generating only a framework-agnostic `RESOURCES` table
+/// and lookups
+pub fn write_static_content_map<P>(fd: &mut File, base_path: P) -> Result<(), io::Error> where P: AsRef<Path> {
+	Builder::new(base_path).backend(Backend::Map).write(fd)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{render_pipeline, ContentRenderer, DjotOptions, Rule, Sanitize, Stage};
+	use std::{collections::BTreeMap, path::Path};
+
+	#[test]
+	fn custom_renderers_output_is_sanitized() {
+		let renderer = |_: &Path, content: &str| Ok((format!("<p onclick=\"x\">{content}</p><script>alert(1)</script><a href=\"javascript:x\">y</a>"), BTreeMap::new()));
+		let render = |rule: &Rule| render_pipeline(&[Stage::Custom], "{{ secret }}", rule, None, Path::new("page.custom"), Some(&renderer as &dyn ContentRenderer), None).unwrap().0;
+
+		assert_eq!(render(&Rule::default()), "<p onclick=\"x\">{{ secret }}</p><script>alert(1)</script><a href=\"javascript:x\">y</a>");
+		assert_eq!(render(&Rule::default().djot(DjotOptions::new().sanitize(Sanitize::new()))), "<p>&#123;&#123; secret }}</p><a>y</a>");
+	}
 }
@@ -0,0 +1,73 @@
+use std::{collections::BTreeMap, env, fs, path::Path};
+
+use crate::{assets::fnv1a, RenderedResource, Resource};
+
+pub(crate) const UPDATE_VAR: &str = "STATIC_BUILDER_UPDATE_MANIFEST";
+
+// Every route, one per line as `<set>\t<path>\t<size>\t<hash>` (the set being empty for the
+// default one), or `<set>\t<path>\t->\t<location>` for redirects; sorted, so it diffs well
+pub(crate) fn manifest<'a, I>(sets: I) -> String where I: IntoIterator<Item = (&'a str, &'a [(Resource, RenderedResource)])> {
+	let mut lines = vec![];
+
+	for (set, rendered) in sets {
+		for (_, page) in rendered {
+			let hash = fnv1a(&page.body);
+
+			for path in &page.paths {
+				lines.push(format!("{set}\t{path}\t{}\t{hash:016x}", page.body.len()));
+			}
+			for path in &page.redirects {
+				lines.push(format!("{set}\t{path}\t->\t{}", page.paths[0]));
+			}
+		}
+	}
+
+	lines.sort();
+	lines.dedup();
+	lines.into_iter().map(|l| l + "\n").collect()
+}
+
+fn routes(manifest: &str) -> BTreeMap<(&str, &str), (&str, &str)> {
+	manifest.lines().filter_map(|line| {
+		let mut fields = line.splitn(4, '\t');
+		Some(((fields.next()?, fields.next()?), (fields.next()?, fields.next()?)))
+	}).collect()
+}
+
+/// Reports (as build warnings) every route added, removed or changed since the manifest committed
+/// at `file`, which is (re)written instead when it doesn't exist or `UPDATE_VAR` is set
+pub(crate) fn compare(file: &Path, manifest: &str) -> Result<(), String> {
+	println!("cargo::rerun-if-changed={}", file.display());
+	println!("cargo::rerun-if-env-changed={UPDATE_VAR}");
+
+	let previous = match fs::read_to_string(file) {
+		Ok(previous) if env::var_os(UPDATE_VAR).is_none() => previous,
+		_ => return fs::write(file, manifest).map_err(|e| format!("failed to write {}: {e}", file.display())),
+	};
+
+	let (before, after) = (routes(&previous), routes(manifest));
+	let route = |(set, path): &(&str, &str)| if set.is_empty() { path.to_string() } else { format!("{path} (in {set})") };
+	let mut changes = 0;
+
+	for (key, old) in &before {
+		match after.get(key) {
+			None => println!("cargo::warning=route removed: {}", route(key)),
+			Some(new) if new != old => println!("cargo::warning=route changed: {}: {} {} -> {} {}", route(key), old.0, old.1, new.0, new.1),
+			Some(_) => continue,
+		}
+		changes += 1;
+	}
+
+	for (key, new) in &after {
+		if !before.contains_key(key) {
+			println!("cargo::warning=route added: {}: {} {}", route(key), new.0, new.1);
+			changes += 1;
+		}
+	}
+
+	if changes > 0 {
+		println!("cargo::warning={changes} route(s) differ from {}; build with {UPDATE_VAR}=1 to update it", file.display());
+	}
+
+	Ok(())
+}
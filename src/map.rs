@@ -0,0 +1,94 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::{codegen::Options, RenderedResource, Resource};
+
+// Checks that the tables agree with themselves, run with the application's own tests
+fn tests() -> TokenStream {
+	quote! {
+		#[cfg(test)]
+		#[allow(clippy::unwrap_used, clippy::panic)]
+		mod static_content_resource_tests {
+			#[test]
+			fn every_resource_is_found() {
+				for (path, body, media_type) in super::RESOURCES {
+					assert_eq!(super::resource(path), Some((*body, *media_type)), "{path}");
+				}
+			}
+
+			#[test]
+			fn redirects_point_at_resources() {
+				for (from, to) in super::REDIRECTS {
+					assert!(super::resource(to).is_some(), "{from} -> {to}");
+				}
+			}
+
+			#[test]
+			fn only_listed_paths_are_found() {
+				let listed: Vec<&str> = super::RESOURCES.iter().map(|(path, _, _)| *path).chain(super::REDIRECTS.iter().map(|(path, _)| *path)).collect();
+
+				for path in ::static_builder::serve::awkward_paths(&listed).iter().filter(|path| !listed.contains(&path.as_str())) {
+					assert!(super::resource(path).is_none() && super::redirect(path).is_none(), "{path:?}");
+				}
+			}
+		}
+	}
+}
+
+pub(crate) fn map_module(rendered: &[(Resource, RenderedResource)], sets: &[(&str, Vec<(Resource, RenderedResource)>)], options: &Options<'_>) -> Result<TokenStream, String> {
+	if !sets.is_empty() {
+		return Err("content sets need a web framework backend to choose between them".to_string());
+	}
+	if rendered.iter().any(|(r, _)| !r.windows().is_empty()) {
+		return Err("scheduled variants need a web framework backend to switch between them".to_string());
+	}
+
+	let mut resources = vec![];
+	let mut redirects = vec![];
+
+	for (_, page) in rendered {
+		for path in &page.paths {
+			resources.push((path, options.static_bytes(&page.body), &page.content_type));
+		}
+
+		for path in &page.redirects {
+			redirects.push((path, &page.paths[0]));
+		}
+	}
+
+	// Lookups are binary searches, and the first resource for a path is the one that's served
+	resources.sort_by(|a, b| a.0.cmp(b.0));
+	resources.dedup_by(|a, b| a.0 == b.0);
+	redirects.sort();
+	redirects.dedup_by(|a, b| a.0 == b.0);
+
+	let resources = resources.into_iter().map(|(path, body, media_type)| quote! { (#path, #body, #media_type) });
+	let (redirect_from, redirect_to): (Vec<_>, Vec<_>) = redirects.into_iter().unzip();
+	let bodies = options.body_statics()?;
+	let tests = if options.tests { tests() } else { quote! {} };
+
+	Ok(quote! {
+		/// Every path served, with its body and media type, sorted by path
+		pub(crate) static RESOURCES: &[(&str, &[u8], &str)] = &[#(#resources),*];
+		/// Every path that should redirect, with the path it redirects to, sorted by path
+		pub(crate) static REDIRECTS: &[(&str, &str)] = &[#((#redirect_from, #redirect_to)),*];
+
+		/// The body and media type to serve at `path`
+		#[allow(dead_code)]
+		pub(crate) fn resource(path: &str) -> Option<(&'static [u8], &'static str)> {
+			let i = RESOURCES.binary_search_by(|(p, _, _)| (*p).cmp(path)).ok()?;
+			Some((RESOURCES[i].1, RESOURCES[i].2))
+		}
+
+		/// Where a request for `path` should be redirected to
+		#[allow(dead_code)]
+		pub(crate) fn redirect(path: &str) -> Option<&'static str> {
+			let i = REDIRECTS.binary_search_by(|(p, _)| (*p).cmp(path)).ok()?;
+			Some(REDIRECTS[i].1)
+		}
+
+		#bodies
+
+		#tests
+	})
+}
@@ -0,0 +1,254 @@
+use jotdown::{Alignment, Attributes, Container, Event, LinkType, ListKind, OrderedListNumbering, OrderedListStyle, SpanLinkType};
+use pulldown_cmark::{Alignment as ColumnAlignment, CodeBlockKind, Event as Markdown, LinkType as MarkdownLinkType, Options, Parser, Tag, TagEnd};
+use std::{borrow::Cow, collections::{BTreeMap, HashSet}};
+
+use crate::djot::{render_events, DjotOptions};
+
+// The id djot gives a heading reading `text`: its words less their punctuation, joined by `-`,
+// and numbered where an earlier heading's is the same
+fn heading_id(text: &str, used: &mut HashSet<String>) -> String {
+	let words = text.split_ascii_whitespace().map(|word| word.chars().filter(|c| !c.is_ascii_punctuation() || matches!(c, '-' | '_')).collect::<String>());
+	let mut id = words.filter(|word| !word.is_empty()).collect::<Vec<_>>().join("-");
+
+	if id.is_empty() || used.contains(&id) {
+		if id.is_empty() {
+			id.push('s');
+		}
+		id = (1..).map(|n| format!("{id}-{n}")).find(|numbered| !used.contains(numbered)).unwrap_or_default();
+	}
+	used.insert(id.clone());
+
+	id
+}
+
+fn alignment(alignment: Option<&ColumnAlignment>) -> Alignment {
+	match alignment {
+		Some(ColumnAlignment::Left) => Alignment::Left,
+		Some(ColumnAlignment::Center) => Alignment::Center,
+		Some(ColumnAlignment::Right) => Alignment::Right,
+		_ => Alignment::Unspecified,
+	}
+}
+
+fn titled(title: &str) -> Attributes<'_> {
+	let mut attributes = Attributes::new();
+	if !title.is_empty() {
+		attributes.insert("title", title.into());
+	}
+
+	attributes
+}
+
+// Markdown's events as djot's, with top-level headings opening sections as djot's do
+fn events<'m>(markdown: &'m [Markdown<'_>]) -> Vec<Event<'m>> {
+	let mut events = vec![];
+	// What each open Markdown tag is as djot, and the level and id of each open section
+	let mut open: Vec<Option<Container<'m>>> = vec![];
+	let mut sections: Vec<(u16, String)> = vec![];
+	let mut ids = HashSet::new();
+	let (mut columns, mut column, mut head) = (&[][..], 0, false);
+
+	for (i, event) in markdown.iter().enumerate() {
+		let start = match event {
+			Markdown::Start(tag) => tag,
+			Markdown::End(_) => {
+				events.extend(open.pop().flatten().map(Event::End));
+				continue;
+			},
+			Markdown::Text(text) | Markdown::Html(text) => {
+				events.push(Event::Str(Cow::Borrowed(text)));
+				continue;
+			},
+			Markdown::Code(code) => {
+				events.extend([Event::Start(Container::Verbatim, Attributes::new()), Event::Str(Cow::Borrowed(code)), Event::End(Container::Verbatim)]);
+				continue;
+			},
+			Markdown::InlineHtml(html) => {
+				let raw = Container::RawInline { format: "html" };
+				events.extend([Event::Start(raw.clone(), Attributes::new()), Event::Str(Cow::Borrowed(html)), Event::End(raw)]);
+				continue;
+			},
+			Markdown::SoftBreak => {
+				events.push(Event::Softbreak);
+				continue;
+			},
+			Markdown::HardBreak => {
+				events.push(Event::Hardbreak);
+				continue;
+			},
+			Markdown::Rule => {
+				events.push(Event::ThematicBreak(Attributes::new()));
+				continue;
+			},
+			// Taken with its item, or not parsed at all
+			_ => continue,
+		};
+
+		let (container, attributes) = match start {
+			Tag::Paragraph => (Container::Paragraph, Attributes::new()),
+			Tag::Heading { level, .. } => {
+				let level = *level as u16;
+				let text: String = markdown[i + 1..].iter()
+					.take_while(|e| !matches!(e, Markdown::End(TagEnd::Heading(_))))
+					.filter_map(|e| match e {
+						Markdown::Text(text) => Some(&**text),
+						Markdown::SoftBreak | Markdown::HardBreak => Some(" "),
+						_ => None,
+					})
+					.collect();
+				let id = heading_id(&text, &mut ids);
+				let top = open.is_empty();
+				if top {
+					while sections.last().is_some_and(|(section, _)| *section >= level) {
+						events.extend(sections.pop().map(|(_, id)| Event::End(Container::Section { id: id.into() })));
+					}
+					events.push(Event::Start(Container::Section { id: id.clone().into() }, Attributes::new()));
+					sections.push((level, id.clone()));
+				}
+				(Container::Heading { level, has_section: top, id: id.into() }, Attributes::new())
+			},
+			Tag::BlockQuote(_) => (Container::Blockquote, Attributes::new()),
+			Tag::CodeBlock(CodeBlockKind::Fenced(info)) => (Container::CodeBlock { language: info.split_whitespace().next().unwrap_or_default() }, Attributes::new()),
+			Tag::CodeBlock(CodeBlockKind::Indented) => (Container::CodeBlock { language: "" }, Attributes::new()),
+			Tag::HtmlBlock => (Container::RawBlock { format: "html" }, Attributes::new()),
+			Tag::List(first) => {
+				let task = matches!(markdown.get(i + 2), Some(Markdown::TaskListMarker(_)));
+				let kind = match first {
+					Some(start) => ListKind::Ordered { numbering: OrderedListNumbering::Decimal, style: OrderedListStyle::Period, start: *start },
+					None if task => ListKind::Task,
+					None => ListKind::Unordered,
+				};
+				// Markdown leaves out the paragraphs of a tight list's items itself
+				(Container::List { kind, tight: false }, Attributes::new())
+			},
+			Tag::Item => match markdown.get(i + 1) {
+				Some(Markdown::TaskListMarker(checked)) => (Container::TaskListItem { checked: *checked }, Attributes::new()),
+				_ => (Container::ListItem, Attributes::new()),
+			},
+			Tag::Table(alignments) => {
+				columns = alignments;
+				(Container::Table, Attributes::new())
+			},
+			Tag::TableHead | Tag::TableRow => {
+				(column, head) = (0, matches!(start, Tag::TableHead));
+				(Container::TableRow { head }, Attributes::new())
+			},
+			Tag::TableCell => {
+				column += 1;
+				(Container::TableCell { alignment: alignment(columns.get(column - 1)), head }, Attributes::new())
+			},
+			Tag::Emphasis => (Container::Emphasis, Attributes::new()),
+			Tag::Strong => (Container::Strong, Attributes::new()),
+			Tag::Strikethrough => (Container::Delete, Attributes::new()),
+			Tag::Link { link_type, dest_url, title, .. } => {
+				let kind = match link_type {
+					MarkdownLinkType::Autolink => LinkType::AutoLink,
+					MarkdownLinkType::Email => LinkType::Email,
+					_ => LinkType::Span(SpanLinkType::Inline),
+				};
+				(Container::Link(Cow::Borrowed(dest_url), kind), titled(title))
+			},
+			Tag::Image { dest_url, title, .. } => (Container::Image(Cow::Borrowed(dest_url), SpanLinkType::Inline), titled(title)),
+			_ => {
+				open.push(None);
+				continue;
+			},
+		};
+		events.push(Event::Start(container.clone(), attributes));
+		open.push(Some(container));
+	}
+	events.extend(sections.into_iter().rev().map(|(_, id)| Event::End(Container::Section { id: id.into() })));
+
+	events
+}
+
+/// Markdown (CommonMark, with GitHub's tables, task lists and strikethrough) rendered as djot is,
+/// with the same options, but none of djot's typography, symbols or attributes
+pub(crate) fn render(markdown: &str, options: &DjotOptions) -> Result<(String, BTreeMap<String, String>), String> {
+	let parsed: Vec<Markdown<'_>> = Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_TASKLISTS | Options::ENABLE_STRIKETHROUGH).collect();
+
+	render_events(events(&parsed).into_iter(), options)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::render;
+	use crate::{DjotOptions, Sanitize};
+
+	fn html(markdown: &str) -> String {
+		render(markdown, &DjotOptions::default()).unwrap().0
+	}
+
+	// The HTML as the CommonMark spec writes it, which puts its line breaks and `/>`s elsewhere
+	// and has no sections
+	fn normalized(html: &str) -> String {
+		let html = html.replace('\n', "").replace(" />", ">").replace("&quot;", "\"");
+		let mut normalized = String::new();
+		let mut rest = html.as_str();
+		while let Some(start) = rest.find("<section").or_else(|| rest.find("</section>")) {
+			normalized.push_str(&rest[..start]);
+			rest = &rest[start + rest[start..].find('>').unwrap_or_default() + 1..];
+		}
+		normalized.push_str(rest);
+
+		normalized
+	}
+
+	#[test]
+	fn spec_examples() {
+		let examples = [
+			("\\*not emphasized*\n", "<p>*not emphasized*</p>\n"),
+			("&copy; &#35; &#x22;\n", "<p>© # &quot;</p>\n"),
+			("***\n---\n___\n", "<hr />\n<hr />\n<hr />\n"),
+			("Foo *bar*\n=========\n\nFoo\n---\n", "<h1>Foo <em>bar</em></h1>\n<h2>Foo</h2>\n"),
+			("    a simple\n      indented code block\n", "<pre><code>a simple\n  indented code block\n</code></pre>\n"),
+			("~~~\n<\n >\n~~~\n", "<pre><code>&lt;\n &gt;\n</code></pre>\n"),
+			("<div>\n*hello*\n         <foo><a>\n", "<div>\n*hello*\n         <foo><a>\n"),
+			("[FOO]: /url\n\n[Foo]\n", "<p><a href=\"/url\">Foo</a></p>\n"),
+			("> bar\nbaz\n> foo\n", "<blockquote>\n<p>bar\nbaz\nfoo</p>\n</blockquote>\n"),
+			("123. ok\n", "<ol start=\"123\">\n<li>ok</li>\n</ol>\n"),
+			("The number of windows in my house is\n14.  The number of doors is 6.\n", "<p>The number of windows in my house is\n14.  The number of doors is 6.</p>\n"),
+			("- a\n- b\n\n- c\n", "<ul>\n<li>\n<p>a</p>\n</li>\n<li>\n<p>b</p>\n</li>\n<li>\n<p>c</p>\n</li>\n</ul>\n"),
+			("`` foo ` bar ``\n", "<p><code>foo ` bar</code></p>\n"),
+			("*foo bar *\n", "<p>*foo bar *</p>\n"),
+			("foo__bar__\n", "<p>foo__bar__</p>\n"),
+			("***strong emph***\n", "<p><em><strong>strong emph</strong></em></p>\n"),
+			("<http://foo.bar.baz>\n", "<p><a href=\"http://foo.bar.baz\">http://foo.bar.baz</a></p>\n"),
+			("foo\\\nbar\n", "<p>foo<br />\nbar</p>\n"),
+			("\"Hello\" -- it's... done\n", "<p>&quot;Hello&quot; -- it's... done</p>\n"),
+		];
+
+		for (markdown, expected) in examples {
+			assert_eq!(normalized(&html(markdown)), normalized(expected), "for {markdown:?}");
+		}
+	}
+
+	#[test]
+	fn headings_open_sections_as_djot_does() {
+		assert_eq!(html("# Title\n\n## Sub *heading* `code`\n\n## Sub heading\n\n# Title\n"), "<section id=\"Title\">\n<h1>Title</h1>\n<section id=\"Sub-heading\">\n<h2>Sub <em>heading</em> <code>code</code></h2>\n</section>\n<section id=\"Sub-heading-1\">\n<h2>Sub heading</h2>\n</section>\n</section>\n<section id=\"Title-1\">\n<h1>Title</h1>\n</section>\n");
+		assert_eq!(html("> # Quoted\n"), "<blockquote>\n<h1 id=\"Quoted\">Quoted</h1>\n</blockquote>\n");
+	}
+
+	#[test]
+	fn github_extensions() {
+		assert_eq!(html("- [x] done\n- [ ] todo\n"), "<ul class=\"task-list\">\n<li class=\"checked\">done\n</li>\n<li class=\"unchecked\">todo\n</li>\n</ul>\n");
+		assert_eq!(html("| a | b |\n|:--|--:|\n| 1 | 2 |\n"), "<table>\n<tr>\n<th style=\"text-align: left;\">a</th>\n<th style=\"text-align: right;\">b</th>\n</tr>\n<tr>\n<td style=\"text-align: left;\">1</td>\n<td style=\"text-align: right;\">2</td>\n</tr>\n</table>\n");
+		assert_eq!(html("~~gone~~\n"), "<p><del>gone</del></p>\n");
+	}
+
+	#[test]
+	fn links_and_images() {
+		assert_eq!(html("[link](/url \"Title\") <me@example.com> ![alt *text*](img.png)\n"), "<p><a href=\"/url\" title=\"Title\">link</a> <a href=\"mailto:me@example.com\">me@example.com</a> <img alt=\"alt text\" src=\"img.png\"></p>\n");
+		assert_eq!(html("```rust ignore\nfn main() {}\n```\n"), "<pre><code class=\"language-rust\">fn main() {}\n</code></pre>\n");
+	}
+
+	#[test]
+	fn djot_options_apply() {
+		let sanitized = DjotOptions::default().sanitize(Sanitize::new());
+		let (page, _) = render("<script>alert(1)</script>\n\nHi <b>there</b> [x](javascript:alert(1)) {{ secret }}\n", &sanitized).unwrap();
+		assert_eq!(page, "<p>Hi there <a>x</a> &#123;&#123; secret }}</p>\n");
+
+		let (page, _) = render("# Title\n", &DjotOptions::default().heading_offset(1)).unwrap();
+		assert_eq!(page, "<section id=\"Title\">\n<h2>Title</h2>\n</section>\n");
+	}
+}
@@ -0,0 +1,303 @@
+// The characters CSS needs no whitespace either side of
+const CSS_PUNCTUATION: &[char] = &['{', '}', ';', ',', '>', '~'];
+
+// The keywords whose parenthesised condition (or loop head) a statement, and so maybe a regular
+// expression, can follow
+const JS_CONTROL: &[&str] = &["if", "while", "for", "with"];
+
+// The keywords after which a `/` starts a regular expression rather than dividing
+const JS_KEYWORDS: &[&str] = &["return", "typeof", "instanceof", "in", "of", "new", "delete", "void", "throw", "case", "do", "else", "yield", "await"];
+
+// The quoted string starting at `chars[start]`, escapes and all, and where it ends
+fn quoted(chars: &[char], start: usize) -> (&[char], usize) {
+	let quote = chars[start];
+	let mut i = start + 1;
+
+	while i < chars.len() && chars[i] != quote && chars[i] != '\n' {
+		i += if chars[i] == '\\' { 2 } else { 1 };
+	}
+	let end = (i + 1).min(chars.len());
+
+	(&chars[start..end], end)
+}
+
+/// A stylesheet without its comments (other than `/*! ... */` licence comments) and the whitespace
+/// CSS doesn't need: runs of it collapse to a space, and go altogether around braces, semicolons,
+/// commas and child and sibling combinators, and after colons
+pub(crate) fn css(text: &str) -> String {
+	let chars: Vec<char> = text.chars().collect();
+	let mut out = String::with_capacity(text.len());
+	let mut space = false;
+	let mut i = 0;
+
+	while i < chars.len() {
+		let c = chars[i];
+		if c == '/' && chars.get(i + 1) == Some(&'*') {
+			let end = (i + 2..chars.len().saturating_sub(1)).find(|&j| chars[j] == '*' && chars[j + 1] == '/').map_or(chars.len(), |j| j + 2);
+			if chars.get(i + 2) == Some(&'!') {
+				out.extend(&chars[i..end]);
+			}
+			i = end;
+			continue;
+		}
+		if c.is_whitespace() {
+			space = true;
+			i += 1;
+			continue;
+		}
+
+		let after_punctuation = out.ends_with(CSS_PUNCTUATION) || out.ends_with(':');
+		if space && !out.is_empty() && !after_punctuation && !CSS_PUNCTUATION.contains(&c) {
+			out.push(' ');
+		}
+		space = false;
+
+		match c {
+			'"' | '\'' => {
+				let (string, end) = quoted(&chars, i);
+				out.extend(string);
+				i = end;
+				continue;
+			},
+			// The last declaration in a block needs no semicolon
+			'}' if out.ends_with(';') => {
+				out.pop();
+			},
+			_ => {},
+		}
+		out.push(c);
+		i += 1;
+	}
+
+	out
+}
+
+// The identifier (or keyword) `out` ends with, ignoring trailing spaces
+fn last_word(out: &str) -> &str {
+	out.trim_end_matches([' ', '\t']).rsplit(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$')).next().unwrap_or_default()
+}
+
+// Whether a `/` after `out` starts a regular expression; a `)` only allows one when it closes an
+// `if`, `while`, `for` or `with`, which `after_control` says
+fn starts_regex(out: &str, after_control: bool) -> bool {
+	let before = out.trim_end_matches([' ', '\t']);
+
+	match before.chars().last() {
+		None => true,
+		Some(')') => after_control,
+		Some(c) if "(,=:[!&|?{};+-*%<>~^\n".contains(c) => true,
+		Some(c) if c.is_alphanumeric() || c == '_' || c == '$' => JS_KEYWORDS.contains(&last_word(before)),
+		_ => false,
+	}
+}
+
+// The regular expression literal starting at `chars[start]`, and where it ends
+fn regex(chars: &[char], start: usize) -> usize {
+	let mut i = start + 1;
+	let mut class = false;
+
+	while i < chars.len() && chars[i] != '\n' {
+		match chars[i] {
+			'\\' => i += 1,
+			'[' => class = true,
+			']' => class = false,
+			'/' if !class => break,
+			_ => {},
+		}
+		i += 1;
+	}
+	i += 1;
+	while chars.get(i).is_some_and(|c| c.is_alphabetic()) {
+		i += 1;
+	}
+
+	i.min(chars.len())
+}
+
+// Ends the line `out` is on, unless it's blank
+fn line_break(out: &mut String) {
+	let trimmed = out.trim_end_matches([' ', '\t']).len();
+	out.truncate(trimmed);
+	if !out.is_empty() && !out.ends_with('\n') {
+		out.push('\n');
+	}
+}
+
+/// A script without its comments (other than `/*! ... */` licence comments), indentation, or
+/// blank lines, and with any other run of spaces collapsed to one.  Line breaks are kept, as
+/// automatic semicolon insertion depends on them, and strings, template literals and regular
+/// expressions are left as they are.
+pub(crate) fn js(text: &str) -> String {
+	let chars: Vec<char> = text.chars().collect();
+	let mut out = String::with_capacity(text.len());
+	// For each template literal being read, how many braces deep its current `${}` is
+	let mut templates: Vec<usize> = vec![];
+	let mut in_template = false;
+	// For each open parenthesis, whether it's an `if`, `while`, `for` or `with`'s, and whether the
+	// last one closed was
+	let mut parens: Vec<bool> = vec![];
+	let mut after_control = false;
+	let mut i = 0;
+
+	while i < chars.len() {
+		let c = chars[i];
+		if !c.is_whitespace() && c != '/' {
+			after_control = false;
+		}
+
+		if in_template {
+			out.push(c);
+			i += 1;
+			match c {
+				'\\' => {
+					out.extend(chars.get(i));
+					i += 1;
+				},
+				'`' => in_template = false,
+				'$' if chars.get(i) == Some(&'{') => {
+					out.push('{');
+					i += 1;
+					templates.push(0);
+					in_template = false;
+				},
+				_ => {},
+			}
+			continue;
+		}
+
+		match c {
+			'/' if chars.get(i + 1) == Some(&'/') => {
+				while i < chars.len() && chars[i] != '\n' {
+					i += 1;
+				}
+			},
+			'/' if chars.get(i + 1) == Some(&'*') => {
+				let end = (i + 2..chars.len().saturating_sub(1)).find(|&j| chars[j] == '*' && chars[j + 1] == '/').map_or(chars.len(), |j| j + 2);
+				if chars.get(i + 2) == Some(&'!') {
+					out.extend(&chars[i..end]);
+				} else if chars[i..end].contains(&'\n') {
+					line_break(&mut out);
+				} else if !out.ends_with([' ', '\n']) {
+					out.push(' ');
+				}
+				i = end;
+			},
+			'/' if starts_regex(&out, after_control) => {
+				let end = regex(&chars, i);
+				out.extend(&chars[i..end]);
+				i = end;
+			},
+			'"' | '\'' => {
+				let (string, end) = quoted(&chars, i);
+				out.extend(string);
+				i = end;
+			},
+			'`' => {
+				out.push(c);
+				in_template = true;
+				i += 1;
+			},
+			'(' => {
+				parens.push(JS_CONTROL.contains(&last_word(&out)));
+				out.push(c);
+				i += 1;
+			},
+			')' => {
+				after_control = parens.pop().unwrap_or(false);
+				out.push(c);
+				i += 1;
+			},
+			'{' => {
+				if let Some(depth) = templates.last_mut() {
+					*depth += 1;
+				}
+				out.push(c);
+				i += 1;
+			},
+			'}' => {
+				out.push(c);
+				i += 1;
+				match templates.last_mut() {
+					Some(0) => {
+						templates.pop();
+						in_template = true;
+					},
+					Some(depth) => *depth -= 1,
+					None => {},
+				}
+			},
+			'\n' | '\r' => {
+				line_break(&mut out);
+				i += 1;
+			},
+			' ' | '\t' => {
+				if !out.is_empty() && !out.ends_with([' ', '\n']) {
+					out.push(' ');
+				}
+				i += 1;
+			},
+			_ => {
+				out.push(c);
+				i += 1;
+			},
+		}
+	}
+
+	let trimmed = out.trim_end().len();
+	out.truncate(trimmed);
+
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{css, js};
+
+	#[test]
+	fn css_drops_comments_and_needless_whitespace() {
+		assert_eq!(css("/* layout */\na  >  b ,\nc ~ d {\n\tmargin:  0  auto ;\n\tcolor: red;\n}\n"), "a>b,c~d{margin:0 auto;color:red}");
+		assert_eq!(css("/*! licence */ a { }"), "/*! licence */ a{}");
+		assert_eq!(css("a { content: \"  two  spaces ; \" }"), "a{content:\"  two  spaces ; \"}");
+	}
+
+	#[test]
+	fn css_keeps_the_spaces_calc_needs() {
+		assert_eq!(css("a { width: calc(100%  -  2 * 1px); margin: calc(1em + -2px) }"), "a{width:calc(100% - 2 * 1px);margin:calc(1em + -2px)}");
+		assert_eq!(css("@media (min-width: 1px) and (max-width: 2px) { a { b: c } }"), "@media (min-width:1px) and (max-width:2px){a{b:c}}");
+	}
+
+	#[test]
+	fn js_drops_comments_indentation_and_blank_lines() {
+		assert_eq!(js("// setup\nlet a = 1;   // one\n\n\n\tif (a) {\n\t\t/* two\n\t\tlines */ b();\n\t}\n"), "let a = 1;\nif (a) {\nb();\n}");
+		assert_eq!(js("/*! licence */\nlet a = 1; /* inline */ let b = 2;"), "/*! licence */\nlet a = 1; let b = 2;");
+	}
+
+	#[test]
+	fn js_keeps_strings_and_templates() {
+		assert_eq!(js("let s = \"a  // b\", t = 'c  /* d */';"), "let s = \"a  // b\", t = 'c  /* d */';");
+		assert_eq!(js("let t = `a  ${ b  +  `c  ${d}  e` }  // f`;"), "let t = `a  ${ b + `c  ${d}  e` }  // f`;");
+		assert_eq!(js("let t = `a  ${ {x: 1}.x }  b`;  // c"), "let t = `a  ${ {x: 1}.x }  b`;");
+	}
+
+	#[test]
+	fn js_keeps_regular_expressions() {
+		for script in [
+			"let r = /a  b/g;",
+			"f(/a  [/]  b/, x);",
+			"return /a  \\/  b/.test(s);",
+			"if (x) /a  b/.test(y);",
+			"while (f(x)) /a  b/.exec(y);",
+			"for (const s of ss) /a  b/.test(s);",
+		] {
+			assert_eq!(js(script), script, "for {script}");
+		}
+	}
+
+	#[test]
+	fn js_divides_after_values() {
+		assert_eq!(js("let a = b  /  c  /  d;"), "let a = b / c / d;");
+		assert_eq!(js("let a = (b + c)  /  2  /  (d);"), "let a = (b + c) / 2 / (d);");
+		assert_eq!(js("let a = f(x)  /  g(y), b = c[0]  /  2;"), "let a = f(x) / g(y), b = c[0] / 2;");
+		assert_eq!(js("if (x) a  /  b;"), "if (x) a / b;");
+	}
+}
@@ -0,0 +1,78 @@
+use std::{collections::HashSet, fs, path::Path};
+
+use crate::{RenderedResource, Resource};
+
+// The path of `url`, which may be just a path, without its query or fragment
+fn path(url: &str) -> String {
+	let path = match url.split_once("://") {
+		Some((_, rest)) => rest.find('/').map_or("/", |i| &rest[i..]),
+		None => url,
+	};
+	let path = path.split(['?', '#']).next().unwrap_or_default();
+
+	match path.starts_with('/') {
+		true => path.to_string(),
+		false => format!("/{path}"),
+	}
+}
+
+// The paths of the URLs in `text`: a sitemap's `<loc>`s, or else each line that isn't blank or a
+// `#` comment
+fn paths(text: &str) -> Result<Vec<String>, String> {
+	if text.contains("<sitemapindex") {
+		return Err("it's a sitemap index, not a sitemap; list the URLs in the sitemaps it names instead".to_string());
+	}
+	if !text.contains("<loc>") {
+		return Ok(text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')).map(path).collect());
+	}
+
+	let locs = text.split("<loc>").skip(1).filter_map(|rest| rest.split_once("</loc>")).map(|(loc, _)| loc.trim());
+	Ok(locs.map(|loc| path(&loc.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&"))).collect())
+}
+
+// What a path names, e.g. `hello-world` for `/2019/hello-world.html` or `/posts/hello-world/`
+fn slug(path: &str) -> Option<String> {
+	let mut segments = path.split('/').filter(|s| !s.is_empty()).rev();
+	let last = segments.next()?;
+	let stem = last.rsplit_once('.').map_or(last, |(stem, _)| stem);
+
+	match stem {
+		"index" => segments.next().map(str::to_lowercase),
+		stem => Some(stem.to_lowercase()),
+	}
+}
+
+// The HTML page whose path names what `old` does, sharing the most directories with it
+fn likely_page<'a>(old: &str, pages: &[&'a RenderedResource]) -> Option<&'a RenderedResource> {
+	let name = slug(old)?;
+	let dirs: HashSet<&str> = old.split('/').filter(|s| !s.is_empty()).collect();
+	let shared = |page: &RenderedResource| page.paths[0].split('/').filter(|s| dirs.contains(s)).count();
+
+	let mut likely = pages.iter().filter(|page| slug(&page.paths[0]).as_deref() == Some(name.as_str()));
+	let first = likely.next()?;
+	Some(likely.fold(*first, |best, page| if shared(page) > shared(best) { *page } else { best }))
+}
+
+/// Describes every URL that `file` (an old site's sitemap, or a list of URLs or paths, one a
+/// line) has and nothing here serves, redirects from or passes through, with the page whose
+/// frontmatter could redirect it (by `redirect_from:`) where there's a page like it
+pub(crate) fn uncovered(file: &Path, rendered: &[(Resource, RenderedResource)], mount_prefix: &str, passthrough: &[String]) -> Result<Vec<String>, String> {
+	let text = fs::read_to_string(file).map_err(|e| format!("failed to read {}: {e}", file.display()))?;
+	let old = paths(&text).map_err(|e| format!("{}: {e}", file.display()))?;
+
+	let served: HashSet<&str> = rendered.iter().flat_map(|(_, page)| page.paths.iter().chain(&page.redirects)).map(String::as_str).collect();
+	// The pages a `redirect_from:` could go in
+	let pages: Vec<&RenderedResource> = rendered.iter()
+		.filter(|(r, page)| !r.is_virtual() && !r.is_template() && !r.pipeline().is_empty() && page.content_type.starts_with("text/html"))
+		.map(|(_, page)| page)
+		.collect();
+	let mut reported = HashSet::new();
+
+	Ok(old.iter()
+		.filter(|path| !served.contains(path.as_str()) && !passthrough.iter().any(|prefix| path.starts_with(prefix.as_str())) && reported.insert(*path))
+		.map(|path| match path.strip_prefix(mount_prefix).filter(|p| p.starts_with('/')).zip(likely_page(path, &pages)) {
+			Some((unmounted, page)) => format!("{path} isn't served; `redirect_from: [{unmounted}]` in {}'s frontmatter would redirect it to {}", page.source.display(), page.paths[0]),
+			None => format!("{path} isn't served, and no page looks like what it was"),
+		})
+		.collect())
+}
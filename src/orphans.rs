@@ -0,0 +1,27 @@
+use crate::{graph::Graph, RenderedResource, Resource};
+
+// Served whether or not anything links to them
+const WELL_KNOWN: &[&str] = &["/robots.txt", "/favicon.ico", "/humans.txt", "/security.txt"];
+
+/// Describes every page that no link leads to from the root page, and every other file that no page
+/// reached that way uses.  Generated resources (sitemaps, feeds and the like), and files crawlers
+/// look for by name, are left out, and `not_found` (the not-found page's path) counts as a root too.
+pub(crate) fn orphans(rendered: &[(Resource, RenderedResource)], mount_prefix: &str, not_found: Option<&str>) -> Vec<String> {
+	let roots: Vec<String> = [format!("{mount_prefix}/"), format!("{mount_prefix}/index.html")].into_iter().chain(not_found.map(String::from)).collect();
+	let reached = Graph::new(rendered).reached(rendered.iter().enumerate()
+		.filter(|(_, (_, page))| page.paths.iter().chain(&page.redirects).any(|path| roots.contains(path)))
+		.map(|(i, _)| i));
+
+	let exempt = |path: &str| {
+		let path = path.strip_prefix(mount_prefix).unwrap_or(path);
+		WELL_KNOWN.contains(&path) || path.starts_with("/.well-known/")
+	};
+
+	rendered.iter().enumerate()
+		.filter(|(i, (r, page))| !reached.contains(i) && !r.is_virtual() && !page.paths.iter().any(|path| exempt(path)))
+		.map(|(_, (_, page))| match page.content_type.starts_with("text/html") {
+			true => format!("{}: no page links to {}", page.source.display(), page.paths[0]),
+			false => format!("{}: no page uses {}", page.source.display(), page.paths[0]),
+		})
+		.collect()
+}
@@ -0,0 +1,53 @@
+use serde_json::json;
+
+use crate::{feed::first_paragraph, RenderedResource};
+
+fn strip_tags(html: &str) -> String {
+	let mut text = String::new();
+	let mut in_tag = false;
+
+	for c in html.chars() {
+		match c {
+			'<' => in_tag = true,
+			'>' => in_tag = false,
+			c if !in_tag => text.push(c),
+			_ => (),
+		}
+	}
+
+	text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&amp;", "&")
+}
+
+// A page's title, URL, date, tags and summary
+fn entry(page: &RenderedResource) -> serde_json::Value {
+	let setting = |key| page.metadata.as_ref().and_then(|m| m.get(key)).cloned();
+	let summary = setting("summary").and_then(|s| s.as_str().map(str::to_string))
+		.or_else(|| page.content.as_deref().and_then(first_paragraph).map(|p| strip_tags(p).trim().to_string()));
+
+	json!({
+		"url": page.paths[0],
+		"title": page.title(),
+		"date": setting("date"),
+		"tags": setting("tags").unwrap_or(serde_yaml::Value::Sequence(vec![])),
+		"summary": summary,
+	})
+}
+
+/// Title, URL, date, tags and summary of every HTML page served under `section`, for client-side
+/// search and related-content widgets
+pub(crate) fn page_index<'a, I>(section: &str, pages: I) -> Vec<u8> where I: IntoIterator<Item = &'a RenderedResource> {
+	let entries: Vec<_> = pages.into_iter().filter(|page| page.content_type.starts_with("text/html") && page.paths[0].starts_with(section)).map(entry).collect();
+
+	serde_json::to_vec_pretty(&entries).unwrap()
+}
+
+/// Every HTML page, as `pages` in `.tera` templates: what the page index has of each, along with
+/// its frontmatter (as `page`) and rendered content (as `content`)
+pub(crate) fn template_pages<'a, I>(pages: I) -> serde_json::Value where I: IntoIterator<Item = &'a RenderedResource> {
+	pages.into_iter().filter(|page| page.content_type.starts_with("text/html")).map(|page| {
+		let mut entry = entry(page);
+		entry["page"] = serde_json::to_value(&page.metadata).unwrap_or_default();
+		entry["content"] = page.content.clone().into();
+		entry
+	}).collect()
+}
@@ -0,0 +1,74 @@
+use globset::GlobBuilder;
+use std::{io::Write as _, path::{Path, PathBuf}, process::{Command, Stdio}, thread};
+
+use crate::{RenderedResource, Resource};
+
+pub(crate) type PdfRenderer = Box<dyn FnMut(&RenderedResource) -> Result<Vec<u8>, String>>;
+
+// A page's PDF sits beside it, named for it, or `index.pdf` for a directory route
+fn pdf_path(path: &str) -> String {
+	let (dir, name) = path.rsplit_once('/').unwrap_or(("", path));
+	let stem = match name.rsplit_once('.') {
+		Some((stem, _)) if !stem.is_empty() => stem,
+		_ if name.is_empty() => "index",
+		_ => name,
+	};
+
+	format!("{dir}/{stem}.pdf")
+}
+
+// Adds a PDF of every HTML page whose source (relative to `base_path`) matches `glob`
+pub(crate) fn add(rendered: &mut Vec<(Resource, RenderedResource)>, base_path: &Path, glob: &str, render: &mut PdfRenderer) -> Result<(), String> {
+	let matcher = GlobBuilder::new(glob).literal_separator(true).build().map_err(|e| format!("invalid glob {glob:?}: {e}"))?.compile_matcher();
+	let mut pdfs = vec![];
+
+	for (r, page) in rendered.iter().filter(|(r, page)| !r.is_virtual() && page.content_type.starts_with("text/html")) {
+		if !r.source.strip_prefix(base_path).is_ok_and(|source| matcher.is_match(source)) {
+			continue;
+		}
+
+		let path = pdf_path(&page.paths[0]);
+		let body = render(page).map_err(|e| format!("failed to render {} as PDF: {e}", r.source.display()))?;
+		let r = Resource::from_bytes(PathBuf::from(&path), body);
+		let mut pdf = r.render()?;
+		pdf.paths = vec![path];
+		pdfs.push((r, pdf));
+	}
+
+	if pdfs.is_empty() {
+		println!("cargo::warning=static-builder: no page matches PDF glob {glob:?}");
+	}
+
+	for (r, pdf) in pdfs {
+		if rendered.iter().any(|(_, page)| page.paths.contains(&pdf.paths[0])) {
+			return Err(format!("{} would be served over another resource", pdf.paths[0]));
+		}
+		rendered.push((r, pdf));
+	}
+
+	Ok(())
+}
+
+/// A renderer for `Builder::pdf` that runs `program` with `args` for each page, writing the page's
+/// HTML to its standard input and reading the PDF from its standard output, e.g.
+/// `pdf_command("weasyprint", ["--base-url", "content/", "-", "-"])`
+pub fn pdf_command<P, I, S>(program: P, args: I) -> impl FnMut(&RenderedResource) -> Result<Vec<u8>, String> where P: Into<String>, I: IntoIterator<Item = S>, S: Into<String> {
+	let program = program.into();
+	let args: Vec<String> = args.into_iter().map(Into::into).collect();
+
+	move |page| {
+		let mut child = Command::new(&program).args(&args).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().map_err(|e| format!("failed to run {program}: {e}"))?;
+		let mut stdin = child.stdin.take().ok_or_else(|| format!("no stdin for {program}"))?;
+		let html = page.body.clone();
+		// Written from a thread of its own, so a renderer writing output as it reads can't deadlock
+		let writer = thread::spawn(move || stdin.write_all(&html));
+		let output = child.wait_with_output().map_err(|e| format!("failed to run {program}: {e}"))?;
+		writer.join().map_err(|_| format!("failed to write to {program}"))?.map_err(|e| format!("failed to write to {program}: {e}"))?;
+
+		if !output.status.success() {
+			return Err(format!("{program} exited with {}", output.status));
+		}
+
+		Ok(output.stdout)
+	}
+}
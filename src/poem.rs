@@ -0,0 +1,288 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+
+use crate::{codegen::{filtered_paths, path_checks, route_table, set_tables, Options, SetTables}, RenderedResource, Resource};
+
+// Runs before responding to `path` from `routes`: in dev builds, renders it on tokio's blocking pool
+fn prerender(routes: TokenStream, options: &Options<'_>) -> TokenStream {
+	if options.dev {
+		quote! {
+			let rendered = path.clone();
+			let _ = ::tokio::task::spawn_blocking(move || ::static_builder::serve::prerender(#routes, &rendered)).await;
+		}
+	} else {
+		quote! {}
+	}
+}
+
+// `Endpoint` for `name`, serving whatever `routes` has and 404ing the rest, and a `route()` with an
+// entry for each of its paths
+fn endpoint_impls(name: &str, routes: TokenStream, select: TokenStream, options: &Options<'_>) -> TokenStream {
+	let ty = format_ident!("{name}");
+	let prerender = prerender(quote! { routes }, options);
+
+	quote! {
+		impl ::poem::Endpoint for #ty {
+			type Output = ::poem::Response;
+
+			async fn call(&self, req: ::poem::Request) -> ::poem::Result<Self::Output> {
+				#select
+				let routes = #routes;
+				let path = req.uri().path().to_owned();
+
+				if StaticContent::passes_through(&path) || ::static_builder::serve::route(routes, &path).is_none() {
+					return Err(::poem::error::NotFoundError.into());
+				}
+				#prerender
+
+				Ok(static_content_http_response(static_content_respond(routes, req.method().as_str(), &path, |name| req.header(name))))
+			}
+		}
+
+		#[allow(dead_code)]
+		impl #ty {
+			/// A route for every path served here, for `Route::nest("/", ...)`; the endpoint
+			/// itself also serves as a catch-all
+			pub(crate) fn route(self) -> ::poem::Route {
+				let mut route = ::poem::Route::new();
+
+				for path in Self::PATHS {
+					route = route.at(*path, self.clone());
+				}
+
+				route
+			}
+		}
+	}
+}
+
+fn content_sets(default_paths: &[String], sets: &[(&str, Vec<(Resource, RenderedResource)>)], options: &Options<'_>) -> Result<TokenStream, String> {
+	let SetTables { tables, routes_fn, names, paths } = set_tables(default_paths, sets, options)?;
+	let all_paths = paths.into_iter();
+	let impls = endpoint_impls("StaticContentSets", quote! { Self::routes(set.as_deref()) }, quote! { let set = (self.select)(&req); }, options);
+
+	Ok(quote! {
+		#[derive(Clone)]
+		pub(crate) struct StaticContentSets {
+			select: ::std::sync::Arc<dyn Fn(&::poem::Request) -> Option<String> + Send + Sync>,
+		}
+
+		#[allow(dead_code)]
+		impl StaticContentSets {
+			pub(crate) const NAMES: &'static [&'static str] = &[#(#names),*];
+			pub(crate) const PATHS: &'static [&'static str] = &[#(#all_paths),*];
+
+			/// `select` names the content set to serve each request from; `None`, or a name that
+			/// isn't in `NAMES`, gets the default set (the one `StaticContent` serves)
+			pub(crate) fn new<F>(select: F) -> Self where F: Fn(&::poem::Request) -> Option<String> + Send + Sync + 'static {
+				StaticContentSets { select: ::std::sync::Arc::new(select) }
+			}
+
+			/// Always serves the named set, e.g. one chosen from configuration at startup
+			pub(crate) fn fixed<S>(name: S) -> Self where S: Into<String> {
+				let name = name.into();
+				Self::new(move |_| Some(name.clone()))
+			}
+
+			/// Serves the set named by the request's `Host`, less any port
+			pub(crate) fn by_host() -> Self {
+				Self::new(|req| {
+					let host = req.header(::poem::http::header::HOST)?.to_ascii_lowercase();
+
+					Some(match host.rfind(':') {
+						Some(i) if !host[i..].contains(']') => host[..i].to_string(),
+						_ => host,
+					})
+				})
+			}
+
+			#routes_fn
+		}
+
+		#impls
+
+		#tables
+	})
+}
+
+// Checks of the HTTP semantics every route should have, run with the application's own tests
+fn http_tests(rendered: &[(Resource, RenderedResource)], options: &Options<'_>) -> TokenStream {
+	let filtered = filtered_paths(rendered);
+	let not_allowed = match options.allows("POST") {
+		true => quote! {},
+		false => quote! {
+			#[::tokio::test]
+			async fn other_methods_are_not_allowed() {
+				let client = TestClient::new(StaticContent.route());
+
+				for path in paths() {
+					let res = client.post(*path).send().await;
+					assert_eq!(res.0.status(), StatusCode::METHOD_NOT_ALLOWED, "POST {path}");
+					assert!(res.0.headers().contains_key(header::ALLOW), "POST {path}");
+				}
+			}
+		},
+	};
+	let options_test = match options.allows("OPTIONS") {
+		true => quote! {
+			#[::tokio::test]
+			async fn options_lists_the_allowed_methods() {
+				let client = TestClient::new(StaticContent.route());
+
+				for path in paths() {
+					let res = client.request(Method::OPTIONS, *path).send().await;
+					assert_eq!(res.0.status(), StatusCode::NO_CONTENT, "OPTIONS {path}");
+					assert!(res.0.headers().contains_key(header::ALLOW), "OPTIONS {path}");
+				}
+			}
+		},
+		false => quote! {},
+	};
+	let redirects: Vec<_> = rendered.iter().flat_map(|(_, page)| page.redirects.iter().map(|r| (r, &page.paths[0]))).collect();
+	let (redirect_from, redirect_to): (Vec<_>, Vec<_>) = redirects.into_iter().unzip();
+
+	quote! {
+		#[cfg(test)]
+		#[allow(clippy::unwrap_used, clippy::panic)]
+		mod static_content_http_tests {
+			use super::StaticContent;
+			use ::poem::{http::{header, Method, StatusCode}, test::TestClient};
+
+			const FILTERED: &[&str] = &[#(#filtered),*];
+
+			fn paths() -> impl Iterator<Item = &'static &'static str> {
+				StaticContent::PATHS.iter().filter(|path| !FILTERED.contains(path))
+			}
+
+			const REDIRECTS: &[(&str, &str)] = &[#((#redirect_from, #redirect_to)),*];
+
+			#[::tokio::test]
+			async fn every_route_answers_get_and_head() {
+				let client = TestClient::new(StaticContent.route());
+
+				for path in paths() {
+					for method in [Method::GET, Method::HEAD] {
+						let res = client.request(method.clone(), *path).send().await;
+						assert!(res.0.status() == StatusCode::OK || res.0.status() == StatusCode::PERMANENT_REDIRECT, "{method} {path}: {}", res.0.status());
+					}
+				}
+			}
+
+			#not_allowed
+
+			#options_test
+
+			#[::tokio::test]
+			async fn redirects_point_at_their_canonical_route() {
+				let client = TestClient::new(StaticContent.route());
+
+				for (from, to) in REDIRECTS {
+					let res = client.get(*from).send().await;
+					assert_eq!(res.0.status(), StatusCode::PERMANENT_REDIRECT, "GET {from}");
+					assert_eq!(res.0.headers().get(header::LOCATION).unwrap(), to, "GET {from}");
+				}
+			}
+
+			#[::tokio::test]
+			async fn matching_etags_are_not_modified() {
+				let client = TestClient::new(StaticContent.route());
+
+				for path in paths() {
+					let res = client.get(*path).send().await;
+					let Some(etag) = res.0.headers().get(header::ETAG).cloned() else { continue };
+					let res = client.get(*path).header(header::IF_NONE_MATCH, etag.clone()).send().await;
+					assert_eq!(res.0.status(), StatusCode::NOT_MODIFIED, "GET {path}");
+					assert_eq!(res.0.headers().get(header::ETAG), Some(&etag), "GET {path}");
+				}
+			}
+
+			#[::tokio::test]
+			async fn unchanged_routes_are_not_modified_since() {
+				let client = TestClient::new(StaticContent.route());
+
+				for path in paths() {
+					let res = client.get(*path).send().await;
+					let Some(modified) = res.0.headers().get(header::LAST_MODIFIED).cloned() else { continue };
+					let res = client.get(*path).header(header::IF_MODIFIED_SINCE, modified).send().await;
+					assert_eq!(res.0.status(), StatusCode::NOT_MODIFIED, "GET {path}");
+				}
+			}
+
+			#[::tokio::test]
+			async fn ranges_are_partial_content() {
+				let client = TestClient::new(StaticContent.route());
+
+				for path in paths() {
+					let res = client.get(*path).header(header::RANGE, "bytes=0-0").send().await;
+					if res.0.status() == StatusCode::PERMANENT_REDIRECT { continue }
+					assert!(res.0.status() == StatusCode::PARTIAL_CONTENT || res.0.status() == StatusCode::RANGE_NOT_SATISFIABLE, "GET {path}: {}", res.0.status());
+					assert!(res.0.headers().contains_key(header::CONTENT_RANGE), "GET {path}");
+				}
+			}
+
+			#[::tokio::test]
+			async fn unknown_paths_are_not_found() {
+				let res = TestClient::new(StaticContent).get("/static-builder-no-such-route").send().await;
+
+				assert_eq!(res.0.status(), StatusCode::NOT_FOUND);
+			}
+		}
+	}
+}
+
+pub(crate) fn poem_module(rendered: &[(Resource, RenderedResource)], sets: &[(&str, Vec<(Resource, RenderedResource)>)], options: &Options<'_>) -> Result<TokenStream, String> {
+	let (routes, resource_paths) = route_table("STATIC_CONTENT_ROUTES", rendered, options)?;
+	let passthrough = options.passthrough;
+	let panic_lints = options.panic_lints();
+	let respond = options.respond_fn();
+	let impls = endpoint_impls("StaticContent", quote! { STATIC_CONTENT_ROUTES }, quote! {}, options);
+	let sets = if sets.is_empty() { quote! {} } else { content_sets(&resource_paths, sets, options)? };
+	let bodies = options.body_statics()?;
+	let tests = if options.tests { http_tests(rendered, options) } else { quote! {} };
+	let path_checks = path_checks(options);
+
+	Ok(quote! {
+		#[derive(Clone)]
+		pub(crate) struct StaticContent;
+
+		impl StaticContent {
+			pub(crate) const PATHS: &'static [&'static str] = &[#(#resource_paths),*];
+			/// Path prefixes never handled here, for another endpoint to respond to
+			pub(crate) const PASSTHROUGH: &'static [&'static str] = &[#(#passthrough),*];
+
+			pub(crate) fn passes_through(path: &str) -> bool {
+				Self::PASSTHROUGH.iter().any(|prefix| path.starts_with(prefix))
+			}
+		}
+
+		#impls
+
+		// What's served is all decided by `static_builder::serve`; this only hands it to poem
+		#panic_lints
+		fn static_content_http_response(res: ::static_builder::serve::Response) -> ::poem::Response {
+			let status = ::poem::http::StatusCode::from_u16(res.status).unwrap_or(::poem::http::StatusCode::INTERNAL_SERVER_ERROR);
+			let mut builder = ::poem::Response::builder().status(status);
+
+			for (name, value) in res.headers {
+				builder = builder.header(name, value.into_owned());
+			}
+
+			match res.body {
+				::std::borrow::Cow::Borrowed(body) => builder.body(body),
+				::std::borrow::Cow::Owned(body) => builder.body(body),
+			}
+		}
+
+		#respond
+
+		#routes
+
+		#sets
+
+		#bodies
+
+		#path_checks
+
+		#tests
+	})
+}
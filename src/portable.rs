@@ -0,0 +1,94 @@
+use crate::{assets::fnv1a, RenderedResource, Resource};
+
+// Device names Windows reserves whatever the extension
+const RESERVED: &[&str] = &[
+	"con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8", "com9",
+	"lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+// What the common filesystems (and object stores' keys) allow, in bytes
+const MAX_SEGMENT: usize = 255;
+const MAX_PATH: usize = 260;
+
+fn reserved(segment: &str) -> bool {
+	RESERVED.contains(&segment.split('.').next().unwrap_or_default().to_ascii_lowercase().as_str())
+}
+
+fn invalid(c: char) -> bool {
+	c.is_control() || "<>:\"\\|?*".contains(c)
+}
+
+// Why `path` can't be written out as a file everywhere, if it can't
+fn problem(path: &str) -> Option<String> {
+	if path.len() > MAX_PATH {
+		return Some(format!("is longer than {MAX_PATH} bytes"));
+	}
+
+	path.split('/').find_map(|segment| {
+		if reserved(segment) {
+			Some(format!("has {segment:?}, a name reserved on Windows"))
+		} else if segment.ends_with(['.', ' ']) {
+			Some(format!("has {segment:?}, which ends with a dot or space"))
+		} else if segment.contains(invalid) {
+			Some(format!("has {segment:?}, with characters Windows doesn't allow"))
+		} else if segment.len() > MAX_SEGMENT {
+			Some(format!("has a segment longer than {MAX_SEGMENT} bytes"))
+		} else {
+			None
+		}
+	})
+}
+
+fn rename_segment(segment: &str) -> String {
+	let mut segment: String = segment.chars().map(|c| if invalid(c) { '_' } else { c }).collect();
+
+	if reserved(&segment) {
+		let stem = segment.split('.').next().unwrap_or_default().len();
+		segment.insert(stem, '_');
+	}
+
+	if segment.ends_with(['.', ' ']) {
+		segment = format!("{}_", segment.trim_end_matches(['.', ' ']));
+	}
+
+	if segment.len() > MAX_SEGMENT {
+		let hash = format!("-{:016x}", fnv1a(segment.as_bytes()));
+		let end = (0..=MAX_SEGMENT - hash.len()).rev().find(|i| segment.is_char_boundary(*i)).unwrap_or_default();
+		segment = format!("{}{hash}", &segment[..end]);
+	}
+
+	segment
+}
+
+fn rename(path: &str) -> String {
+	path.split('/').map(rename_segment).collect::<Vec<_>>().join("/")
+}
+
+/// Checks that every path served can be a file name on any platform (so that the content can be
+/// exported, or synced to a CDN): without `rename`, failing on any that can't, and with it,
+/// renaming them (as a warning, since links to them won't follow) where that's enough
+pub(crate) fn check(rendered: &mut [(Resource, RenderedResource)], rename_paths: bool) -> Result<(), String> {
+	let mut errors = vec![];
+
+	for (_, page) in rendered.iter_mut() {
+		let source = page.source.display().to_string();
+
+		for path in page.paths.iter_mut().chain(page.redirects.iter_mut()) {
+			let Some(problem) = problem(path) else { continue };
+
+			match rename_paths.then(|| rename(path)).filter(|renamed| self::problem(renamed).is_none()) {
+				Some(renamed) => {
+					println!("cargo::warning={source}: {path} {problem}; serving it as {renamed} instead");
+					*path = renamed;
+				},
+				None => errors.push(format!("{source}: {path} {problem}")),
+			}
+		}
+	}
+
+	if errors.is_empty() {
+		Ok(())
+	} else {
+		Err(format!("paths that can't be exported:\n{}", errors.join("\n")))
+	}
+}
@@ -0,0 +1,22 @@
+use std::{env, path::Path};
+
+/// The name of the cargo profile being built, which (unlike `PROFILE`, only ever `debug` or
+/// `release`) tells custom profiles like `release-lto` apart.  Only meaningful in build scripts.
+pub fn build_profile() -> String {
+	// `OUT_DIR` is `<target dir>/[<target triple>/]<profile>/build/<package>-<hash>/out`
+	env::var_os("OUT_DIR")
+		.and_then(|out_dir| {
+			Path::new(&out_dir).ancestors()
+				.find(|p| p.file_name().is_some_and(|n| n == "build"))
+				.and_then(Path::parent)
+				.and_then(Path::file_name)
+				.map(|n| n.to_string_lossy().into_owned())
+		})
+		.or_else(|| env::var("PROFILE").ok())
+		.unwrap_or_default()
+}
+
+/// The target triple being built for, in build scripts
+pub fn build_target() -> String {
+	env::var("TARGET").unwrap_or_default()
+}
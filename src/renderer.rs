@@ -0,0 +1,16 @@
+use std::{collections::BTreeMap, path::Path};
+
+/// A converter for a source format of the application's own (org-mode, say), registered for an
+/// extension with `Builder::renderer`.  Its HTML goes through the page's layout as djot's does,
+/// frontmatter and all.
+pub trait ContentRenderer {
+	/// The HTML of the page rendered from `source`, whose `content` is less any frontmatter, and
+	/// any fields for templates (as `page.fields`) and `Builder::fields_index`
+	fn render(&self, source: &Path, content: &str) -> Result<(String, BTreeMap<String, String>), String>;
+}
+
+impl<F> ContentRenderer for F where F: Fn(&Path, &str) -> Result<(String, BTreeMap<String, String>), String> {
+	fn render(&self, source: &Path, content: &str) -> Result<(String, BTreeMap<String, String>), String> {
+		self(source, content)
+	}
+}
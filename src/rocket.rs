@@ -0,0 +1,293 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+
+use crate::{codegen::{filtered_paths, path_checks, route_table, set_tables, Options, SetTables}, RenderedResource, Resource};
+
+// Every method is routed, so that the ones `serve` doesn't allow get a 405 rather than a 404
+const METHODS: &[&str] = &["Get", "Head", "Post", "Put", "Delete", "Patch", "Options"];
+
+// Runs before responding to `path` from `routes`: in dev builds, renders it on the blocking pool
+fn prerender(routes: TokenStream, options: &Options<'_>) -> TokenStream {
+	if options.dev {
+		quote! {
+			let routes = #routes;
+			let rendered = path.clone();
+			let _ = ::rocket::tokio::task::spawn_blocking(move || ::static_builder::serve::prerender(routes, &rendered)).await;
+		}
+	} else {
+		quote! {}
+	}
+}
+
+// `Handler` and `Vec<Route>` impls for `name`, so that it can be mounted itself
+fn handler_impls(name: &str, respond: TokenStream, select: TokenStream, prerender: TokenStream) -> TokenStream {
+	let ty = format_ident!("{name}");
+	let methods = METHODS.iter().map(|m| format_ident!("{m}"));
+
+	quote! {
+		#[::rocket::async_trait]
+		impl ::rocket::route::Handler for #ty {
+			async fn handle<'r>(&self, req: &'r ::rocket::Request<'_>, _data: ::rocket::Data<'r>) -> ::rocket::route::Outcome<'r> {
+				#select
+				let path = req.uri().path().as_str().to_owned();
+				#prerender
+
+				::rocket::outcome::Outcome::Success(static_content_http_response(#respond))
+			}
+		}
+
+		impl From<#ty> for Vec<::rocket::Route> {
+			fn from(handler: #ty) -> Self {
+				let mut routes = vec![];
+
+				for path in #ty::PATHS {
+					for method in [#(::rocket::http::Method::#methods),*] {
+						routes.push(::rocket::Route::new(method, path, handler.clone()));
+					}
+				}
+
+				routes
+			}
+		}
+	}
+}
+
+fn content_sets(default_paths: &[String], sets: &[(&str, Vec<(Resource, RenderedResource)>)], options: &Options<'_>) -> Result<TokenStream, String> {
+	let SetTables { tables, routes_fn, names, paths } = set_tables(default_paths, sets, options)?;
+	let all_paths = paths.into_iter();
+	let impls = handler_impls(
+		"StaticContentSets",
+		quote! { static_content_respond(Self::routes(set.as_deref()), req.method().as_str(), &path, |name| req.headers().get_one(name)) },
+		quote! { let set = (self.select)(req); },
+		prerender(quote! { Self::routes(set.as_deref()) }, options),
+	);
+
+	Ok(quote! {
+		#[derive(Clone)]
+		pub(crate) struct StaticContentSets {
+			select: ::std::sync::Arc<dyn Fn(&::rocket::Request<'_>) -> Option<String> + Send + Sync>,
+		}
+
+		#[allow(dead_code)]
+		impl StaticContentSets {
+			pub(crate) const NAMES: &'static [&'static str] = &[#(#names),*];
+			pub(crate) const PATHS: &'static [&'static str] = &[#(#all_paths),*];
+
+			/// `select` names the content set to serve each request from; `None`, or a name that
+			/// isn't in `NAMES`, gets the default set (the one `StaticContent` serves)
+			pub(crate) fn new<F>(select: F) -> Self where F: Fn(&::rocket::Request<'_>) -> Option<String> + Send + Sync + 'static {
+				StaticContentSets { select: ::std::sync::Arc::new(select) }
+			}
+
+			/// Always serves the named set, e.g. one chosen from configuration at startup
+			pub(crate) fn fixed<S>(name: S) -> Self where S: Into<String> {
+				let name = name.into();
+				Self::new(move |_| Some(name.clone()))
+			}
+
+			/// Serves the set named by the request's `Host`, less any port
+			pub(crate) fn by_host() -> Self {
+				Self::new(|req| req.host().map(|host| host.domain().as_str().to_ascii_lowercase()))
+			}
+
+			#routes_fn
+		}
+
+		#impls
+
+		#tables
+	})
+}
+
+// Checks of the HTTP semantics every route should have, run with the application's own tests
+fn http_tests(rendered: &[(Resource, RenderedResource)], options: &Options<'_>) -> TokenStream {
+	let filtered = filtered_paths(rendered);
+	let not_allowed = match options.allows("POST") {
+		true => quote! {},
+		false => quote! {
+			#[test]
+			fn other_methods_are_not_allowed() {
+				let client = client();
+
+				for path in paths() {
+					let res = client.post(*path).dispatch();
+					assert_eq!(res.status(), Status::MethodNotAllowed, "POST {path}");
+					assert!(res.headers().contains("allow"), "POST {path}");
+				}
+			}
+		},
+	};
+	let options_test = match options.allows("OPTIONS") {
+		true => quote! {
+			#[test]
+			fn options_lists_the_allowed_methods() {
+				let client = client();
+
+				for path in paths() {
+					let res = client.req(Method::Options, *path).dispatch();
+					assert_eq!(res.status(), Status::NoContent, "OPTIONS {path}");
+					assert!(res.headers().contains("allow"), "OPTIONS {path}");
+				}
+			}
+		},
+		false => quote! {},
+	};
+	let redirects: Vec<_> = rendered.iter().flat_map(|(_, page)| page.redirects.iter().map(|r| (r, &page.paths[0]))).collect();
+	let (redirect_from, redirect_to): (Vec<_>, Vec<_>) = redirects.into_iter().unzip();
+
+	quote! {
+		#[cfg(test)]
+		#[allow(clippy::unwrap_used, clippy::panic)]
+		mod static_content_http_tests {
+			use super::StaticContent;
+			use ::rocket::{http::{Header, Method, Status}, local::blocking::Client};
+
+			const FILTERED: &[&str] = &[#(#filtered),*];
+
+			fn paths() -> impl Iterator<Item = &'static &'static str> {
+				StaticContent::PATHS.iter().filter(|path| !FILTERED.contains(path))
+			}
+
+			const REDIRECTS: &[(&str, &str)] = &[#((#redirect_from, #redirect_to)),*];
+
+			fn client() -> Client {
+				Client::tracked(::rocket::build().mount("/", StaticContent)).unwrap()
+			}
+
+			#[test]
+			fn every_route_answers_get_and_head() {
+				let client = client();
+
+				for path in paths() {
+					for method in [Method::Get, Method::Head] {
+						let res = client.req(method, *path).dispatch();
+						assert!(res.status() == Status::Ok || res.status() == Status::PermanentRedirect, "{method} {path}: {}", res.status());
+					}
+				}
+			}
+
+			#not_allowed
+
+			#options_test
+
+			#[test]
+			fn redirects_point_at_their_canonical_route() {
+				let client = client();
+
+				for (from, to) in REDIRECTS {
+					let res = client.get(*from).dispatch();
+					assert_eq!(res.status(), Status::PermanentRedirect, "GET {from}");
+					assert_eq!(res.headers().get_one("location"), Some(*to), "GET {from}");
+				}
+			}
+
+			#[test]
+			fn matching_etags_are_not_modified() {
+				let client = client();
+
+				for path in paths() {
+					let res = client.get(*path).dispatch();
+					let Some(etag) = res.headers().get_one("etag").map(String::from) else { continue };
+					let res = client.get(*path).header(Header::new("if-none-match", etag.clone())).dispatch();
+					assert_eq!(res.status(), Status::NotModified, "GET {path}");
+					assert_eq!(res.headers().get_one("etag"), Some(etag.as_str()), "GET {path}");
+				}
+			}
+
+			#[test]
+			fn unchanged_routes_are_not_modified_since() {
+				let client = client();
+
+				for path in paths() {
+					let res = client.get(*path).dispatch();
+					let Some(modified) = res.headers().get_one("last-modified").map(String::from) else { continue };
+					let res = client.get(*path).header(Header::new("if-modified-since", modified)).dispatch();
+					assert_eq!(res.status(), Status::NotModified, "GET {path}");
+				}
+			}
+
+			#[test]
+			fn ranges_are_partial_content() {
+				let client = client();
+
+				for path in paths() {
+					let res = client.get(*path).header(Header::new("range", "bytes=0-0")).dispatch();
+					if res.status() == Status::PermanentRedirect { continue }
+					assert!(res.status() == Status::PartialContent || res.status() == Status::RangeNotSatisfiable, "GET {path}: {}", res.status());
+					assert!(res.headers().contains("content-range"), "GET {path}");
+				}
+			}
+
+			#[test]
+			fn unknown_paths_are_not_found() {
+				let res = client().get("/static-builder-no-such-route").dispatch();
+
+				assert_eq!(res.status(), Status::NotFound);
+			}
+		}
+	}
+}
+
+pub(crate) fn rocket_module(rendered: &[(Resource, RenderedResource)], sets: &[(&str, Vec<(Resource, RenderedResource)>)], options: &Options<'_>) -> Result<TokenStream, String> {
+	let (routes, resource_paths) = route_table("STATIC_CONTENT_ROUTES", rendered, options)?;
+	let passthrough = options.passthrough;
+	let panic_lints = options.panic_lints();
+	let respond = options.respond_fn();
+	let impls = handler_impls(
+		"StaticContent",
+		quote! { static_content_respond(STATIC_CONTENT_ROUTES, req.method().as_str(), &path, |name| req.headers().get_one(name)) },
+		quote! {},
+		prerender(quote! { STATIC_CONTENT_ROUTES }, options),
+	);
+	let sets = if sets.is_empty() { quote! {} } else { content_sets(&resource_paths, sets, options)? };
+	let bodies = options.body_statics()?;
+	let tests = if options.tests { http_tests(rendered, options) } else { quote! {} };
+	let path_checks = path_checks(options);
+
+	Ok(quote! {
+		/// Mounted with `rocket.mount("/", StaticContent)`
+		#[derive(Clone)]
+		pub(crate) struct StaticContent;
+
+		impl StaticContent {
+			pub(crate) const PATHS: &'static [&'static str] = &[#(#resource_paths),*];
+			/// Path prefixes never handled here, for another route to respond to
+			pub(crate) const PASSTHROUGH: &'static [&'static str] = &[#(#passthrough),*];
+
+			#[allow(dead_code)]
+			pub(crate) fn passes_through(path: &str) -> bool {
+				Self::PASSTHROUGH.iter().any(|prefix| path.starts_with(prefix))
+			}
+		}
+
+		#impls
+
+		// What's served is all decided by `static_builder::serve`; this only hands it to rocket
+		#panic_lints
+		fn static_content_http_response(res: ::static_builder::serve::Response) -> ::rocket::Response<'static> {
+			let mut builder = ::rocket::Response::build();
+			builder.status(::rocket::http::Status::new(res.status));
+			// Rocket strips HEAD responses' bodies itself, keeping the length they're sized at
+			let length = res.headers.iter().find(|(name, _)| *name == "content-length").and_then(|(_, length)| length.parse().ok()).unwrap_or(res.body.len());
+
+			for (name, value) in res.headers.into_iter().filter(|(name, _)| *name != "content-length") {
+				builder.raw_header(name, value);
+			}
+
+			builder.sized_body(length, ::std::io::Cursor::new(res.body));
+			builder.finalize()
+		}
+
+		#respond
+
+		#routes
+
+		#sets
+
+		#bodies
+
+		#path_checks
+
+		#tests
+	})
+}
@@ -0,0 +1,83 @@
+use std::{cell::RefCell, fs, io, path::{Path, PathBuf}};
+
+// The files a stylesheet reads as it's compiled, but for the stylesheet itself, whose text may be
+// what earlier stages made of it
+#[derive(Debug)]
+struct Loading<'a> {
+	source: &'a Path,
+	text: &'a str,
+	loaded: RefCell<Vec<PathBuf>>,
+}
+
+impl grass::Fs for Loading<'_> {
+	fn is_dir(&self, path: &Path) -> bool {
+		path.is_dir()
+	}
+
+	fn is_file(&self, path: &Path) -> bool {
+		path == self.source || path.is_file()
+	}
+
+	fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+		if path == self.source {
+			return Ok(self.text.as_bytes().to_vec());
+		}
+		let mut loaded = self.loaded.borrow_mut();
+		if !loaded.iter().any(|l| l == path) {
+			loaded.push(path.to_path_buf());
+		}
+
+		fs::read(path)
+	}
+
+	fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+		fs::canonicalize(path)
+	}
+}
+
+/// The CSS a Sass stylesheet (SCSS, or the indented syntax if `source` is a `.sass` file) compiles
+/// to, and every file it loaded with `@use`, `@forward` or `@import` on the way, which are found
+/// relative to the file loading them (as `_partial.scss`, `partial.scss` or `partial/_index.scss`)
+pub(crate) fn compile(source: &Path, text: &str) -> Result<(String, Vec<PathBuf>), String> {
+	let loading = Loading { source, text, loaded: RefCell::new(vec![]) };
+	let css = grass::from_path(source, &grass::Options::default().fs(&loading)).map_err(|e| e.to_string())?;
+
+	Ok((css, loading.loaded.into_inner()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::compile;
+	use std::{env, fs, path::Path};
+
+	#[test]
+	fn compiles_what_it_is_given_and_tracks_what_it_loads() {
+		let dir = env::temp_dir().join(format!("static-builder-sass-{}", std::process::id()));
+		fs::create_dir_all(dir.join("theme")).unwrap();
+		fs::write(dir.join("_colors.scss"), "$red: #c00;\n").unwrap();
+		fs::write(dir.join("theme/_index.scss"), "@use \"../colors\";\n.theme { color: colors.$red; }\n").unwrap();
+		let source = dir.join("site.scss");
+		fs::write(&source, "not what's compiled").unwrap();
+
+		let (css, loaded) = compile(&source, "@use \"colors\";\n@use \"theme\";\na { b { color: colors.$red; } }\n").unwrap();
+		let _ = fs::remove_dir_all(&dir);
+
+		assert_eq!(css, ".theme {\n  color: #c00;\n}\n\na b {\n  color: #c00;\n}\n");
+		let names: Vec<_> = loaded.iter().map(|l| l.strip_prefix(&dir).unwrap_or(l)).collect();
+		assert_eq!(names, [Path::new("_colors.scss"), Path::new("theme/_index.scss")]);
+	}
+
+	#[test]
+	fn indented_syntax_by_extension() {
+		let (css, loaded) = compile(Path::new("site.sass"), "a\n  color: red\n").unwrap();
+
+		assert_eq!(css, "a {\n  color: red;\n}\n");
+		assert!(loaded.is_empty());
+	}
+
+	#[test]
+	fn errors() {
+		assert!(compile(Path::new("site.scss"), "a { color: $missing; }").is_err());
+		assert!(compile(Path::new("site.scss"), "@use \"missing\";").is_err());
+	}
+}
@@ -0,0 +1,54 @@
+use crate::sitemap::xml_escape;
+
+pub(crate) fn descriptor(short_name: &str, search_url: &str) -> Vec<u8> {
+	format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<OpenSearchDescription xmlns="http://a9.com/-/spec/opensearch/1.1/">
+	<ShortName>{}</ShortName>
+	<Description>Search {}</Description>
+	<InputEncoding>UTF-8</InputEncoding>
+	<Url type="text/html" method="get" template="{}?q={{searchTerms}}"/>
+</OpenSearchDescription>
+"#, xml_escape(short_name), xml_escape(short_name), xml_escape(search_url)).into_bytes()
+}
+
+// Everything happens client side, against the page index: a page matches when every word of the
+// query appears somewhere in its title, summary or tags
+pub(crate) fn page(short_name: &str, index_url: &str) -> Vec<u8> {
+	format!(r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Search {0}</title>
+</head>
+<body>
+<form><input type="search" name="q" autofocus> <button>Search</button></form>
+<ul id="results"></ul>
+<script>
+(function () {{
+	const q = new URLSearchParams(location.search).get("q") || "";
+	const words = q.toLowerCase().split(/\s+/).filter(w => w);
+	document.querySelector("input[name=q]").value = q;
+	if (!words.length) return;
+
+	fetch({1}).then(r => r.json()).then(pages => {{
+		const results = document.getElementById("results");
+		for (const page of pages) {{
+			const text = [page.title, page.summary, ...(page.tags || [])].join(" ").toLowerCase();
+			if (!words.every(w => text.includes(w))) continue;
+
+			const li = document.createElement("li");
+			const a = document.createElement("a");
+			a.href = page.url;
+			a.textContent = page.title || page.url;
+			li.appendChild(a);
+			if (page.summary) li.appendChild(document.createTextNode(" — " + page.summary));
+			results.appendChild(li);
+		}}
+		if (!results.children.length) results.textContent = "No results.";
+	}});
+}})();
+</script>
+</body>
+</html>
+"#, xml_escape(short_name), serde_json::to_string(index_url).unwrap()).into_bytes()
+}
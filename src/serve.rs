@@ -0,0 +1,1030 @@
+//! Building responses from the route tables in generated code, independently of any web framework,
+//! so that fixes to how content is served only need a new version of this crate.
+
+use std::{borrow::Cow, fs, path::PathBuf, sync::{atomic::{AtomicU64, Ordering}, OnceLock}, time::SystemTime};
+
+use crate::{assets::fnv1a, freshness::days_from_civil, Resource, Rule};
+
+/// Where a route's body comes from.  Generated route tables choose one for each route, by the
+/// `store` its rule names, and the application can implement its own (see `Store::Custom`).
+pub trait BodyStore: Sync {
+	fn load(&self, path: &str) -> Result<Cow<'static, [u8]>, String>;
+
+	/// The length of what `load` would return, without loading it where that can be helped
+	fn len(&self, path: &str) -> Result<u64, String> {
+		self.load(path).map(|body| body.len() as u64)
+	}
+
+	/// Does whatever `load` would block on, ahead of it
+	fn prerender(&self, _path: &str) {}
+}
+
+/// Embedded in the binary as a static of its own
+pub struct Embedded(pub &'static [u8]);
+
+impl BodyStore for Embedded {
+	fn load(&self, _path: &str) -> Result<Cow<'static, [u8]>, String> {
+		Ok(Cow::Borrowed(self.0))
+	}
+
+	fn len(&self, _path: &str) -> Result<u64, String> {
+		Ok(self.0.len() as u64)
+	}
+}
+
+/// A range of one static blob, shared by every body packed into it
+pub struct Packed {
+	pub blob: &'static [u8],
+	pub start: usize,
+	pub end: usize,
+}
+
+impl BodyStore for Packed {
+	fn load(&self, _path: &str) -> Result<Cow<'static, [u8]>, String> {
+		self.blob.get(self.start..self.end).map(Cow::Borrowed).ok_or_else(|| "packed body is out of range".to_string())
+	}
+}
+
+/// Read, on each request, from a file beside the build, which must be this many bytes long
+pub struct External(pub &'static str, pub u64);
+
+impl BodyStore for External {
+	fn load(&self, _path: &str) -> Result<Cow<'static, [u8]>, String> {
+		match fs::read(self.0) {
+			Ok(body) if body.len() as u64 == self.1 => Ok(Cow::Owned(body)),
+			_ => Err(format!("external body {} is missing or changed", self.0)),
+		}
+	}
+
+	fn len(&self, _path: &str) -> Result<u64, String> {
+		match fs::metadata(self.0) {
+			Ok(metadata) if metadata.len() == self.1 => Ok(self.1),
+			_ => Err(format!("external body {} is missing or changed", self.0)),
+		}
+	}
+}
+
+/// Rendered, in dev builds, from its source with the (JSON) rule that applies to it
+pub struct Rendered {
+	pub source: &'static str,
+	pub rule: &'static str,
+}
+
+impl BodyStore for Rendered {
+	fn load(&self, path: &str) -> Result<Cow<'static, [u8]>, String> {
+		let rule: Rule = self.rule.parse().map_err(|e| format!("invalid rule for {}: {e}", self.source))?;
+
+		Resource::new(PathBuf::from(self.source), PathBuf::from(path)).with_rule(rule).try_cached_content().map(Cow::Owned)
+	}
+
+	fn prerender(&self, path: &str) {
+		let _ = self.load(path);
+	}
+}
+
+pub enum Serve {
+	/// `headers` are any besides `content-type`, with lowercase names; `encodings` are the body
+	/// precompressed, by content coding, smallest first; `etag` is the body's hash, unless it's
+	/// rendered on request and so hashed then; `last_modified` is in seconds since the Unix epoch;
+	/// `formats` are the content in other media types, smallest first, for requests that accept them;
+	/// `filters` name those the body goes through as it's served (see `add_filter`)
+	Content {
+		media_type: &'static str,
+		headers: &'static [(&'static str, &'static str)],
+		body: &'static dyn BodyStore,
+		encodings: &'static [(&'static str, &'static dyn BodyStore)],
+		etag: Option<&'static str>,
+		last_modified: Option<u64>,
+		formats: &'static [(&'static str, &'static Serve)],
+		filters: &'static [&'static str],
+	},
+	/// A permanent redirect to the canonical path
+	Redirect(&'static str),
+	/// Every route under this one's path (a directory), in a table of their own
+	Shard(&'static [Route]),
+	/// Whatever the window the server's clock is in serves, or else `otherwise`, chosen on each
+	/// request (see `Builder::schedule`)
+	Scheduled {
+		windows: &'static [Window],
+		otherwise: &'static Serve,
+	},
+}
+
+/// What a scheduled route serves from `from` until `until`, in seconds since the Unix epoch
+pub struct Window {
+	pub from: u64,
+	pub until: u64,
+	pub serve: Serve,
+}
+
+/// One entry in a generated route table, which is sorted by path
+pub struct Route {
+	pub path: &'static str,
+	pub serve: Serve,
+}
+
+/// A response for the framework glue in generated code to pass on as is
+#[derive(Debug)]
+pub struct Response {
+	pub status: u16,
+	/// Header names are lowercase, and in order by name (a name's values in the order they apply),
+	/// so the same request of the same build always gets the same headers in the same order.  Only
+	/// HEAD responses have a `content-length`, the length of the body a GET would get, which the
+	/// glue has to see the framework sends rather than the empty body's.  The glue for each backend
+	/// hands the headers on in this order, which all but actix-web keep (see `Backend::Actix`)
+	pub headers: Vec<(&'static str, Cow<'static, str>)>,
+	pub body: Cow<'static, [u8]>,
+}
+
+impl Response {
+	fn status(status: u16) -> Self {
+		Response { status, headers: vec![], body: Cow::Borrowed(&[]) }
+	}
+}
+
+/// A request for a path nothing serves, as `on_not_found` hooks see it
+pub struct NotFound<'a> {
+	pub method: &'a str,
+	pub path: &'a str,
+	pub referer: Option<&'a str>,
+	pub user_agent: Option<&'a str>,
+}
+
+type NotFoundHook = Box<dyn Fn(&NotFound<'_>) + Send + Sync>;
+static NOT_FOUND_HOOK: OnceLock<NotFoundHook> = OnceLock::new();
+
+/// Has `hook` called with every request `respond` (or any of its variants) 404s for want of a
+/// route, e.g. to track broken inbound links.  Only one hook can be registered, once; without one,
+/// a 404 costs nothing more (and allocates nothing) than it would anyway.
+pub fn on_not_found<F>(hook: F) -> Result<(), String> where F: Fn(&NotFound<'_>) + Send + Sync + 'static {
+	NOT_FOUND_HOOK.set(Box::new(hook)).map_err(|_| "a not-found hook is already registered".to_string())
+}
+
+fn not_found<'a, H>(method: &str, path: &str, header: H) -> Response where H: Fn(&str) -> Option<&'a str> {
+	if let Some(hook) = NOT_FOUND_HOOK.get() {
+		hook(&NotFound { method, path, referer: header("referer"), user_agent: header("user-agent") });
+	}
+
+	Response::status(404)
+}
+
+/// A response `respond` (or any of its variants) gave, as `on_access` hooks see it
+pub struct Access<'a> {
+	pub method: &'a str,
+	pub path: &'a str,
+	pub status: u16,
+	/// The length of the body sent, which for a HEAD request is none
+	pub bytes: usize,
+	pub referer: Option<&'a str>,
+	pub user_agent: Option<&'a str>,
+	/// The client, as the first address in the request's `X-Forwarded-For` (the only one the
+	/// headers have), if any
+	pub client: Option<&'a str>,
+	/// Seconds since the Unix epoch
+	pub time: u64,
+}
+
+impl Access<'_> {
+	/// The response as a line of the combined log format, with `-` for what isn't known (the
+	/// client, without an `X-Forwarded-For`, and who they are) and `HTTP/1.1` for the protocol
+	pub fn combined(&self) -> String {
+		let (year, month, day) = civil_from_days((self.time / 86400) as i64);
+		let secs = self.time % 86400;
+		let quoted = |value: Option<&str>| value.map_or("-".to_string(), |v| v.replace('\\', "\\\\").replace('"', "\\\""));
+		let bytes = if self.bytes == 0 { "-".to_string() } else { self.bytes.to_string() };
+
+		let time = format!("{day:02}/{}/{year}:{:02}:{:02}:{:02} +0000", MONTHS[month as usize - 1], secs / 3600, secs / 60 % 60, secs % 60);
+
+		format!("{} - - [{time}] \"{} {} HTTP/1.1\" {} {bytes} \"{}\" \"{}\"", self.client.unwrap_or("-"), self.method, self.path, self.status, quoted(self.referer), quoted(self.user_agent))
+	}
+}
+
+type AccessHook = Box<dyn Fn(&Access<'_>) + Send + Sync>;
+static ACCESS_HOOK: OnceLock<AccessHook> = OnceLock::new();
+static ACCESSES: AtomicU64 = AtomicU64::new(0);
+
+/// Has `hook` called with the responses `Builder::access_log` samples, in place of printing them
+/// to standard output in the combined log format, e.g. to emit them as `tracing` events.  Only one
+/// hook can be registered, once.
+pub fn on_access<F>(hook: F) -> Result<(), String> where F: Fn(&Access<'_>) + Send + Sync + 'static {
+	ACCESS_HOOK.set(Box::new(hook)).map_err(|_| "an access hook is already registered".to_string())
+}
+
+/// Reports one in every `every` responses (counting from the first) to the `on_access` hook, or
+/// else prints it as a combined log format line; what code generated with `Builder::access_log`
+/// calls with everything it responds with
+pub fn log_access<'a, H>(every: u64, method: &str, path: &str, response: &Response, header: H) where H: Fn(&str) -> Option<&'a str> {
+	if every == 0 || !ACCESSES.fetch_add(1, Ordering::Relaxed).is_multiple_of(every) {
+		return;
+	}
+
+	let client = header("x-forwarded-for").and_then(|f| f.split(',').next()).map(str::trim).filter(|c| !c.is_empty());
+	let access = Access { method, path, status: response.status, bytes: response.body.len(), referer: header("referer"), user_agent: header("user-agent"), client, time: now() };
+	match ACCESS_HOOK.get() {
+		Some(hook) => hook(&access),
+		None => println!("{}", access.combined()),
+	}
+}
+
+/// The route for `path`, if `routes` (or a shard of it) has one
+pub fn route(routes: &'static [Route], path: &str) -> Option<&'static Route> {
+	// A shard sorts before every path under it, and after any that aren't
+	let candidate = &routes[routes.partition_point(|r| r.path <= path).checked_sub(1)?];
+
+	match candidate.serve {
+		Serve::Shard(shard) if path.starts_with(candidate.path) => route(shard, path),
+		_ => (candidate.path == path).then_some(candidate),
+	}
+}
+
+fn now() -> u64 {
+	SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+// What `serve` serves at `now`, with (for a scheduled route) when that last changed and when it next
+// will, if it ever does
+fn scheduled(serve: &'static Serve, now: u64) -> (&'static Serve, Option<u64>, Option<u64>) {
+	let Serve::Scheduled { windows, otherwise } = serve else { return (serve, None, None) };
+	let current = windows.iter().find(|w| w.from <= now && now < w.until);
+	let boundaries = || windows.iter().flat_map(|w| [w.from, w.until]);
+
+	(current.map_or(*otherwise, |w| &w.serve), boundaries().filter(|t| *t <= now).max(), boundaries().filter(|t| *t > now).min())
+}
+
+// A `Cache-Control` policy letting nothing keep the body more than `left` seconds
+fn capped(policy: &str, left: u64) -> String {
+	let mut capped = false;
+	let mut directives: Vec<String> = policy.split(',').map(str::trim).filter(|d| !d.is_empty() && *d != "immutable").map(|directive| {
+		match directive.split_once('=') {
+			Some((name @ ("max-age" | "s-maxage"), age)) => {
+				capped = true;
+				format!("{name}={}", age.trim().parse::<u64>().map_or(left, |age| age.min(left)))
+			},
+			_ => directive.to_string(),
+		}
+	}).collect();
+
+	if !capped && !directives.iter().any(|d| d == "no-cache" || d == "no-store") {
+		directives.push(format!("max-age={left}"));
+	}
+
+	directives.join(", ")
+}
+
+// Lets nothing keep a response's body more than `left` seconds
+fn cap(headers: &mut Vec<(&'static str, Cow<'static, str>)>, left: u64) {
+	match headers.iter_mut().find(|(name, _)| *name == "cache-control") {
+		Some((_, policy)) => *policy = Cow::Owned(capped(policy, left)),
+		None => headers.push(("cache-control", Cow::Owned(capped("", left)))),
+	}
+}
+
+// Each of an `Accept`-style header's items, with its weight
+fn weighted(header: &str) -> impl Iterator<Item = (&str, f32)> {
+	header.split(',').map(|item| {
+		let mut params = item.split(';');
+		let name = params.next().unwrap_or_default().trim();
+		(name, params.find_map(|p| p.trim().strip_prefix("q=")).map_or(1.0, |q| q.trim().parse().unwrap_or(0.0)))
+	})
+}
+
+// How much `accept` wants `media_type` by name, as `*/*` and `image/*` say nothing of which formats
+// a client can decode
+fn accepts(accept: &str, media_type: &str) -> f32 {
+	weighted(accept).find(|(name, _)| name.eq_ignore_ascii_case(media_type)).map_or(0.0, |(_, q)| q)
+}
+
+// How much `accept_language` wants `tag`, by the most specific range matching it: `en` matches
+// `en-GB` as well as `en`, and `*` any tag
+fn speaks(accept_language: &str, tag: &str) -> f32 {
+	let matches = |range: &str| range == "*" || tag.get(..range.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(range)) && matches!(tag.as_bytes().get(range.len()), None | Some(b'-'));
+	let specificity = |range: &str| if range == "*" { 0 } else { range.len() + 1 };
+
+	weighted(accept_language).filter(|(range, _)| matches(range)).max_by_key(|(range, _)| specificity(range)).map_or(0.0, |(_, q)| q)
+}
+
+// The quality an `Accept-Encoding` value gives `coding`, with `*` standing in for any coding it
+// doesn't name
+fn quality(accept_encoding: &str, coding: &str) -> f32 {
+	let mut wildcard = None;
+
+	for (name, q) in weighted(accept_encoding) {
+		if name.eq_ignore_ascii_case(coding) {
+			return q;
+		}
+		if name == "*" {
+			wildcard = Some(q);
+		}
+	}
+
+	wildcard.unwrap_or(0.0)
+}
+
+// How much `accept_encoding` wants a body as it is: as much as it says where it names `identity`,
+// fully without an `Accept-Encoding` at all, and otherwise less than any coding it names
+fn identity_quality(accept_encoding: Option<&str>) -> f32 {
+	match accept_encoding {
+		Some(accept_encoding) => weighted(accept_encoding).find(|(name, _)| name.eq_ignore_ascii_case("identity")).map_or(0.0, |(_, q)| q),
+		None => 1.0,
+	}
+}
+
+// The first of `candidates` with the greatest `weight`, if any's is more than nothing
+fn preferred<T, I, W>(candidates: I, weight: W) -> Option<T> where I: IntoIterator<Item = T>, W: Fn(&T) -> f32 {
+	candidates.into_iter()
+		.map(|c| (weight(&c), c))
+		.filter(|(q, _)| *q > 0.0)
+		.fold(None, |best: Option<(f32, T)>, c| if best.as_ref().is_some_and(|b| b.0 >= c.0) { best } else { Some(c) })
+		.map(|(_, c)| c)
+}
+
+/// One representation of a route's content, for `select_variant` to choose among
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Variant<'a> {
+	pub media_type: &'a str,
+	/// The content coding of a precompressed body, or `None` for the body as it is
+	pub encoding: Option<&'a str>,
+	/// The content's language tag, where it's in more than one language
+	pub language: Option<&'a str>,
+}
+
+/// Which of `variants` a request whose headers `header` looks up (by lowercase name) gets, as
+/// `respond` chooses: the media type `Accept` names with the greatest weight (by name, as `*/*`
+/// and `image/*` say nothing of which formats a client can decode), or else the first variant's;
+/// then of its variants, the language `Accept-Language` wants most, or else the first's; then of
+/// those, the coding `Accept-Encoding` wants more than `identity` (which it wants less than any it
+/// names, unless it names it), or else the one with none.  Ties go to the earlier variant, so
+/// smaller ones should come first.
+pub fn select_variant<'a, 'h, H>(header: H, variants: &[Variant<'a>]) -> Option<Variant<'a>> where H: Fn(&str) -> Option<&'h str> {
+	let first = variants.first()?;
+
+	let accept = header("accept").unwrap_or_default();
+	let media_type = preferred(variants.iter().map(|v| v.media_type), |t| accepts(accept, t)).unwrap_or(first.media_type);
+	let of_type = || variants.iter().filter(move |v| v.media_type == media_type);
+
+	let accept_language = header("accept-language").unwrap_or_default();
+	let language = preferred(of_type().filter_map(|v| v.language), |tag| speaks(accept_language, tag)).or(of_type().next()?.language);
+	let of_language = || of_type().filter(move |v| v.language == language);
+
+	let accept_encoding = header("accept-encoding");
+	let weight = |v: &&Variant<'a>| v.encoding.map_or_else(|| identity_quality(accept_encoding), |coding| quality(accept_encoding.unwrap_or_default(), coding));
+	// Listed first, so a coding has to be wanted more than the body as it is
+	let unencoded = of_language().find(|v| v.encoding.is_none());
+	let encoded = preferred(unencoded.into_iter().chain(of_language().filter(|v| v.encoding.is_some())), weight);
+
+	encoded.or(unencoded).or_else(|| of_language().next()).copied()
+}
+
+// The representation of `serve` a request gets, its content coding, and what its headers make the
+// choice vary by
+fn negotiate<'a, H>(serve: &'static Serve, header: H) -> (&'static Serve, Option<&'static str>, Option<&'static str>) where H: Fn(&str) -> Option<&'a str> {
+	let Serve::Content { media_type, encodings, formats, .. } = serve else { return (serve, None, None) };
+	if encodings.is_empty() && formats.is_empty() {
+		return (serve, None, None);
+	}
+
+	// Each format is a representation of its own, with a tag of its own, though all vary alike
+	let representations = || std::iter::once((*media_type, serve)).chain(formats.iter().copied());
+	let variants: Vec<Variant<'static>> = representations().flat_map(|(media_type, serve)| {
+		let encodings = match serve {
+			Serve::Content { encodings, .. } => *encodings,
+			_ => &[],
+		};
+		std::iter::once(Variant { media_type, encoding: None, language: None })
+			.chain(encodings.iter().map(move |(coding, _)| Variant { media_type, encoding: Some(*coding), language: None }))
+	}).collect();
+
+	let vary = match (formats.is_empty(), variants.iter().any(|v| v.encoding.is_some())) {
+		(false, true) => "accept, accept-encoding",
+		(false, false) => "accept",
+		(true, _) => "accept-encoding",
+	};
+	let Some(chosen) = select_variant(header, &variants) else { return (serve, None, Some(vary)) };
+	let serve = representations().find(|(media_type, _)| *media_type == chosen.media_type).map_or(serve, |(_, serve)| serve);
+
+	(serve, chosen.encoding, Some(vary))
+}
+
+const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+// The year, month and day `days` after the Unix epoch, by Howard Hinnant's `civil_from_days`
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+	let z = days + 719468;
+	let era = z.div_euclid(146097);
+	let day_of_era = z - era * 146097;
+	let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let mp = (5 * day_of_year + 2) / 153;
+	let day = day_of_year - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+	(year, month, day)
+}
+
+// `secs` since the Unix epoch as an HTTP date (`Sun, 06 Nov 1994 08:49:37 GMT`)
+fn http_date(secs: u64) -> String {
+	let (days, secs) = ((secs / 86400) as i64, secs % 86400);
+	let (year, month, day) = civil_from_days(days);
+
+	format!("{}, {day:02} {} {year} {:02}:{:02}:{:02} GMT", DAYS[days.rem_euclid(7) as usize], MONTHS[month as usize - 1], secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+// Seconds since the Unix epoch of an HTTP date, in the one format it should now be sent in
+fn parse_http_date(date: &str) -> Option<u64> {
+	let [_, day, month, year, time, "GMT"] = date.split_whitespace().collect::<Vec<_>>()[..] else { return None };
+	let month = MONTHS.iter().position(|m| *m == month)? as i64 + 1;
+	let mut time = time.splitn(3, ':').map(|t| t.parse::<u64>().ok());
+	let (hours, minutes, seconds) = (time.next()??, time.next()??, time.next()??);
+	let days = u64::try_from(days_from_civil(year.parse().ok()?, month, day.parse().ok()?)).ok()?;
+
+	Some(days * 86400 + hours * 3600 + minutes * 60 + seconds)
+}
+
+// Whether an `If-None-Match` value lists `etag` (or is `*`), weakly compared
+fn none_match(if_none_match: &str, etag: &str) -> bool {
+	if_none_match.trim() == "*" || if_none_match.split(',').any(|tag| tag.trim().trim_start_matches("W/") == etag)
+}
+
+// The one byte range a `Range` value asks of a `len`-byte body, as `Some(Err(()))` when it can't be
+// satisfied; `None` when there's no range to serve, including for several, which gets the whole body
+fn byte_range(range: &str, len: usize) -> Option<Result<(usize, usize), ()>> {
+	let (first, last) = range.trim().strip_prefix("bytes=")?.split_once('-')?;
+	let (first, last) = (first.trim(), last.trim());
+
+	if last.contains(',') {
+		return None;
+	}
+	let range = match (first.parse::<usize>(), last.parse::<usize>()) {
+		(Ok(first), Ok(last)) if first <= last => (first, last.min(len.saturating_sub(1))),
+		(Ok(first), Err(_)) if last.is_empty() => (first, len.saturating_sub(1)),
+		(Err(_), Ok(suffix)) if first.is_empty() && suffix > 0 => (len.saturating_sub(suffix), len.saturating_sub(1)),
+		_ => return None,
+	};
+
+	Some(if range.0 < len { Ok(range) } else { Err(()) })
+}
+
+/// What `respond` allows
+pub const DEFAULT_METHODS: &[&str] = &["GET", "HEAD", "OPTIONS"];
+
+/// The response to a `method` request for `path`; `header` looks up the request's headers by
+/// lowercase name
+pub fn respond<'a, H>(routes: &'static [Route], method: &str, path: &str, header: H) -> Response where H: Fn(&str) -> Option<&'a str> {
+	respond_allowing(routes, DEFAULT_METHODS, method, path, header)
+}
+
+/// Like `respond`, but allowing only `methods`, each of which other than HEAD and OPTIONS is
+/// answered as GET is
+pub fn respond_allowing<'a, H>(routes: &'static [Route], methods: &[&str], method: &str, path: &str, header: H) -> Response where H: Fn(&str) -> Option<&'a str> {
+	let mut response = response(routes, methods, method, path, header);
+	// Whatever order they're worked out in, so what's sent is the same from build to build
+	response.headers.sort_by_key(|(name, _)| *name);
+	response
+}
+
+// What `respond_allowing` responds with, the headers in no particular order
+fn response<'a, H>(routes: &'static [Route], methods: &[&str], method: &str, path: &str, header: H) -> Response where H: Fn(&str) -> Option<&'a str> {
+	let allow = || vec![("allow", Cow::Owned(methods.join(", ")))];
+
+	if !methods.contains(&method) {
+		return Response { headers: allow(), ..Response::status(405) };
+	}
+	if method == "OPTIONS" && path == "*" {
+		return Response { headers: allow(), ..Response::status(204) };
+	}
+
+	let Some(route) = route(routes, path) else { return not_found(method, path, header) };
+
+	if method == "OPTIONS" {
+		return Response { headers: allow(), ..Response::status(204) };
+	}
+
+	let now = now();
+	let (serve, changed, changes) = scheduled(&route.serve, now);
+	let (serve, coding, vary) = negotiate(serve, &header);
+
+	match serve {
+		Serve::Redirect(location) => Response { headers: vec![("location", Cow::Borrowed(*location))], ..Response::status(308) },
+		Serve::Shard(_) => Response::status(404),
+		// Windows only ever serve content
+		Serve::Scheduled { .. } => Response::status(500),
+		Serve::Content { filters, .. } if !filters.is_empty() => filtered(route.path, method, serve, changes.map(|changes| changes - now), header),
+		Serve::Content { media_type, headers: extra, body, encodings, etag, last_modified, .. } => {
+			// What a client has from before the last swap is older than it says
+			let last_modified = &last_modified.map(|modified| changed.map_or(modified, |changed| modified.max(changed)));
+			let body = coding.and_then(|coding| encodings.iter().find(|(name, _)| *name == coding)).map_or(*body, |(_, body)| *body);
+			let load = || body.load(route.path).inspect_err(|e| eprintln!("static-builder: {e}"));
+
+			// Each encoding is a representation of its own, so has a tag of its own
+			let (hash, loaded) = match etag {
+				Some(hash) => (Cow::Borrowed(*hash), None),
+				None => {
+					let Ok(body) = load() else { return Response::status(500) };
+					(Cow::Owned(format!("{:016x}", fnv1a(&body))), Some(body))
+				},
+			};
+			let etag = match coding {
+				Some(coding) => format!("\"{hash}-{coding}\""),
+				None => format!("\"{hash}\""),
+			};
+			// `If-Modified-Since` only counts without an `If-None-Match`, which is the more exact
+			let not_modified = match header("if-none-match") {
+				Some(tags) => none_match(tags, &etag),
+				None => header("if-modified-since").and_then(parse_http_date).zip(*last_modified).is_some_and(|(since, modified)| modified <= since),
+			};
+
+			// HEAD only needs the length, which most stores know without loading anything
+			let head = method == "HEAD" && !not_modified;
+			let (body, head_length) = match loaded {
+				_ if not_modified => (Cow::Borrowed(&[][..]), None),
+				Some(body) if head => (Cow::Borrowed(&[][..]), Some(body.len() as u64)),
+				Some(body) => (body, None),
+				None if head => match body.len(route.path) {
+					Ok(len) => (Cow::Borrowed(&[][..]), Some(len)),
+					Err(e) => {
+						eprintln!("static-builder: {e}");
+						return Response::status(500);
+					},
+				},
+				None => match load() {
+					Ok(body) => (body, None),
+					Err(_) => return Response::status(500),
+				},
+			};
+
+			// Only when `If-Range` names this very representation, strongly, if it names one at all
+			let ranged = method != "HEAD" && !not_modified && header("if-range").is_none_or(|validator| {
+				let validator = validator.trim();
+				validator == etag || last_modified.is_some_and(|modified| validator == http_date(modified))
+			});
+			let len = body.len();
+			let (status, body, content_range) = match header("range").filter(|_| ranged).and_then(|range| byte_range(range, len)) {
+				Some(Ok((first, last))) => {
+					let part = match body {
+						Cow::Borrowed(body) => Cow::Borrowed(&body[first..=last]),
+						Cow::Owned(body) => Cow::Owned(body[first..=last].to_vec()),
+					};
+					(206, part, Some(format!("bytes {first}-{last}/{len}")))
+				},
+				Some(Err(())) => (416, Cow::Borrowed(&[][..]), Some(format!("bytes */{len}"))),
+				None if not_modified => (304, body, None),
+				None => (200, body, None),
+			};
+
+			let mut headers = vec![("content-type", Cow::Borrowed(*media_type)), ("etag", Cow::Owned(etag))];
+			if let Some(length) = head_length {
+				headers.push(("content-length", Cow::Owned(length.to_string())));
+			}
+			headers.extend(extra.iter().map(|(name, value)| (*name, Cow::Borrowed(*value))));
+			if let Some(modified) = last_modified {
+				headers.push(("last-modified", Cow::Owned(http_date(*modified))));
+			}
+			if let Some(coding) = coding {
+				headers.push(("content-encoding", Cow::Borrowed(coding)));
+			}
+			if let Some(vary) = vary {
+				headers.push(("vary", Cow::Borrowed(vary)));
+			}
+			headers.push(("accept-ranges", Cow::Borrowed("bytes")));
+			if let Some(range) = content_range {
+				headers.push(("content-range", Cow::Owned(range)));
+			}
+			// Nothing should keep a scheduled body past the time it's swapped for another
+			if let Some(left) = changes.map(|changes| changes - now) {
+				cap(&mut headers, left);
+			}
+
+			Response { status, headers, body }
+		},
+	}
+}
+
+/// A page on its way out, as the filters its rule names see it
+#[cfg(feature = "filters")]
+pub struct Filtered<'a> {
+	pub method: &'a str,
+	pub path: &'a str,
+	/// The response's headers so far, with lowercase names
+	pub headers: &'a mut Vec<(&'static str, Cow<'static, str>)>,
+	pub body: &'a mut Vec<u8>,
+	header: &'a dyn Fn(&str) -> Option<&'a str>,
+}
+
+#[cfg(feature = "filters")]
+impl Filtered<'_> {
+	/// The request's header with the lowercase `name`, if it has one
+	pub fn header(&self, name: &str) -> Option<&str> {
+		(self.header)(name)
+	}
+}
+
+#[cfg(feature = "filters")]
+type Filter = Box<dyn Fn(&mut Filtered<'_>) -> Result<(), String> + Send + Sync>;
+#[cfg(feature = "filters")]
+static FILTERS: std::sync::RwLock<Vec<(String, Filter)>> = std::sync::RwLock::new(vec![]);
+
+/// Registers `filter` as `name`, for the pages whose rules name it (see `Rule::filter`) to go
+/// through as each is served, e.g. to give each response's scripts a CSP nonce of its own.  A page
+/// a filter fails for, or whose rule names one that isn't registered, gets a 500.
+#[cfg(feature = "filters")]
+pub fn add_filter<S, F>(name: S, filter: F) -> Result<(), String> where S: Into<String>, F: Fn(&mut Filtered<'_>) -> Result<(), String> + Send + Sync + 'static {
+	let name = name.into();
+	let mut filters = FILTERS.write().unwrap_or_else(std::sync::PoisonError::into_inner);
+
+	if filters.iter().any(|(registered, _)| *registered == name) {
+		return Err(format!("a filter is already registered as {name}"));
+	}
+	filters.push((name, Box::new(filter)));
+	Ok(())
+}
+
+// The filtered page `serve` is, which is only known once it's been through its filters, so is
+// served whole, unencoded and without validators
+#[cfg(feature = "filters")]
+fn filtered<'a, H>(path: &str, method: &str, serve: &Serve, left: Option<u64>, header: H) -> Response where H: Fn(&str) -> Option<&'a str> {
+	let Serve::Content { media_type, headers: extra, body, filters, .. } = serve else { return Response::status(500) };
+	let Ok(body) = body.load(path).inspect_err(|e| eprintln!("static-builder: {e}")) else { return Response::status(500) };
+	let mut body = body.into_owned();
+	let mut headers = vec![("content-type", Cow::Borrowed(*media_type))];
+	headers.extend(extra.iter().map(|(name, value)| (*name, Cow::Borrowed(*value))));
+
+	let registered = FILTERS.read().unwrap_or_else(std::sync::PoisonError::into_inner);
+	for name in *filters {
+		let Some((_, filter)) = registered.iter().find(|(registered, _)| registered == name) else {
+			eprintln!("static-builder: {path} is filtered by {name}, which isn't registered");
+			return Response::status(500);
+		};
+		let header = |name: &str| -> Option<&str> { header(name) };
+		if let Err(e) = filter(&mut Filtered { method, path, headers: &mut headers, body: &mut body, header: &header }) {
+			eprintln!("static-builder: filtering {path} by {name} failed: {e}");
+			return Response::status(500);
+		}
+	}
+
+	if let Some(left) = left {
+		cap(&mut headers, left);
+	}
+	if method == "HEAD" {
+		headers.push(("content-length", Cow::Owned(body.len().to_string())));
+		return Response { status: 200, headers, body: Cow::Borrowed(&[]) };
+	}
+
+	Response { status: 200, headers, body: Cow::Owned(body) }
+}
+
+#[cfg(not(feature = "filters"))]
+fn filtered<'a, H>(path: &str, _method: &str, _serve: &Serve, _left: Option<u64>, _header: H) -> Response where H: Fn(&str) -> Option<&'a str> {
+	eprintln!("static-builder: {path} is filtered, but static-builder's filters feature is off");
+	Response::status(500)
+}
+
+/// Like `respond_allowing`, but answering requests for paths nothing serves with the route at
+/// `not_found` (where there is one), with a 404
+pub fn respond_with_not_found<'a, H>(routes: &'static [Route], methods: &[&str], not_found: &str, method: &str, path: &str, header: H) -> Response where H: Fn(&str) -> Option<&'a str> {
+	let response = respond_allowing(routes, methods, method, path, &header);
+
+	if response.status != 404 || method == "OPTIONS" || route(routes, not_found).is_none() {
+		return response;
+	}
+
+	// Anything conditional or ranged was asked of what wasn't found, not of this
+	let method = if method == "HEAD" { "HEAD" } else { "GET" };
+	let page = respond_allowing(routes, DEFAULT_METHODS, method, not_found, |name| header(name).filter(|_| name == "accept-encoding"));
+
+	match page.status {
+		200 => Response { status: 404, ..page },
+		_ => page,
+	}
+}
+
+/// Renders `path` into the render cache, if it's rendered at all, so that responding to it doesn't
+/// have to; for running somewhere blocking is fine, ahead of `respond`
+pub fn prerender(routes: &'static [Route], path: &str) {
+	if let Some(route) = route(routes, path) {
+		if let (Serve::Content { body, .. }, _, _) = scheduled(&route.serve, now()) {
+			body.prerender(route.path);
+		}
+	}
+}
+
+// Requests a lookup mustn't trip over, whatever the route table holds
+const AWKWARD_PATHS: &[&str] = &[
+	"", "/", "*", "//", "/.", "/..", "/../", "/../etc/passwd", "/..\\..\\windows\\win.ini", "/%2e%2e/%2e%2e/etc/passwd",
+	"/..%2f..%2fetc%2fpasswd", "/%c0%ae%c0%ae/", "/%00", "/\0", "/?", "/#", "/%", "/%zz", "/%ff", "/\u{fffd}", "/é", "/%C3%A9",
+	"/\u{202e}", "\\", "http://example.com/", "/index.html\0.png",
+];
+
+/// Request paths for property tests and fuzzing to start from: traversal attempts, odd encodings
+/// and very long paths, then each of `paths` mangled every way a stray request might (with
+/// extra, doubled or missing characters, and some bytes changed at random), reproducibly
+pub fn awkward_paths(paths: &[&str]) -> Vec<String> {
+	let mut awkward: Vec<String> = AWKWARD_PATHS.iter().map(|p| p.to_string()).collect();
+	awkward.extend([format!("/{}", "a".repeat(8192)), "/a".repeat(32768), "/%2e%2e".repeat(4096)]);
+
+	for path in paths {
+		let mut chars: Vec<char> = path.chars().collect();
+		awkward.extend([
+			format!("{path}/"), format!("{path}x"), format!("{path}%00"), format!("{path}/.."), format!("{path}?q"),
+			format!("/.{path}"), format!("/{path}"), path.replace('/', "//"), path.replace('/', "\\"), path.to_uppercase(),
+			path.trim_end_matches('/').to_string(),
+		]);
+		chars.pop();
+		awkward.push(chars.iter().collect());
+
+		let mut seed = fnv1a(path.as_bytes()) | 1;
+		for _ in 0..4 {
+			let mut chars: Vec<char> = path.chars().collect();
+			seed ^= seed << 13;
+			seed ^= seed >> 7;
+			seed ^= seed << 17;
+			let at = (seed % (chars.len() as u64 + 1)) as usize;
+			let c = char::from_u32((seed >> 32) as u32 % 0x250).unwrap_or('\u{fffd}');
+
+			match seed >> 60 & 3 {
+				0 if at < chars.len() => { chars.remove(at); },
+				1 if at < chars.len() => chars[at] = c,
+				_ => chars.insert(at, c),
+			}
+			awkward.push(chars.into_iter().collect());
+		}
+	}
+
+	awkward.sort();
+	awkward.dedup();
+	awkward
+}
+
+/// Panics unless looking `path` up in `routes` (which has a route for each of `paths`, and no
+/// others) finds one exactly when it's among `paths`, and a GET or HEAD of any other gets a 404;
+/// what fuzz targets and generated property tests check
+pub fn check_path(routes: &'static [Route], paths: &[&str], path: &str) {
+	let listed = paths.contains(&path);
+	assert_eq!(route(routes, path).is_some(), listed, "looking up {path:?}");
+
+	if !listed {
+		for method in ["GET", "HEAD"] {
+			assert_eq!(respond(routes, method, path, |_| None).status, 404, "{method} {path:?}");
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{respond, respond_allowing, select_variant, Embedded, Response, Route, Serve, Variant};
+
+	fn variant(media_type: &'static str, encoding: Option<&'static str>, language: Option<&'static str>) -> Variant<'static> {
+		Variant { media_type, encoding, language }
+	}
+
+	// What a request with `headers` (lowercase name, value) gets of `variants`
+	fn selected(headers: &[(&str, &'static str)], variants: &[Variant<'static>]) -> Variant<'static> {
+		select_variant(|name| headers.iter().find(|(n, _)| *n == name).map(|(_, value)| *value), variants).unwrap()
+	}
+
+	const IMAGES: [Variant<'static>; 3] = [
+		Variant { media_type: "image/png", encoding: None, language: None },
+		Variant { media_type: "image/avif", encoding: None, language: None },
+		Variant { media_type: "image/webp", encoding: None, language: None },
+	];
+
+	const TEXT: [Variant<'static>; 3] = [
+		Variant { media_type: "text/plain", encoding: None, language: None },
+		Variant { media_type: "text/plain", encoding: Some("br"), language: None },
+		Variant { media_type: "text/plain", encoding: Some("gzip"), language: None },
+	];
+
+	#[test]
+	fn no_variants() {
+		assert_eq!(select_variant(|_| None, &[]), None);
+	}
+
+	#[test]
+	fn no_headers_get_the_first_variant() {
+		assert_eq!(selected(&[], &IMAGES), IMAGES[0]);
+		assert_eq!(selected(&[], &TEXT), TEXT[0]);
+	}
+
+	#[test]
+	fn media_type_by_weight() {
+		assert_eq!(selected(&[("accept", "image/webp, image/avif;q=0.9")], &IMAGES).media_type, "image/webp");
+		assert_eq!(selected(&[("accept", "image/avif,image/webp,*/*;q=0.8")], &IMAGES).media_type, "image/avif");
+		assert_eq!(selected(&[("accept", "image/png;q=0.5, image/avif")], &IMAGES).media_type, "image/avif");
+	}
+
+	#[test]
+	fn own_media_type_weighs_against_formats() {
+		assert_eq!(selected(&[("accept", "image/png, image/webp;q=0.5")], &IMAGES).media_type, "image/png");
+	}
+
+	#[test]
+	fn media_type_wildcards_name_no_format() {
+		assert_eq!(selected(&[("accept", "*/*")], &IMAGES).media_type, "image/png");
+		assert_eq!(selected(&[("accept", "image/*")], &IMAGES).media_type, "image/png");
+		assert_eq!(selected(&[("accept", "image/webp,*/*")], &IMAGES).media_type, "image/webp");
+	}
+
+	#[test]
+	fn media_type_q0_excludes() {
+		assert_eq!(selected(&[("accept", "image/avif;q=0, image/webp;q=0")], &IMAGES).media_type, "image/png");
+		assert_eq!(selected(&[("accept", "image/avif;q=0, image/webp;q=0.1")], &IMAGES).media_type, "image/webp");
+	}
+
+	#[test]
+	fn media_type_ties_go_to_the_earlier_variant() {
+		assert_eq!(selected(&[("accept", "image/webp, image/avif")], &IMAGES).media_type, "image/avif");
+		assert_eq!(selected(&[("accept", "image/webp;q=0.5, image/avif;q=0.5, image/png;q=0.5")], &IMAGES).media_type, "image/png");
+	}
+
+	#[test]
+	fn coding_by_weight() {
+		assert_eq!(selected(&[("accept-encoding", "gzip")], &TEXT).encoding, Some("gzip"));
+		assert_eq!(selected(&[("accept-encoding", "gzip, br;q=0.5")], &TEXT).encoding, Some("gzip"));
+		assert_eq!(selected(&[("accept-encoding", "deflate")], &TEXT).encoding, None);
+	}
+
+	#[test]
+	fn coding_ties_go_to_the_earlier_variant() {
+		assert_eq!(selected(&[("accept-encoding", "gzip, deflate, br")], &TEXT).encoding, Some("br"));
+	}
+
+	#[test]
+	fn coding_wildcard() {
+		assert_eq!(selected(&[("accept-encoding", "*")], &TEXT).encoding, Some("br"));
+		assert_eq!(selected(&[("accept-encoding", "br;q=0, *")], &TEXT).encoding, Some("gzip"));
+	}
+
+	#[test]
+	fn coding_q0_excludes() {
+		assert_eq!(selected(&[("accept-encoding", "gzip;q=0, br;q=0")], &TEXT).encoding, None);
+		assert_eq!(selected(&[("accept-encoding", "*;q=0")], &TEXT).encoding, None);
+	}
+
+	#[test]
+	fn identity_weighs_against_codings() {
+		assert_eq!(selected(&[("accept-encoding", "identity;q=1, gzip;q=0.1")], &TEXT).encoding, None);
+		assert_eq!(selected(&[("accept-encoding", "identity, gzip")], &TEXT).encoding, None);
+		assert_eq!(selected(&[("accept-encoding", "identity;q=0.5, gzip")], &TEXT).encoding, Some("gzip"));
+	}
+
+	#[test]
+	fn unencoded_without_one_falls_back_to_a_coding() {
+		let variants = [variant("text/plain", Some("gzip"), None)];
+
+		assert_eq!(selected(&[("accept-encoding", "br")], &variants).encoding, Some("gzip"));
+	}
+
+	#[test]
+	fn language_prefix_matches() {
+		let variants = [variant("text/html", None, Some("de")), variant("text/html", None, Some("en-GB")), variant("text/html", None, Some("en"))];
+
+		assert_eq!(selected(&[("accept-language", "en")], &variants).language, Some("en-GB"));
+		assert_eq!(selected(&[("accept-language", "en-gb")], &variants).language, Some("en-GB"));
+		assert_eq!(selected(&[("accept-language", "en-US, de;q=0.5")], &variants).language, Some("de"));
+		assert_eq!(selected(&[("accept-language", "fr")], &variants).language, Some("de"));
+	}
+
+	#[test]
+	fn language_most_specific_range_wins() {
+		let variants = [variant("text/html", None, Some("en-GB")), variant("text/html", None, Some("en-US"))];
+
+		assert_eq!(selected(&[("accept-language", "en, en-GB;q=0.5")], &variants).language, Some("en-US"));
+		assert_eq!(selected(&[("accept-language", "*;q=0.1, en-US")], &variants).language, Some("en-US"));
+		assert_eq!(selected(&[("accept-language", "en-g, en-US;q=0.1")], &variants).language, Some("en-US"));
+	}
+
+	static BODY: Embedded = Embedded(b"hello, world");
+	static GZIPPED: Embedded = Embedded(b"gzipped hello");
+
+	// Sun, 06 Nov 1994 08:49:37 GMT
+	const MODIFIED: u64 = 784111777;
+
+	static ROUTES: &[Route] = &[
+		Route { path: "/", serve: Serve::Content { media_type: "text/plain", headers: &[("cache-control", "no-cache")], body: &BODY, encodings: &[("gzip", &GZIPPED)], etag: Some("abc"), last_modified: Some(MODIFIED), formats: &[], filters: &[] } },
+		Route { path: "/old", serve: Serve::Redirect("/") },
+	];
+
+	// What `respond` gives a `method` request for `path` with `headers` (lowercase name, value)
+	fn respond_to(method: &str, path: &str, headers: &[(&str, &'static str)]) -> Response {
+		respond(ROUTES, method, path, |name| headers.iter().find(|(n, _)| *n == name).map(|(_, value)| *value))
+	}
+
+	fn header<'r>(response: &'r Response, name: &str) -> Option<&'r str> {
+		response.headers.iter().find(|(n, _)| *n == name).map(|(_, value)| value.as_ref())
+	}
+
+	#[test]
+	fn get_sends_the_body_with_headers_in_order() {
+		let response = respond_to("GET", "/", &[]);
+
+		assert_eq!(response.status, 200);
+		assert_eq!(&response.body[..], b"hello, world");
+		assert_eq!(response.headers.iter().map(|(name, value)| (*name, value.as_ref())).collect::<Vec<_>>(), [
+			("accept-ranges", "bytes"),
+			("cache-control", "no-cache"),
+			("content-type", "text/plain"),
+			("etag", "\"abc\""),
+			("last-modified", "Sun, 06 Nov 1994 08:49:37 GMT"),
+			("vary", "accept-encoding"),
+		]);
+	}
+
+	#[test]
+	fn encodings_have_tags_of_their_own() {
+		let response = respond_to("GET", "/", &[("accept-encoding", "gzip")]);
+
+		assert_eq!(&response.body[..], b"gzipped hello");
+		assert_eq!(header(&response, "content-encoding"), Some("gzip"));
+		assert_eq!(header(&response, "etag"), Some("\"abc-gzip\""));
+	}
+
+	#[test]
+	fn if_none_match() {
+		for tags in ["\"abc\"", "W/\"abc\"", "\"other\", \"abc\"", "*"] {
+			let response = respond_to("GET", "/", &[("if-none-match", tags)]);
+			assert_eq!((response.status, response.body.len()), (304, 0), "for {tags}");
+			assert_eq!(header(&response, "etag"), Some("\"abc\""));
+		}
+
+		assert_eq!(respond_to("GET", "/", &[("if-none-match", "\"other\"")]).status, 200);
+		assert_eq!(respond_to("GET", "/", &[("if-none-match", "\"abc\""), ("accept-encoding", "gzip")]).status, 200);
+		// The tag is the more exact, so wins over the date
+		assert_eq!(respond_to("GET", "/", &[("if-none-match", "\"other\""), ("if-modified-since", "Sun, 06 Nov 1994 08:49:37 GMT")]).status, 200);
+	}
+
+	#[test]
+	fn if_modified_since() {
+		assert_eq!(respond_to("GET", "/", &[("if-modified-since", "Sun, 06 Nov 1994 08:49:37 GMT")]).status, 304);
+		assert_eq!(respond_to("GET", "/", &[("if-modified-since", "Mon, 07 Nov 1994 00:00:00 GMT")]).status, 304);
+		assert_eq!(respond_to("GET", "/", &[("if-modified-since", "Sun, 06 Nov 1994 08:49:36 GMT")]).status, 200);
+		assert_eq!(respond_to("GET", "/", &[("if-modified-since", "Sunday, 06-Nov-94 08:49:37 GMT")]).status, 200);
+	}
+
+	#[test]
+	fn ranges() {
+		let cases = [("bytes=0-4", "hello", "bytes 0-4/12"), ("bytes=7-", "world", "bytes 7-11/12"), ("bytes=-5", "world", "bytes 7-11/12"), ("bytes=7-100", "world", "bytes 7-11/12")];
+		for (range, part, content_range) in cases {
+			let response = respond_to("GET", "/", &[("range", range)]);
+			assert_eq!((response.status, &response.body[..], header(&response, "content-range")), (206, part.as_bytes(), Some(content_range)), "for {range}");
+		}
+
+		let response = respond_to("GET", "/", &[("range", "bytes=12-")]);
+		assert_eq!((response.status, response.body.len(), header(&response, "content-range")), (416, 0, Some("bytes */12")));
+
+		// Several ranges, or ones that make no sense, get the whole body
+		for range in ["bytes=0-1,3-4", "bytes=4-1", "lines=1-2", "bytes=-0"] {
+			let response = respond_to("GET", "/", &[("range", range)]);
+			assert_eq!((response.status, &response.body[..], header(&response, "content-range")), (200, &b"hello, world"[..], None), "for {range}");
+		}
+	}
+
+	#[test]
+	fn if_range() {
+		for validator in ["\"abc\"", "Sun, 06 Nov 1994 08:49:37 GMT"] {
+			assert_eq!(respond_to("GET", "/", &[("range", "bytes=0-4"), ("if-range", validator)]).status, 206, "for {validator}");
+		}
+		for validator in ["\"other\"", "W/\"abc\"", "Sun, 06 Nov 1994 08:49:38 GMT"] {
+			let response = respond_to("GET", "/", &[("range", "bytes=0-4"), ("if-range", validator)]);
+			assert_eq!((response.status, response.body.len()), (200, 12), "for {validator}");
+		}
+	}
+
+	#[test]
+	fn head_sends_the_length_without_the_body() {
+		let response = respond_to("HEAD", "/", &[("range", "bytes=0-4")]);
+
+		assert_eq!((response.status, response.body.len()), (200, 0));
+		assert_eq!(header(&response, "content-length"), Some("12"));
+		assert_eq!(header(&response, "content-range"), None);
+		assert_eq!(header(&response, "etag"), Some("\"abc\""));
+
+		let response = respond_to("HEAD", "/", &[("if-none-match", "\"abc\"")]);
+		assert_eq!((response.status, header(&response, "content-length")), (304, None));
+	}
+
+	#[test]
+	fn options_and_allow() {
+		for path in ["/", "*"] {
+			let response = respond_to("OPTIONS", path, &[]);
+			assert_eq!((response.status, header(&response, "allow")), (204, Some("GET, HEAD, OPTIONS")), "for {path}");
+		}
+		assert_eq!(respond_to("OPTIONS", "/missing", &[]).status, 404);
+
+		let response = respond_to("POST", "/", &[]);
+		assert_eq!((response.status, header(&response, "allow")), (405, Some("GET, HEAD, OPTIONS")));
+
+		let response = respond_allowing(ROUTES, &["GET", "POST"], "POST", "/", |_| None);
+		assert_eq!((response.status, &response.body[..]), (200, &b"hello, world"[..]));
+		let response = respond_allowing(ROUTES, &["GET", "POST"], "HEAD", "/", |_| None);
+		assert_eq!((response.status, header(&response, "allow")), (405, Some("GET, POST")));
+	}
+
+	#[test]
+	fn redirects_and_missing_routes() {
+		let response = respond_to("GET", "/old", &[]);
+		assert_eq!((response.status, header(&response, "location")), (308, Some("/")));
+
+		assert_eq!(respond_to("GET", "/missing", &[]).status, 404);
+	}
+}
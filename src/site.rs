@@ -0,0 +1,113 @@
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use std::{collections::BTreeMap, env, fs, path::{Path, PathBuf}};
+
+use crate::Rule;
+
+// The environment variable naming the environment being built for, e.g. `production`
+const ENVIRONMENT: &str = "STATIC_BUILDER_ENV";
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct Settings {
+	base_url: Option<String>,
+	robots: Option<String>,
+	headers: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+// What `site.yaml` (with any environment's overlay) says, and all of it for templates
+pub(crate) struct SiteConfig {
+	pub(crate) base_url: Option<String>,
+	robots: Option<String>,
+	headers: BTreeMap<String, BTreeMap<String, String>>,
+	vars: serde_json::Value,
+}
+
+// `overlay` over `base`, mapping by mapping, with anything else in `overlay` replacing what's in `base`
+fn merge(base: &mut Value, overlay: Value) {
+	match (base, overlay) {
+		(Value::Mapping(base), Value::Mapping(overlay)) => {
+			for (key, value) in overlay {
+				match base.get_mut(&key) {
+					Some(existing) => merge(existing, value),
+					None => {
+						base.insert(key, value);
+					},
+				}
+			}
+		},
+		(base, overlay) => *base = overlay,
+	}
+}
+
+fn read(file: &Path) -> Result<Value, String> {
+	let yaml = fs::read_to_string(file).map_err(|e| format!("failed to read {}: {e}", file.display()))?;
+	let value: Value = serde_yaml::from_str(&yaml).map_err(|e| format!("failed to parse {}: {e}", file.display()))?;
+
+	Ok(if value.is_null() { Value::Mapping(Mapping::new()) } else { value })
+}
+
+// `site.production.yaml` for `site.yaml`
+fn overlay_file(file: &Path, environment: &str) -> PathBuf {
+	let stem = file.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+
+	match file.extension() {
+		Some(ext) => file.with_file_name(format!("{stem}.{environment}.{}", ext.to_string_lossy())),
+		None => file.with_file_name(format!("{stem}.{environment}")),
+	}
+}
+
+impl SiteConfig {
+	pub(crate) fn load(file: &Path) -> Result<Self, String> {
+		println!("cargo::rerun-if-changed={}", file.display());
+		println!("cargo::rerun-if-env-changed={ENVIRONMENT}");
+
+		let mut value = read(file)?;
+
+		if let Some(environment) = env::var(ENVIRONMENT).ok().filter(|e| !e.is_empty()) {
+			let overlay = overlay_file(file, &environment);
+			println!("cargo::rerun-if-changed={}", overlay.display());
+
+			if !overlay.exists() {
+				return Err(format!("{ENVIRONMENT} is {environment:?}, but there's no {}", overlay.display()));
+			}
+			merge(&mut value, read(&overlay)?);
+		}
+
+		let settings: Settings = serde_yaml::from_value(value.clone()).map_err(|e| format!("invalid site config {}: {e}", file.display()))?;
+		let vars = serde_json::to_value(&value).map_err(|e| format!("invalid site config {}: {e}", file.display()))?;
+
+		Ok(SiteConfig {
+			base_url: settings.base_url.map(|url| url.trim_end_matches('/').to_string()),
+			robots: settings.robots,
+			headers: settings.headers,
+			vars,
+		})
+	}
+
+	/// Rules going before the builder's own: everything gets the settings (as `site` in templates),
+	/// and each glob under `headers` its header set
+	pub(crate) fn rules(&self) -> Vec<(String, Rule)> {
+		let mut rules = vec![("**".to_string(), Rule::new().site(self.vars.clone()))];
+
+		for (glob, headers) in &self.headers {
+			let rule = headers.iter().fold(Rule::new(), |rule, (name, value)| rule.header(name, value));
+			rules.push((glob.clone(), rule));
+		}
+
+		rules
+	}
+
+	/// `/robots.txt`: `allow` lets every crawler in everywhere (pointing them at `sitemap`, if
+	/// there is one), `deny` keeps them all out, and anything else is the file itself
+	pub(crate) fn robots(&self, sitemap: Option<&str>) -> Option<String> {
+		Some(match self.robots.as_deref()?.trim() {
+			"allow" => match sitemap {
+				Some(sitemap) => format!("User-agent: *\nDisallow:\n\nSitemap: {sitemap}\n"),
+				None => "User-agent: *\nDisallow:\n".to_string(),
+			},
+			"deny" => "User-agent: *\nDisallow: /\n".to_string(),
+			robots => format!("{robots}\n"),
+		})
+	}
+}
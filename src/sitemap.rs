@@ -0,0 +1,50 @@
+use crate::RenderedResource;
+
+const CHANGEFREQS: &[&str] = &["always", "hourly", "daily", "weekly", "monthly", "yearly", "never"];
+
+pub(crate) fn xml_escape(s: &str) -> String {
+	s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// A sitemap of every HTML page's canonical URL (aliases and redirects never appear), less those
+/// whose frontmatter says `sitemap: false` or `noindex: true`; `priority:` and `changefreq:` are
+/// passed along.
+pub(crate) fn sitemap<'a, I>(base_url: &str, pages: I) -> Result<Vec<u8>, String> where I: IntoIterator<Item = &'a RenderedResource> {
+	let base_url = base_url.trim_end_matches('/');
+	let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+
+	for page in pages {
+		if !page.content_type.starts_with("text/html") || page.noindex() {
+			continue;
+		}
+
+		let frontmatter = page.metadata.as_ref();
+		let setting = |key| frontmatter.and_then(|m| m.get(key));
+
+		if let Some(included) = setting("sitemap") {
+			if !included.as_bool().ok_or_else(|| format!("{}: sitemap must be true or false", page.source.display()))? {
+				continue;
+			}
+		}
+
+		xml.push_str(&format!("\t<url>\n\t\t<loc>{}</loc>\n", xml_escape(&format!("{base_url}{}", page.paths[0]))));
+
+		if let Some(changefreq) = setting("changefreq") {
+			let changefreq = changefreq.as_str().filter(|c| CHANGEFREQS.contains(c))
+				.ok_or_else(|| format!("{}: changefreq must be one of {}", page.source.display(), CHANGEFREQS.join(", ")))?;
+			xml.push_str(&format!("\t\t<changefreq>{changefreq}</changefreq>\n"));
+		}
+
+		if let Some(priority) = setting("priority") {
+			let priority = priority.as_f64().filter(|p| (0.0..=1.0).contains(p))
+				.ok_or_else(|| format!("{}: priority must be a number from 0.0 to 1.0", page.source.display()))?;
+			xml.push_str(&format!("\t\t<priority>{priority:.1}</priority>\n"));
+		}
+
+		xml.push_str("\t</url>\n");
+	}
+
+	xml.push_str("</urlset>\n");
+
+	Ok(xml.into_bytes())
+}
@@ -0,0 +1,112 @@
+use std::{collections::HashSet, fs, path::{Path, PathBuf}};
+
+use crate::RenderedResource;
+
+pub(crate) struct Dictionary(HashSet<String>);
+
+impl Dictionary {
+	// Plain word lists, one word per line; hunspell `.dic` files work too, as their affix flags
+	// are dropped and the leading word count is just another (numeric, so never checked) word
+	pub(crate) fn load(files: &[PathBuf]) -> Result<Self, String> {
+		let mut words = HashSet::new();
+
+		for file in files {
+			let list = fs::read_to_string(file).map_err(|e| format!("failed to read dictionary {}: {e}", file.display()))?;
+
+			for line in list.lines() {
+				let word = line.split('/').next().unwrap_or_default().trim();
+
+				if !word.is_empty() && !word.starts_with('#') {
+					words.insert(word.to_string());
+				}
+			}
+		}
+
+		Ok(Dictionary(words))
+	}
+
+	fn knows(&self, word: &str) -> bool {
+		self.0.contains(word) || self.0.contains(&word.to_lowercase())
+	}
+}
+
+// The text of an HTML page, less its markup, entities, and the contents of elements that aren't prose
+fn text(html: &str) -> String {
+	let mut text = String::new();
+	let mut rest = html;
+	let mut skipping: Option<&str> = None;
+
+	while let Some(open) = rest.find('<') {
+		if skipping.is_none() {
+			text.push_str(&rest[..open]);
+		}
+		text.push(' ');
+
+		let Some(close) = rest[open..].find('>') else { break };
+		let tag = &rest[open + 1..open + close];
+		let name = tag.trim_start_matches('/').split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or_default().to_ascii_lowercase();
+
+		match skipping {
+			Some(skipped) if tag.starts_with('/') && name == skipped => skipping = None,
+			None if !tag.starts_with('/') => {
+				skipping = ["code", "pre", "script", "style", "kbd", "samp"].into_iter().find(|t| *t == name);
+			},
+			_ => (),
+		}
+
+		rest = &rest[open + close + 1..];
+	}
+
+	if skipping.is_none() {
+		text.push_str(rest);
+	}
+
+	let mut prose = String::new();
+	let mut rest = text.as_str();
+
+	while let Some(amp) = rest.find('&') {
+		prose.push_str(&rest[..amp]);
+		prose.push(' ');
+		rest = &rest[amp + 1..];
+
+		if let Some(semi) = rest.find(';').filter(|semi| rest[..*semi].chars().all(|c| c.is_ascii_alphanumeric() || c == '#')) {
+			rest = &rest[semi + 1..];
+		}
+	}
+	prose.push_str(rest);
+
+	prose
+}
+
+fn words(text: &str) -> impl Iterator<Item = &str> {
+	text.split(|c: char| !(c.is_alphabetic() || c == '\'' || c == '’'))
+		.map(|w| w.trim_matches(|c| c == '\'' || c == '’'))
+		.filter(|w| w.chars().count() > 1 && !w.chars().any(|c| c.is_ascii_digit()))
+}
+
+/// Emits a `cargo::warning` for every word in an HTML page's prose that isn't in `dictionary`,
+/// located at the first line of the page's source that has it.  Words that aren't in the source at
+/// all came from a layout, and aren't reported against every page that uses it.
+pub(crate) fn check(page: &RenderedResource, dictionary: &Dictionary) {
+	if !page.content_type.starts_with("text/html") {
+		return;
+	}
+
+	let Ok(source) = fs::read_to_string(&page.source) else { return };
+	let body = String::from_utf8_lossy(&page.body);
+	let mut reported = HashSet::new();
+
+	for word in words(&text(&body)) {
+		if dictionary.knows(word) || !reported.insert(word) {
+			continue;
+		}
+
+		if let Some(n) = source.lines().position(|line| words(line).any(|w| w == word)) {
+			report(&page.source, n + 1, word);
+		}
+	}
+}
+
+fn report(source: &Path, line: usize, word: &str) {
+	println!("cargo::warning={}:{line}: unknown word {word:?}", source.display());
+}
@@ -0,0 +1,111 @@
+// Elements whose content is kept exactly as it is
+const RAW_ELEMENTS: &[&str] = &["pre", "textarea", "script", "style"];
+
+// The raw element a tag at the start of `html` opens, if any
+fn raw_element(html: &str) -> Option<&'static str> {
+	RAW_ELEMENTS.iter().copied().find(|name| {
+		let tag = html.get(1..name.len() + 1);
+		let after = html[1..].chars().nth(name.len());
+
+		tag.is_some_and(|t| t.eq_ignore_ascii_case(name)) && after.is_none_or(|c| c == '>' || c == '/' || c.is_whitespace())
+	})
+}
+
+// Trims trailing whitespace from every line ending in `text`
+fn push_trimmed(out: &mut String, text: &str) {
+	let mut lines = text.split('\n').peekable();
+
+	while let Some(line) = lines.next() {
+		if lines.peek().is_some() {
+			out.push_str(line.trim_end_matches([' ', '\t', '\r']));
+			out.push('\n');
+		} else {
+			out.push_str(line);
+		}
+	}
+}
+
+// Collapses every run of whitespace in `text` to a single space, dropping it altogether where
+// what's already out ends with some
+fn push_collapsed(out: &mut String, text: &str) {
+	for c in text.chars() {
+		if !c.is_ascii_whitespace() {
+			out.push(c);
+		} else if !out.is_empty() && !out.ends_with(|c: char| c.is_ascii_whitespace()) {
+			out.push(' ');
+		}
+	}
+}
+
+/// A light cleanup of an HTML page, short of minifying it: comments go (except IE conditional
+/// comments), as does trailing whitespace on every line, leaving raw text elements untouched
+pub(crate) fn tidy(html: &str) -> String {
+	clean(html, false)
+}
+
+/// An HTML page minified: comments go (except IE conditional comments), and every run of
+/// whitespace between tags is collapsed to a single space, leaving raw text elements and the
+/// tags themselves untouched
+pub(crate) fn minify(html: &str) -> String {
+	clean(html, true)
+}
+
+fn clean(html: &str, minify: bool) -> String {
+	let push_text = if minify { push_collapsed } else { push_trimmed };
+	let mut out = String::with_capacity(html.len());
+	let mut rest = html;
+
+	while let Some(i) = rest.find('<') {
+		push_text(&mut out, &rest[..i]);
+		rest = &rest[i..];
+
+		if let Some(comment) = rest.strip_prefix("<!--") {
+			let end = comment.find("-->").map(|e| e + "-->".len()).unwrap_or(comment.len());
+
+			if comment.starts_with("[if") || comment.starts_with("<![endif]") {
+				out.push_str(&rest[.."<!--".len() + end]);
+				rest = &comment[end..];
+				continue;
+			}
+
+			rest = &comment[end..];
+			if minify {
+				continue;
+			}
+
+			// A comment on a line of its own takes the line with it
+			let line_start = out.rfind('\n').map(|n| n + 1).unwrap_or(0);
+			let line_end = rest.find('\n');
+			if out[line_start..].trim().is_empty() && line_end.is_some_and(|n| rest[..n].trim().is_empty()) {
+				out.truncate(line_start);
+				rest = &rest[line_end.unwrap_or_default() + 1..];
+			}
+		} else if let Some(name) = raw_element(rest) {
+			let close = format!("</{name}");
+			let end = rest.to_ascii_lowercase().find(&close).unwrap_or(rest.len());
+
+			out.push_str(&rest[..end]);
+			rest = &rest[end..];
+
+			// The closing tag itself, which mustn't be taken for another opening
+			let tag_end = rest.find('>').map(|e| e + 1).unwrap_or(rest.len());
+			out.push_str(&rest[..tag_end]);
+			rest = &rest[tag_end..];
+		} else {
+			out.push('<');
+			rest = &rest[1..];
+		}
+	}
+
+	push_text(&mut out, rest);
+
+	let trimmed = out.trim_end().len();
+	if minify {
+		out.truncate(trimmed);
+	} else if trimmed < out.len() {
+		out.truncate(trimmed);
+		out.push('\n');
+	}
+
+	out
+}
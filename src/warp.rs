@@ -0,0 +1,272 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::{codegen::{filtered_paths, path_checks, route_table, set_tables, Options, SetTables}, RenderedResource, Resource};
+
+// Runs before responding to `path` from `routes`: in dev builds, renders it on tokio's blocking pool
+fn prerender(routes: TokenStream, options: &Options<'_>) -> TokenStream {
+	if options.dev {
+		quote! {
+			let rendered = path.clone();
+			let _ = ::tokio::task::spawn_blocking(move || ::static_builder::serve::prerender(#routes, &rendered)).await;
+		}
+	} else {
+		quote! {}
+	}
+}
+
+fn content_sets(default_paths: &[String], sets: &[(&str, Vec<(Resource, RenderedResource)>)], options: &Options<'_>) -> Result<TokenStream, String> {
+	let SetTables { tables, routes_fn, names, paths } = set_tables(default_paths, sets, options)?;
+	let all_paths = paths.into_iter();
+	let prerender = prerender(quote! { routes }, options);
+
+	Ok(quote! {
+		#[derive(Clone)]
+		pub(crate) struct StaticContentSets {
+			select: ::std::sync::Arc<dyn Fn(&::warp::http::HeaderMap) -> Option<String> + Send + Sync>,
+		}
+
+		#[allow(dead_code)]
+		impl StaticContentSets {
+			pub(crate) const NAMES: &'static [&'static str] = &[#(#names),*];
+			pub(crate) const PATHS: &'static [&'static str] = &[#(#all_paths),*];
+
+			/// `select` names the content set to serve each request (by its headers) from; `None`,
+			/// or a name that isn't in `NAMES`, gets the default set (the one `StaticContent` serves)
+			pub(crate) fn new<F>(select: F) -> Self where F: Fn(&::warp::http::HeaderMap) -> Option<String> + Send + Sync + 'static {
+				StaticContentSets { select: ::std::sync::Arc::new(select) }
+			}
+
+			/// Always serves the named set, e.g. one chosen from configuration at startup
+			pub(crate) fn fixed<S>(name: S) -> Self where S: Into<String> {
+				let name = name.into();
+				Self::new(move |_| Some(name.clone()))
+			}
+
+			/// Serves the set named by the request's `Host`, less any port
+			pub(crate) fn by_host() -> Self {
+				Self::new(|headers| {
+					let host = headers.get(::warp::http::header::HOST)?.to_str().ok()?.to_ascii_lowercase();
+
+					Some(match host.rfind(':') {
+						Some(i) if !host[i..].contains(']') => host[..i].to_string(),
+						_ => host,
+					})
+				})
+			}
+
+			/// A filter answering every path in the selected set, and rejecting the rest
+			pub(crate) fn filter(self) -> impl ::warp::Filter<Extract = (::warp::reply::Response,), Error = ::warp::Rejection> + Clone {
+				use ::warp::Filter as _;
+
+				::warp::method().and(::warp::path::full()).and(::warp::header::headers_cloned()).and_then(move |method: ::warp::http::Method, path: ::warp::path::FullPath, headers: ::warp::http::HeaderMap| {
+					let set = (self.select)(&headers);
+
+					async move {
+						let routes = Self::routes(set.as_deref());
+						let path = path.as_str().to_owned();
+
+						if ::static_builder::serve::route(routes, &path).is_none() {
+							return Err(::warp::reject::not_found());
+						}
+						#prerender
+
+						Ok(static_content_http_response(static_content_respond(routes, method.as_str(), &path, |name| headers.get(name).and_then(|v| v.to_str().ok()))))
+					}
+				})
+			}
+
+			#routes_fn
+		}
+
+		#tables
+	})
+}
+
+// Checks of the HTTP semantics every route should have, run with the application's own tests
+fn http_tests(rendered: &[(Resource, RenderedResource)], options: &Options<'_>) -> TokenStream {
+	let filtered = filtered_paths(rendered);
+	let not_allowed = match options.allows("POST") {
+		true => quote! {},
+		false => quote! {
+			#[::tokio::test]
+			async fn other_methods_are_not_allowed() {
+				for path in paths() {
+					let res = test::request().method("POST").path(path).reply(&StaticContent::filter()).await;
+					assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED, "POST {path}");
+					assert!(res.headers().contains_key(header::ALLOW), "POST {path}");
+				}
+			}
+		},
+	};
+	let options_test = match options.allows("OPTIONS") {
+		true => quote! {
+			#[::tokio::test]
+			async fn options_lists_the_allowed_methods() {
+				for path in paths() {
+					let res = test::request().method("OPTIONS").path(path).reply(&StaticContent::filter()).await;
+					assert_eq!(res.status(), StatusCode::NO_CONTENT, "OPTIONS {path}");
+					assert!(res.headers().contains_key(header::ALLOW), "OPTIONS {path}");
+				}
+			}
+		},
+		false => quote! {},
+	};
+	let redirects: Vec<_> = rendered.iter().flat_map(|(_, page)| page.redirects.iter().map(|r| (r, &page.paths[0]))).collect();
+	let (redirect_from, redirect_to): (Vec<_>, Vec<_>) = redirects.into_iter().unzip();
+
+	quote! {
+		#[cfg(test)]
+		#[allow(clippy::unwrap_used, clippy::panic)]
+		mod static_content_http_tests {
+			use super::StaticContent;
+			use ::warp::{http::{header, StatusCode}, test};
+
+			const FILTERED: &[&str] = &[#(#filtered),*];
+
+			fn paths() -> impl Iterator<Item = &'static &'static str> {
+				StaticContent::PATHS.iter().filter(|path| !FILTERED.contains(path))
+			}
+
+			const REDIRECTS: &[(&str, &str)] = &[#((#redirect_from, #redirect_to)),*];
+
+			#[::tokio::test]
+			async fn every_route_answers_get_and_head() {
+				for path in paths() {
+					for method in ["GET", "HEAD"] {
+						let res = test::request().method(method).path(path).reply(&StaticContent::filter()).await;
+						assert!(res.status() == StatusCode::OK || res.status() == StatusCode::PERMANENT_REDIRECT, "{method} {path}: {}", res.status());
+					}
+				}
+			}
+
+			#not_allowed
+
+			#options_test
+
+			#[::tokio::test]
+			async fn redirects_point_at_their_canonical_route() {
+				for (from, to) in REDIRECTS {
+					let res = test::request().path(from).reply(&StaticContent::filter()).await;
+					assert_eq!(res.status(), StatusCode::PERMANENT_REDIRECT, "GET {from}");
+					assert_eq!(res.headers().get(header::LOCATION).unwrap(), to, "GET {from}");
+				}
+			}
+
+			#[::tokio::test]
+			async fn matching_etags_are_not_modified() {
+				for path in paths() {
+					let res = test::request().path(path).reply(&StaticContent::filter()).await;
+					let Some(etag) = res.headers().get(header::ETAG).cloned() else { continue };
+					let res = test::request().path(path).header(header::IF_NONE_MATCH, etag.clone()).reply(&StaticContent::filter()).await;
+					assert_eq!(res.status(), StatusCode::NOT_MODIFIED, "GET {path}");
+					assert_eq!(res.headers().get(header::ETAG), Some(&etag), "GET {path}");
+				}
+			}
+
+			#[::tokio::test]
+			async fn unchanged_routes_are_not_modified_since() {
+				for path in paths() {
+					let res = test::request().path(path).reply(&StaticContent::filter()).await;
+					let Some(modified) = res.headers().get(header::LAST_MODIFIED).cloned() else { continue };
+					let res = test::request().path(path).header(header::IF_MODIFIED_SINCE, modified).reply(&StaticContent::filter()).await;
+					assert_eq!(res.status(), StatusCode::NOT_MODIFIED, "GET {path}");
+				}
+			}
+
+			#[::tokio::test]
+			async fn ranges_are_partial_content() {
+				for path in paths() {
+					let res = test::request().path(path).header(header::RANGE, "bytes=0-0").reply(&StaticContent::filter()).await;
+					if res.status() == StatusCode::PERMANENT_REDIRECT { continue }
+					assert!(res.status() == StatusCode::PARTIAL_CONTENT || res.status() == StatusCode::RANGE_NOT_SATISFIABLE, "GET {path}: {}", res.status());
+					assert!(res.headers().contains_key(header::CONTENT_RANGE), "GET {path}");
+				}
+			}
+
+			#[::tokio::test]
+			async fn unknown_paths_are_not_found() {
+				let res = test::request().path("/static-builder-no-such-route").reply(&StaticContent::filter()).await;
+
+				assert_eq!(res.status(), StatusCode::NOT_FOUND);
+			}
+		}
+	}
+}
+
+pub(crate) fn warp_module(rendered: &[(Resource, RenderedResource)], sets: &[(&str, Vec<(Resource, RenderedResource)>)], options: &Options<'_>) -> Result<TokenStream, String> {
+	let (routes, resource_paths) = route_table("STATIC_CONTENT_ROUTES", rendered, options)?;
+	let passthrough = options.passthrough;
+	let panic_lints = options.panic_lints();
+	let respond = options.respond_fn();
+	let prerender = prerender(quote! { STATIC_CONTENT_ROUTES }, options);
+	let sets = if sets.is_empty() { quote! {} } else { content_sets(&resource_paths, sets, options)? };
+	let bodies = options.body_statics()?;
+	let tests = if options.tests { http_tests(rendered, options) } else { quote! {} };
+	let path_checks = path_checks(options);
+
+	Ok(quote! {
+		#[derive(Clone)]
+		pub(crate) struct StaticContent;
+
+		impl StaticContent {
+			pub(crate) const PATHS: &'static [&'static str] = &[#(#resource_paths),*];
+			/// Path prefixes never handled here, for another filter to respond to
+			pub(crate) const PASSTHROUGH: &'static [&'static str] = &[#(#passthrough),*];
+
+			pub(crate) fn passes_through(path: &str) -> bool {
+				Self::PASSTHROUGH.iter().any(|prefix| path.starts_with(prefix))
+			}
+
+			/// A filter answering every embedded path, and rejecting the rest so filters `or`ed
+			/// after it get them
+			pub(crate) fn filter() -> impl ::warp::Filter<Extract = (::warp::reply::Response,), Error = ::warp::Rejection> + Clone {
+				use ::warp::Filter as _;
+
+				::warp::method().and(::warp::path::full()).and(::warp::header::headers_cloned()).and_then(|method: ::warp::http::Method, path: ::warp::path::FullPath, headers: ::warp::http::HeaderMap| async move {
+					let path = path.as_str().to_owned();
+
+					if Self::passes_through(&path) || ::static_builder::serve::route(STATIC_CONTENT_ROUTES, &path).is_none() {
+						return Err(::warp::reject::not_found());
+					}
+					#prerender
+
+					Ok(static_content_http_response(static_content_respond(STATIC_CONTENT_ROUTES, method.as_str(), &path, |name| headers.get(name).and_then(|v| v.to_str().ok()))))
+				})
+			}
+		}
+
+		// What's served is all decided by `static_builder::serve`; this only hands it to warp
+		#panic_lints
+		fn static_content_http_response(res: ::static_builder::serve::Response) -> ::warp::reply::Response {
+			let mut builder = ::warp::http::Response::builder().status(res.status);
+
+			for (name, value) in res.headers {
+				builder = builder.header(name, value.into_owned());
+			}
+
+			let body = match res.body {
+				::std::borrow::Cow::Borrowed(body) => ::warp::hyper::Body::from(body),
+				::std::borrow::Cow::Owned(body) => ::warp::hyper::Body::from(body),
+			};
+
+			builder.body(body).unwrap_or_else(|_| {
+				let mut res = ::warp::reply::Response::default();
+				*res.status_mut() = ::warp::http::StatusCode::INTERNAL_SERVER_ERROR;
+				res
+			})
+		}
+
+		#respond
+
+		#routes
+
+		#sets
+
+		#bodies
+
+		#path_checks
+
+		#tests
+	})
+}
@@ -0,0 +1,149 @@
+use std::{collections::{BTreeMap, BTreeSet}, convert::Infallible, fs, path::{Path, PathBuf}, thread, time::{Duration, SystemTime, UNIX_EPOCH}};
+#[cfg(feature = "watch")]
+use std::{ops::ControlFlow, sync::mpsc};
+use walkdir::WalkDir;
+
+#[cfg(feature = "watch")]
+use crate::Builder;
+
+// How long `watch` lets writes settle (an editor's save is often several) before rendering again
+#[cfg(feature = "watch")]
+const SETTLE: Duration = Duration::from_millis(100);
+
+// Each embedded route's body, by content set and path
+#[cfg(feature = "watch")]
+pub(crate) type Routes = BTreeMap<(Option<String>, String), Vec<u8>>;
+
+#[cfg(feature = "watch")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RouteChange {
+	/// The content set the route is in, or `None` for the builder's own
+	pub content_set: Option<String>,
+	pub route: String,
+	/// `None` when the route has gone away
+	pub body: Option<Vec<u8>>,
+}
+
+fn snapshot(dirs: &[&Path]) -> BTreeMap<PathBuf, Option<SystemTime>> {
+	let mut files = BTreeMap::new();
+
+	for dir in dirs {
+		for entry in WalkDir::new(dir).into_iter().filter_map(Result::ok) {
+			if entry.file_type().is_file() {
+				files.insert(entry.path().to_path_buf(), entry.metadata().ok().and_then(|m| m.modified().ok()));
+			}
+		}
+	}
+
+	files
+}
+
+#[cfg(feature = "watch")]
+fn diff_routes(old: &Routes, new: &Routes) -> Vec<RouteChange> {
+	let mut changes = vec![];
+
+	for ((content_set, route), body) in new {
+		if old.get(&(content_set.clone(), route.clone())) != Some(body) {
+			changes.push(RouteChange { content_set: content_set.clone(), route: route.clone(), body: Some(body.clone()) });
+		}
+	}
+
+	for (content_set, route) in old.keys() {
+		if !new.contains_key(&(content_set.clone(), route.clone())) {
+			changes.push(RouteChange { content_set: content_set.clone(), route: route.clone(), body: None });
+		}
+	}
+
+	changes
+}
+
+// Watches what there is of `paths`, but not what's in a directory already watched
+#[cfg(feature = "watch")]
+fn rewatch(watcher: &mut notify::RecommendedWatcher, watched: &mut Vec<PathBuf>, paths: &[PathBuf]) -> Result<(), String> {
+	use notify::{RecursiveMode, Watcher as _};
+
+	let covered = |path: &PathBuf| paths.iter().any(|dir| dir != path && dir.is_dir() && path.starts_with(dir));
+	let wanted: Vec<PathBuf> = paths.iter().filter(|path| path.exists() && !covered(path)).cloned().collect();
+
+	for path in watched.iter().filter(|path| !wanted.contains(path)) {
+		// Gone already, if it was deleted
+		let _ = watcher.unwatch(path);
+	}
+	for path in wanted.iter().filter(|path| !watched.contains(path)) {
+		watcher.watch(path, RecursiveMode::Recursive).map_err(|e| format!("failed to watch {}: {e}", path.display()))?;
+	}
+	*watched = wanted;
+
+	Ok(())
+}
+
+/// Renders everything `builder` would embed with `Builder::write` (content sets and all), hands
+/// every route to `on_change`, and then watches (with the `watch` feature) whatever that was
+/// rendered from: the content, each rule's layouts and partials, and the files stylesheets load.
+/// On each change it renders again, reporting only the routes whose bodies changed.  Render
+/// failures are passed along instead of ending the watch, so a typo mid-edit doesn't kill a dev
+/// server; the previous bodies stay current until the next good render.  Returns once `on_change`
+/// breaks, or with an error if the files can't be watched.
+#[cfg(feature = "watch")]
+pub fn watch<F>(mut builder: Builder, mut on_change: F) -> Result<(), String> where F: FnMut(Result<Vec<RouteChange>, String>) -> ControlFlow<()> {
+	let (events, changes) = mpsc::channel();
+	let mut watcher = notify::recommended_watcher(events).map_err(|e| format!("failed to watch for changes: {e}"))?;
+	let mut watched = vec![];
+	let mut routes = BTreeMap::new();
+
+	loop {
+		let result = match builder.routes() {
+			Ok((new_routes, read)) => {
+				rewatch(&mut watcher, &mut watched, &read)?;
+				let changes = diff_routes(&routes, &new_routes);
+				routes = new_routes;
+				Ok(changes)
+			},
+			Err(e) => Err(e),
+		};
+
+		if !matches!(&result, Ok(changes) if changes.is_empty()) && on_change(result).is_break() {
+			return Ok(());
+		}
+
+		// Rendering opens every file, which isn't a change
+		loop {
+			match changes.recv() {
+				Ok(Ok(event)) if event.kind.is_access() => {},
+				Ok(_) => break,
+				Err(_) => return Err("stopped watching for changes".to_string()),
+			}
+		}
+		while changes.recv_timeout(SETTLE).is_ok() {}
+	}
+}
+
+// What a dev build's generated code depends on: which files there are, and what's in those it
+// embeds
+fn structure<P>(dirs: &[P], rebuild_on: &[P]) -> (BTreeSet<PathBuf>, Vec<Option<SystemTime>>) where P: AsRef<Path> {
+	let dirs: Vec<&Path> = dirs.iter().map(AsRef::as_ref).collect();
+	let files = snapshot(&dirs).into_keys().collect();
+	let modified = rebuild_on.iter().map(|file| fs::metadata(file).and_then(|m| m.modified()).ok()).collect();
+
+	(files, modified)
+}
+/// Polls `dirs` every `interval`, and writes to `stamp` whenever a file is added to or removed from
+/// any of them, or any of `rebuild_on` changes, but not for edits to anything else, which a dev
+/// build serves from disk as it is.  With cargo-watch (or bacon) ignoring the content directories
+/// but watching `stamp`, only changes that need the generated module regenerated rebuild and
+/// restart the application.  This is what the generated `StaticContent::watch_for_rebuild` runs,
+/// given everything the build script read; it only returns on failing to write `stamp`.
+pub fn watch_for_rebuild<P>(dirs: &[P], rebuild_on: &[P], stamp: &Path, interval: Duration) -> Result<Infallible, String> where P: AsRef<Path> {
+	let mut seen = structure(dirs, rebuild_on);
+
+	loop {
+		thread::sleep(interval);
+
+		let current = structure(dirs, rebuild_on);
+		if current != seen {
+			seen = current;
+			let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+			fs::write(stamp, format!("{now}\n")).map_err(|e| format!("failed to write {}: {e}", stamp.display()))?;
+		}
+	}
+}